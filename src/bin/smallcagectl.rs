@@ -0,0 +1,116 @@
+//! Send a command to a running smallcage over its IPC socket and print the
+//! replies as JSON, one per line.
+
+#[allow(dead_code)]
+#[path = "../ipc/protocol.rs"]
+mod protocol;
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use protocol::{socket_path, Reply, Request, SplitDirection, SOCKET_ENV};
+
+const USAGE: &str = "USAGE: smallcagectl COMMAND
+
+Commands:
+\twindows : List the windows with their title, geometry and state.
+\tfocus ID : Focus a window.
+\tclose ID : Ask a window to close.
+\tsplit horizontal|vertical : Set the split direction of new windows.
+\tworkspace N : Switch the current output to a workspace.
+\taction ACTION : Run an action, as written in the keybindings of the config.
+\tkeyboard-layout : Print the active keyboard layout.
+\tsubscribe : Print focus changes, opened and closed windows and layout switches.";
+
+fn parse_request(args: &[String]) -> Result<Request, String> {
+    let id = || -> Result<u64, String> {
+        args.get(1)
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(|| format!("`{}` needs a window id", args[0]))
+    };
+    let request = match args.first().map(String::as_str) {
+        Some("windows") => Request::Windows,
+        Some("focus") => Request::Focus { id: id()? },
+        Some("close") => Request::Close { id: id()? },
+        Some("split") => Request::Split {
+            direction: match args.get(1).map(String::as_str) {
+                Some("horizontal") => SplitDirection::Horizontal,
+                Some("vertical") => SplitDirection::Vertical,
+                _ => return Err("`split` needs horizontal or vertical".to_owned()),
+            },
+        },
+        Some("workspace") => Request::Workspace {
+            index: args
+                .get(1)
+                .and_then(|index| index.parse().ok())
+                .ok_or("`workspace` needs a number")?,
+        },
+        Some("action") if args.len() > 1 => Request::Action {
+            action: args[1..].join(" "),
+        },
+        Some("action") => return Err("`action` needs an action".to_owned()),
+        Some("keyboard-layout") => Request::KeyboardLayout,
+        Some("subscribe") => Request::Subscribe,
+        Some(other) => return Err(format!("Unknown command `{}`", other)),
+        None => return Err(USAGE.to_owned()),
+    };
+    Ok(request)
+}
+
+/// `$SMALLCAGE_SOCKET`, or the socket next to `$WAYLAND_DISPLAY`
+fn find_socket() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(SOCKET_ENV) {
+        return Some(PathBuf::from(path));
+    }
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")?;
+    let display = std::env::var_os("WAYLAND_DISPLAY")?;
+    Some(socket_path(runtime_dir.as_ref(), &display))
+}
+
+fn run(request: &Request) -> Result<(), Box<dyn std::error::Error>> {
+    let path = find_socket().ok_or("Neither SMALLCAGE_SOCKET nor WAYLAND_DISPLAY is set")?;
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|err| format!("Failed to connect to {}: {}", path.display(), err))?;
+    let mut line = serde_json::to_vec(request)?;
+    line.push(b'\n');
+    stream.write_all(&line)?;
+
+    let mut lines = BufReader::new(stream).lines();
+    let reply = lines
+        .next()
+        .ok_or("The compositor closed the connection")??;
+    if let Reply::Error { message } = serde_json::from_str(&reply)? {
+        return Err(message.into());
+    }
+    if !matches!(request, Request::Subscribe) {
+        println!("{}", reply);
+        return Ok(());
+    }
+    // the events follow the reply until the compositor goes away
+    for event in lines {
+        println!("{}", event?);
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let request = match parse_request(&args) {
+        Ok(request) => request,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE;
+        }
+    };
+    match run(&request) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
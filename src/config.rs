@@ -0,0 +1,1033 @@
+//! The config file, `$XDG_CONFIG_HOME/smallcage/config.toml`. It is loaded
+//! again whenever it changes, or on the `reload-config` keybinding:
+//!
+//! ```toml
+//! gaps = 8
+//! default-split = "vertical"
+//! autostart = ["kitty", "mako"]
+//! screenshot-dir = "~/Pictures/screenshots"
+//!
+//! [keybindings]
+//! "Super+Return" = "run foot"
+//! "Super+Shift+q" = "quit"
+//! "Super+l" = "none"
+//!
+//! [keyboard]
+//! layout = "us,de"
+//! variant = ",nodeadkeys"
+//! options = "ctrl:nocaps"
+//! repeat-delay = 300
+//! repeat-rate = 30
+//!
+//! # every matching entry is applied to a libinput device, later ones win
+//! [[input]]
+//! type = "touchpad"
+//! tap-to-click = true
+//! natural-scroll = true
+//! scroll-method = "two-finger"
+//! disable-while-typing = true
+//!
+//! [[input]]
+//! type = "touch"
+//! map-to-output = "eDP-1"
+//!
+//! [[input]]
+//! name = "Logitech G502"
+//! accel-profile = "flat"
+//! accel-speed = -0.2
+//! left-handed = false
+//!
+//! [theme]
+//! background = "#ccccdd"
+//! header-bar = "#bfe6c7"
+//! close-button = "#ffa89c"
+//! header-bar-height = 30
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use serde::Deserialize;
+use smithay::{
+    input::keyboard::{keysyms as xkb, xkb as xkbcommon, ModifiersState, XkbConfig},
+    output::Output,
+    reexports::calloop::{
+        timer::{TimeoutAction, Timer},
+        LoopHandle,
+    },
+    reexports::input::{AccelProfile, ScrollMethod},
+};
+use toml::Spanned;
+
+use crate::{
+    drawing::CLEAR_COLOR,
+    handlers::{BG_COLOR, CLOSE_COLOR, FULLSCREEN_COLOR, HEADER_BAR_HEIGHT, STATE_CHANGE_COLOR},
+    input::KeyAction,
+    screenshot::ScreenshotMode,
+    state::{Backend, SmallCageState, SplitState},
+    workspace::WORKSPACE_COUNT,
+    CalloopData,
+};
+
+const CONFIG_FILE: &str = "config.toml";
+/// How often the config file is checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The keybindings used when the config file does not change them
+const DEFAULT_KEYBINDINGS: &[(&str, &str)] = &[
+    ("Ctrl+Alt+BackSpace", "quit"),
+    ("Super+q", "quit"),
+    ("Super+Return", "run kitty"),
+    ("Super+l", "run utena"),
+    ("Super+1", "workspace 1"),
+    ("Super+2", "workspace 2"),
+    ("Super+3", "workspace 3"),
+    ("Super+4", "workspace 4"),
+    ("Super+5", "workspace 5"),
+    ("Super+6", "workspace 6"),
+    ("Super+7", "workspace 7"),
+    ("Super+8", "workspace 8"),
+    ("Super+9", "workspace 9"),
+    ("Super+Shift+1", "move-to-workspace 1"),
+    ("Super+Shift+2", "move-to-workspace 2"),
+    ("Super+Shift+3", "move-to-workspace 3"),
+    ("Super+Shift+4", "move-to-workspace 4"),
+    ("Super+Shift+5", "move-to-workspace 5"),
+    ("Super+Shift+6", "move-to-workspace 6"),
+    ("Super+Shift+7", "move-to-workspace 7"),
+    ("Super+Shift+8", "move-to-workspace 8"),
+    ("Super+Shift+9", "move-to-workspace 9"),
+    ("Super+p", "toggle-floating"),
+    ("Super+Shift+p", "scale-up"),
+    ("Super+Shift+m", "scale-down"),
+    ("Super+Shift+w", "toggle-preview"),
+    ("Super+Shift+r", "rotate-output"),
+    ("Super+Shift+t", "toggle-tint"),
+    ("Super+v", "split-vertical"),
+    ("Super+b", "split-horizontal"),
+    ("Super+space", "cycle-layout"),
+    ("Super+Shift+space", "next-keyboard-layout"),
+    ("Super+Shift+c", "reload-config"),
+    ("Print", "screenshot output"),
+    ("Super+Print", "screenshot window"),
+    ("Shift+Print", "screenshot region"),
+];
+
+/// A key together with the modifiers which have to be held for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyCombo {
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+    logo: bool,
+    /// The keysym without the modifiers applied
+    keysym: u32,
+}
+
+impl KeyCombo {
+    pub fn matches(&self, modifiers: &ModifiersState, raw_keysym: u32) -> bool {
+        self.keysym == raw_keysym
+            && self.ctrl == modifiers.ctrl
+            && self.alt == modifiers.alt
+            && self.shift == modifiers.shift
+            && self.logo == modifiers.logo
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// The colour behind the windows
+    pub background: [f32; 4],
+    pub header_bar: [f32; 4],
+    pub state_button: [f32; 4],
+    pub fullscreen_button: [f32; 4],
+    pub close_button: [f32; 4],
+    pub header_bar_height: i32,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            background: CLEAR_COLOR,
+            header_bar: BG_COLOR,
+            state_button: STATE_CHANGE_COLOR,
+            fullscreen_button: FULLSCREEN_COLOR,
+            close_button: CLOSE_COLOR,
+            header_bar_height: HEADER_BAR_HEIGHT,
+        }
+    }
+}
+
+/// The XKB names of the keymap, empty ones are taken from the
+/// `XKB_DEFAULT_*` variables
+#[derive(Debug, Clone)]
+pub struct Keyboard {
+    pub rules: String,
+    pub model: String,
+    /// Several layouts are separated by commas
+    pub layout: String,
+    pub variant: String,
+    pub options: Option<String>,
+    /// Milliseconds a key is held before it repeats
+    pub repeat_delay: i32,
+    /// Repeats per second
+    pub repeat_rate: i32,
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self {
+            rules: String::new(),
+            model: String::new(),
+            layout: String::new(),
+            variant: String::new(),
+            options: None,
+            repeat_delay: 200,
+            repeat_rate: 200,
+        }
+    }
+}
+
+impl Keyboard {
+    pub fn xkb_config(&self) -> XkbConfig<'_> {
+        XkbConfig {
+            rules: &self.rules,
+            model: &self.model,
+            layout: &self.layout,
+            variant: &self.variant,
+            options: self.options.clone(),
+        }
+    }
+
+    /// The name of the layout with `index` in the keymap
+    pub fn layout_name(&self, index: usize) -> Option<&str> {
+        self.layout
+            .split(',')
+            .map(str::trim)
+            .nth(index)
+            .filter(|name| !name.is_empty())
+    }
+
+    /// Whether xkbcommon can build a keymap from the names
+    fn compiles(&self) -> bool {
+        let context = xkbcommon::Context::new(xkbcommon::CONTEXT_NO_FLAGS);
+        xkbcommon::Keymap::new_from_names(
+            &context,
+            &self.rules,
+            &self.model,
+            &self.layout,
+            &self.variant,
+            self.options.clone(),
+            xkbcommon::KEYMAP_COMPILE_NO_FLAGS,
+        )
+        .is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceType {
+    Keyboard,
+    Pointer,
+    Touchpad,
+    Touch,
+    Tablet,
+}
+
+/// libinput settings for the devices with a name or of a type, the ones
+/// which are not given are left as they are
+#[derive(Debug, Clone, Default)]
+pub struct InputDevice {
+    pub name: Option<String>,
+    pub device_type: Option<DeviceType>,
+    pub tap_to_click: Option<bool>,
+    pub natural_scroll: Option<bool>,
+    pub accel_profile: Option<AccelProfile>,
+    /// From -1 for the slowest to 1 for the fastest
+    pub accel_speed: Option<f64>,
+    pub left_handed: Option<bool>,
+    pub disable_while_typing: Option<bool>,
+    pub scroll_method: Option<ScrollMethod>,
+    /// The output absolute devices like touchscreens are mapped onto
+    pub map_to_output: Option<String>,
+}
+
+impl InputDevice {
+    pub fn matches(&self, name: &str, device_types: &[DeviceType]) -> bool {
+        self.name.as_deref().map_or(true, |n| n == name)
+            && self
+                .device_type
+                .map_or(true, |device_type| device_types.contains(&device_type))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub keybindings: Vec<(KeyCombo, KeyAction)>,
+    pub theme: Theme,
+    pub keyboard: Keyboard,
+    pub input: Vec<InputDevice>,
+    /// The space around and between tiled windows
+    pub gaps: i32,
+    /// The split direction new windows start with
+    pub default_split: SplitState,
+    /// Commands started once the compositor is up
+    pub autostart: Vec<String>,
+    /// Where screenshots are saved, `~/Pictures` if unset
+    pub screenshot_dir: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let keybindings = DEFAULT_KEYBINDINGS
+            .iter()
+            .map(|(combo, action)| {
+                (
+                    parse_key_combo(combo).expect("invalid default key combo"),
+                    parse_action(action).expect("invalid default action"),
+                )
+            })
+            .collect();
+        Self {
+            keybindings,
+            theme: Theme::default(),
+            keyboard: Keyboard::default(),
+            input: Vec::new(),
+            gaps: 0,
+            default_split: SplitState::default(),
+            autostart: vec!["kitty".to_owned()],
+            screenshot_dir: None,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read {}: {source}", .path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// One message for every invalid entry, with the line it is on
+    #[error("{}", .0.join("\n"))]
+    Invalid(Vec<String>),
+}
+
+// The file as it is written, every value keeps its place for the errors
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct ConfigFile {
+    keybindings: BTreeMap<String, Spanned<String>>,
+    theme: ThemeFile,
+    keyboard: Option<Spanned<KeyboardFile>>,
+    input: Vec<InputFile>,
+    gaps: Option<Spanned<i32>>,
+    default_split: Option<Spanned<String>>,
+    autostart: Option<Vec<String>>,
+    screenshot_dir: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct ThemeFile {
+    background: Option<Spanned<String>>,
+    header_bar: Option<Spanned<String>>,
+    state_button: Option<Spanned<String>>,
+    fullscreen_button: Option<Spanned<String>>,
+    close_button: Option<Spanned<String>>,
+    header_bar_height: Option<Spanned<i32>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct KeyboardFile {
+    rules: Option<String>,
+    model: Option<String>,
+    layout: Option<String>,
+    variant: Option<String>,
+    options: Option<String>,
+    repeat_delay: Option<Spanned<i32>>,
+    repeat_rate: Option<Spanned<i32>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct InputFile {
+    name: Option<String>,
+    #[serde(rename = "type")]
+    device_type: Option<Spanned<String>>,
+    tap_to_click: Option<bool>,
+    natural_scroll: Option<bool>,
+    accel_profile: Option<Spanned<String>>,
+    accel_speed: Option<Spanned<f64>>,
+    left_handed: Option<bool>,
+    disable_while_typing: Option<bool>,
+    scroll_method: Option<Spanned<String>>,
+    map_to_output: Option<String>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/smallcage/config.toml`, or `~/.config/smallcage/config.toml`
+    pub fn path() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("smallcage").join(CONFIG_FILE))
+    }
+
+    /// Load the config file, the defaults are used if there is none
+    pub fn load() -> Result<Config, ConfigError> {
+        let Some(path) = Self::path() else {
+            return Ok(Config::default());
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(content) => Self::parse(&path, &content),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(source) => Err(ConfigError::Io { path, source }),
+        }
+    }
+
+    fn parse(path: &Path, content: &str) -> Result<Config, ConfigError> {
+        let location = |span: std::ops::Range<usize>| {
+            let line = content[..span.start.min(content.len())]
+                .matches('\n')
+                .count()
+                + 1;
+            format!("{}:{}", path.display(), line)
+        };
+
+        let file: ConfigFile = toml::from_str(content).map_err(|err| {
+            let message = match err.span() {
+                Some(span) => format!("{}: {}", location(span), err.message()),
+                None => format!("{}: {}", path.display(), err.message()),
+            };
+            ConfigError::Invalid(vec![message])
+        })?;
+
+        let mut errors = Vec::new();
+        let mut config = Config::default();
+
+        for (combo, action) in file.keybindings.iter() {
+            let parsed = parse_key_combo(combo).and_then(|combo| {
+                if action.get_ref() == "none" {
+                    return Ok((combo, None));
+                }
+                Ok((combo, Some(parse_action(action.get_ref())?)))
+            });
+            match parsed {
+                Ok((combo, action)) => {
+                    // the file replaces the default binding of the same keys
+                    config.keybindings.retain(|(c, _)| *c != combo);
+                    if let Some(action) = action {
+                        config.keybindings.push((combo, action));
+                    }
+                }
+                Err(message) => errors.push(format!(
+                    "{}: keybinding `{}`: {}",
+                    location(action.span()),
+                    combo,
+                    message
+                )),
+            }
+        }
+
+        let theme = &mut config.theme;
+        for (value, target) in [
+            (&file.theme.background, &mut theme.background),
+            (&file.theme.header_bar, &mut theme.header_bar),
+            (&file.theme.state_button, &mut theme.state_button),
+            (&file.theme.fullscreen_button, &mut theme.fullscreen_button),
+            (&file.theme.close_button, &mut theme.close_button),
+        ] {
+            let Some(value) = value else {
+                continue;
+            };
+            match parse_color(value.get_ref()) {
+                Ok(color) => *target = color,
+                Err(message) => errors.push(format!("{}: {}", location(value.span()), message)),
+            }
+        }
+        if let Some(height) = &file.theme.header_bar_height {
+            if *height.get_ref() > 0 {
+                theme.header_bar_height = *height.get_ref();
+            } else {
+                errors.push(format!(
+                    "{}: header-bar-height must be greater than 0",
+                    location(height.span())
+                ));
+            }
+        }
+
+        if let Some(keyboard_file) = &file.keyboard {
+            let keyboard = &mut config.keyboard;
+            let names = keyboard_file.get_ref();
+            for (value, target) in [
+                (&names.rules, &mut keyboard.rules),
+                (&names.model, &mut keyboard.model),
+                (&names.layout, &mut keyboard.layout),
+                (&names.variant, &mut keyboard.variant),
+            ] {
+                if let Some(value) = value {
+                    *target = value.clone();
+                }
+            }
+            keyboard.options = names.options.clone();
+            if !keyboard.compiles() {
+                errors.push(format!(
+                    "{}: xkbcommon can not build a keymap from this keyboard",
+                    location(keyboard_file.span())
+                ));
+            }
+
+            for (value, target, name) in [
+                (
+                    &names.repeat_delay,
+                    &mut keyboard.repeat_delay,
+                    "repeat-delay",
+                ),
+                (&names.repeat_rate, &mut keyboard.repeat_rate, "repeat-rate"),
+            ] {
+                let Some(value) = value else {
+                    continue;
+                };
+                if *value.get_ref() >= 0 {
+                    *target = *value.get_ref();
+                } else {
+                    errors.push(format!(
+                        "{}: {} can not be negative",
+                        location(value.span()),
+                        name
+                    ));
+                }
+            }
+        }
+
+        for input in file.input.iter() {
+            let mut device = InputDevice {
+                name: input.name.clone(),
+                tap_to_click: input.tap_to_click,
+                natural_scroll: input.natural_scroll,
+                left_handed: input.left_handed,
+                disable_while_typing: input.disable_while_typing,
+                map_to_output: input.map_to_output.clone(),
+                ..Default::default()
+            };
+            if let Some(device_type) = &input.device_type {
+                match device_type.get_ref().as_str() {
+                    "keyboard" => device.device_type = Some(DeviceType::Keyboard),
+                    "pointer" => device.device_type = Some(DeviceType::Pointer),
+                    "touchpad" => device.device_type = Some(DeviceType::Touchpad),
+                    "touch" => device.device_type = Some(DeviceType::Touch),
+                    "tablet" => device.device_type = Some(DeviceType::Tablet),
+                    other => errors.push(format!(
+                        "{}: unknown device type `{}`, expected `keyboard`, `pointer`, `touchpad`, `touch` or `tablet`",
+                        location(device_type.span()),
+                        other
+                    )),
+                }
+            }
+            if let Some(profile) = &input.accel_profile {
+                match profile.get_ref().as_str() {
+                    "flat" => device.accel_profile = Some(AccelProfile::Flat),
+                    "adaptive" => device.accel_profile = Some(AccelProfile::Adaptive),
+                    other => errors.push(format!(
+                        "{}: unknown acceleration profile `{}`, expected `flat` or `adaptive`",
+                        location(profile.span()),
+                        other
+                    )),
+                }
+            }
+            if let Some(speed) = &input.accel_speed {
+                if (-1.0..=1.0).contains(speed.get_ref()) {
+                    device.accel_speed = Some(*speed.get_ref());
+                } else {
+                    errors.push(format!(
+                        "{}: accel-speed has to be from -1 to 1",
+                        location(speed.span())
+                    ));
+                }
+            }
+            if let Some(method) = &input.scroll_method {
+                match method.get_ref().as_str() {
+                    "none" => device.scroll_method = Some(ScrollMethod::NoScroll),
+                    "two-finger" => device.scroll_method = Some(ScrollMethod::TwoFinger),
+                    "edge" => device.scroll_method = Some(ScrollMethod::Edge),
+                    "on-button-down" => device.scroll_method = Some(ScrollMethod::OnButtonDown),
+                    other => errors.push(format!(
+                        "{}: unknown scroll method `{}`, expected `none`, `two-finger`, `edge` or `on-button-down`",
+                        location(method.span()),
+                        other
+                    )),
+                }
+            }
+            config.input.push(device);
+        }
+
+        if let Some(gaps) = &file.gaps {
+            if *gaps.get_ref() >= 0 {
+                config.gaps = *gaps.get_ref();
+            } else {
+                errors.push(format!(
+                    "{}: gaps can not be negative",
+                    location(gaps.span())
+                ));
+            }
+        }
+
+        if let Some(split) = &file.default_split {
+            match split.get_ref().as_str() {
+                "horizontal" => config.default_split = SplitState::HSplit,
+                "vertical" => config.default_split = SplitState::VSplit,
+                other => errors.push(format!(
+                    "{}: unknown split direction `{}`, expected `horizontal` or `vertical`",
+                    location(split.span()),
+                    other
+                )),
+            }
+        }
+
+        if let Some(autostart) = file.autostart {
+            config.autostart = autostart;
+        }
+
+        if let Some(dir) = file.screenshot_dir {
+            config.screenshot_dir = Some(expand_home(&dir));
+        }
+
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError::Invalid(errors))
+        }
+    }
+}
+
+/// Replace a leading `~` with the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(home).join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Parse combos like `Super+Shift+Return`, the key comes last
+fn parse_key_combo(text: &str) -> Result<KeyCombo, String> {
+    let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+    let key = parts
+        .pop()
+        .filter(|key| !key.is_empty())
+        .ok_or("no key given")?;
+
+    let mut combo = KeyCombo {
+        ctrl: false,
+        alt: false,
+        shift: false,
+        logo: false,
+        keysym: 0,
+    };
+    for modifier in parts {
+        match modifier.to_lowercase().as_str() {
+            "ctrl" | "control" => combo.ctrl = true,
+            "alt" | "mod1" => combo.alt = true,
+            "shift" => combo.shift = true,
+            "super" | "logo" | "mod4" => combo.logo = true,
+            _ => return Err(format!("unknown modifier `{}`", modifier)),
+        }
+    }
+
+    // keys are matched without shift, so single letters are always lower case
+    let key = if key.chars().count() == 1 {
+        key.to_lowercase()
+    } else {
+        key.to_owned()
+    };
+    let keysym: u32 = xkbcommon::keysym_from_name(&key, xkbcommon::KEYSYM_NO_FLAGS).into();
+    if keysym == xkb::KEY_NoSymbol {
+        return Err(format!("unknown key `{}`", key));
+    }
+    combo.keysym = keysym;
+    Ok(combo)
+}
+
+pub fn parse_action(text: &str) -> Result<KeyAction, String> {
+    let (name, argument) = match text.trim().split_once(char::is_whitespace) {
+        Some((name, argument)) => (name, Some(argument.trim())),
+        None => (text.trim(), None),
+    };
+    let workspace = || -> Result<usize, String> {
+        argument
+            .and_then(|n| n.parse::<usize>().ok())
+            .filter(|n| (1..=WORKSPACE_COUNT).contains(n))
+            .map(|n| n - 1)
+            .ok_or_else(|| format!("`{}` needs a workspace from 1 to {}", name, WORKSPACE_COUNT))
+    };
+    let action = match name {
+        "quit" => KeyAction::Quit,
+        "run" => match argument {
+            Some(command) if !command.is_empty() => KeyAction::Run(command.to_owned()),
+            _ => return Err("`run` needs a command".to_owned()),
+        },
+        "toggle-floating" => KeyAction::ChangeElementState,
+        "split-horizontal" => KeyAction::ChangeSplitSate(SplitState::HSplit),
+        "split-vertical" => KeyAction::ChangeSplitSate(SplitState::VSplit),
+        "workspace" => KeyAction::Screen(workspace()?),
+        "move-to-workspace" => KeyAction::MoveToScreen(workspace()?),
+        "scale-up" => KeyAction::ScaleUp,
+        "scale-down" => KeyAction::ScaleDown,
+        "toggle-preview" => KeyAction::TogglePreview,
+        "rotate-output" => KeyAction::RotateOutput,
+        "toggle-tint" => KeyAction::ToggleTint,
+        "cycle-layout" => KeyAction::CycleLayout,
+        "reload-config" => KeyAction::ReloadConfig,
+        "next-keyboard-layout" => KeyAction::NextKeyboardLayout,
+        "screenshot" => KeyAction::Screenshot(match argument {
+            None | Some("output") => ScreenshotMode::Output,
+            Some("window") => ScreenshotMode::Window,
+            Some("region") => ScreenshotMode::Region,
+            Some(other) => {
+                return Err(format!(
+                    "unknown screenshot mode `{}`, expected `output`, `window` or `region`",
+                    other
+                ))
+            }
+        }),
+        other => return Err(format!("unknown action `{}`", other)),
+    };
+    if argument.is_some()
+        && !matches!(
+            name,
+            "run" | "workspace" | "move-to-workspace" | "screenshot"
+        )
+    {
+        return Err(format!("`{}` takes no argument", name));
+    }
+    Ok(action)
+}
+
+/// Parse `#rrggbb` or `#rrggbbaa`
+fn parse_color(text: &str) -> Result<[f32; 4], String> {
+    let invalid = || {
+        format!(
+            "invalid colour `{}`, expected `#rrggbb` or `#rrggbbaa`",
+            text
+        )
+    };
+    let hex = text.strip_prefix('#').ok_or_else(invalid)?;
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let mut color = [1.0; 4];
+    for (i, channel) in color.iter_mut().enumerate().take(hex.len() / 2) {
+        let value = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+        *channel = value as f32 / 255.0;
+    }
+    Ok(color)
+}
+
+static CONFIG: RwLock<Option<Arc<Config>>> = RwLock::new(None);
+
+/// The config in use
+pub fn config() -> Arc<Config> {
+    CONFIG
+        .read()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| Arc::new(Config::default()))
+}
+
+pub fn set_config(config: Config) {
+    *CONFIG.write().unwrap() = Some(Arc::new(config));
+}
+
+fn config_modified() -> Option<SystemTime> {
+    std::fs::metadata(Config::path()?)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Reload the config when its file changes, polled once a second
+pub fn watch_config<BackendData: Backend + 'static>(
+    handle: &LoopHandle<'static, CalloopData<BackendData>>,
+) {
+    let mut last_modified = config_modified();
+    handle
+        .insert_source(Timer::from_duration(WATCH_INTERVAL), move |_, _, data| {
+            let modified = config_modified();
+            if modified != last_modified {
+                last_modified = modified;
+                data.state.reload_config();
+            }
+            TimeoutAction::ToDuration(WATCH_INTERVAL)
+        })
+        .expect("Failed to init the config watcher.");
+}
+
+impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
+    /// Load the config file again and apply it, an invalid file leaves the
+    /// current config in place
+    pub fn reload_config(&mut self) {
+        match Config::load() {
+            Ok(config) => set_config(config),
+            Err(err) => {
+                tracing::error!("Invalid config, keeping the current one:\n{}", err);
+                return;
+            }
+        }
+        tracing::info!("Reloaded the config");
+
+        self.apply_keyboard_config();
+        self.backend_data.configure_input_devices();
+        // the split keybindings change it until the next reload
+        self.splitstate = config().default_split;
+
+        // keybindings are looked up on every key press, only what was
+        // derived from the old config has to be redone
+        let outputs: Vec<Output> = self.space.outputs().cloned().collect();
+        let windows = self.space.elements().cloned().chain(
+            outputs
+                .iter()
+                .flat_map(|output| self.hidden_workspace_windows(output))
+                .map(|(_, window)| window),
+        );
+        for window in windows.collect::<Vec<_>>() {
+            window.invalidate_header_bar();
+        }
+        for output in outputs.iter() {
+            self.relayout_output(output);
+        }
+        // the clear colour is not part of the damage of a frame
+        self.backend_data.reset_buffers();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Result<Config, ConfigError> {
+        Config::parse(Path::new("config.toml"), content)
+    }
+
+    fn errors(content: &str) -> Vec<String> {
+        match parse(content) {
+            Err(ConfigError::Invalid(errors)) => errors,
+            other => panic!("expected an invalid config, got {:?}", other),
+        }
+    }
+
+    fn combo(ctrl: bool, alt: bool, shift: bool, logo: bool, keysym: u32) -> KeyCombo {
+        KeyCombo {
+            ctrl,
+            alt,
+            shift,
+            logo,
+            keysym,
+        }
+    }
+
+    #[test]
+    fn key_combo_with_modifiers() {
+        assert_eq!(
+            parse_key_combo("Super+Shift+Return"),
+            Ok(combo(false, false, true, true, xkb::KEY_Return))
+        );
+        assert_eq!(
+            parse_key_combo("control + Mod1 + BackSpace"),
+            Ok(combo(true, true, false, false, xkb::KEY_BackSpace))
+        );
+        assert_eq!(
+            parse_key_combo("Print"),
+            Ok(combo(false, false, false, false, xkb::KEY_Print))
+        );
+    }
+
+    #[test]
+    fn key_combo_letters_are_lower_case() {
+        assert_eq!(
+            parse_key_combo("Super+Shift+Q"),
+            Ok(combo(false, false, true, true, xkb::KEY_q))
+        );
+        assert_eq!(parse_key_combo("Super+Q"), parse_key_combo("Super+q"));
+    }
+
+    #[test]
+    fn invalid_key_combo() {
+        assert_eq!(parse_key_combo(""), Err("no key given".to_owned()));
+        assert_eq!(parse_key_combo("Super+"), Err("no key given".to_owned()));
+        assert_eq!(
+            parse_key_combo("Hyper+q"),
+            Err("unknown modifier `Hyper`".to_owned())
+        );
+        assert_eq!(
+            parse_key_combo("Super+NoSuchKey"),
+            Err("unknown key `NoSuchKey`".to_owned())
+        );
+    }
+
+    #[test]
+    fn action_with_argument() {
+        assert!(matches!(
+            parse_action("run foot --server"),
+            Ok(KeyAction::Run(command)) if command == "foot --server"
+        ));
+        assert!(matches!(
+            parse_action("  workspace   3 "),
+            Ok(KeyAction::Screen(2))
+        ));
+        assert!(matches!(
+            parse_action("move-to-workspace 9"),
+            Ok(KeyAction::MoveToScreen(8))
+        ));
+        assert!(matches!(
+            parse_action("screenshot"),
+            Ok(KeyAction::Screenshot(ScreenshotMode::Output))
+        ));
+        assert!(matches!(
+            parse_action("screenshot region"),
+            Ok(KeyAction::Screenshot(ScreenshotMode::Region))
+        ));
+        assert!(matches!(
+            parse_action("split-vertical"),
+            Ok(KeyAction::ChangeSplitSate(SplitState::VSplit))
+        ));
+    }
+
+    #[test]
+    fn invalid_action() {
+        assert_eq!(parse_action("dance").unwrap_err(), "unknown action `dance`");
+        assert_eq!(parse_action("run").unwrap_err(), "`run` needs a command");
+        assert_eq!(
+            parse_action("quit now").unwrap_err(),
+            "`quit` takes no argument"
+        );
+        for text in ["workspace", "workspace 0", "workspace 10", "workspace two"] {
+            assert_eq!(
+                parse_action(text).unwrap_err(),
+                "`workspace` needs a workspace from 1 to 9"
+            );
+        }
+        assert_eq!(
+            parse_action("screenshot screen").unwrap_err(),
+            "unknown screenshot mode `screen`, expected `output`, `window` or `region`"
+        );
+    }
+
+    #[test]
+    fn color() {
+        assert_eq!(parse_color("#ff0000"), Ok([1.0, 0.0, 0.0, 1.0]));
+        assert_eq!(parse_color("#00ff0000"), Ok([0.0, 1.0, 0.0, 0.0]));
+        assert_eq!(parse_color("#336699cc"), Ok([0.2, 0.4, 0.6, 0.8]));
+    }
+
+    #[test]
+    fn invalid_color() {
+        for text in ["ff0000", "#fff", "#ff00000", "#gg0000", "#ffffé", ""] {
+            assert_eq!(
+                parse_color(text),
+                Err(format!(
+                    "invalid colour `{}`, expected `#rrggbb` or `#rrggbbaa`",
+                    text
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn empty_file_is_default() {
+        let config = parse("").unwrap();
+        assert_eq!(config.keybindings.len(), DEFAULT_KEYBINDINGS.len());
+        assert_eq!(config.gaps, 0);
+        assert_eq!(config.default_split, SplitState::HSplit);
+        assert_eq!(config.autostart, vec!["kitty".to_owned()]);
+    }
+
+    #[test]
+    fn file_overrides_defaults() {
+        let config = parse(
+            r##"
+gaps = 8
+default-split = "vertical"
+autostart = []
+screenshot-dir = "/tmp/shots"
+
+[keybindings]
+"Super+Return" = "run foot"
+"Super+q" = "none"
+"Super+x" = "quit"
+
+[theme]
+background = "#ff0000"
+header-bar-height = 30
+"##,
+        )
+        .unwrap();
+        assert_eq!(config.gaps, 8);
+        assert_eq!(config.default_split, SplitState::VSplit);
+        assert!(config.autostart.is_empty());
+        assert_eq!(config.screenshot_dir, Some(PathBuf::from("/tmp/shots")));
+        assert_eq!(config.theme.background, [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(config.theme.header_bar_height, 30);
+
+        let action = |combo: &str| {
+            let combo = parse_key_combo(combo).unwrap();
+            config
+                .keybindings
+                .iter()
+                .find(|(c, _)| *c == combo)
+                .map(|(_, action)| action.clone())
+        };
+        assert!(matches!(
+            action("Super+Return"),
+            Some(KeyAction::Run(command)) if command == "foot"
+        ));
+        assert!(action("Super+q").is_none());
+        assert!(matches!(action("Super+x"), Some(KeyAction::Quit)));
+        // the replaced binding is not kept next to the new one
+        assert_eq!(config.keybindings.len(), DEFAULT_KEYBINDINGS.len());
+    }
+
+    #[test]
+    fn errors_name_their_line() {
+        let errors = errors(
+            r#"gaps = -1
+default-split = "diagonal"
+
+[keybindings]
+"Super+x" = "dance"
+"Hyper+x" = "quit"
+
+[theme]
+background = "red"
+header-bar-height = 0
+"#,
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "config.toml:6: keybinding `Hyper+x`: unknown modifier `Hyper`",
+                "config.toml:5: keybinding `Super+x`: unknown action `dance`",
+                "config.toml:9: invalid colour `red`, expected `#rrggbb` or `#rrggbbaa`",
+                "config.toml:10: header-bar-height must be greater than 0",
+                "config.toml:1: gaps can not be negative",
+                "config.toml:2: unknown split direction `diagonal`, expected `horizontal` or `vertical`",
+            ]
+        );
+    }
+
+    #[test]
+    fn toml_error_names_its_line() {
+        let errors = errors("autostart = [\"kitty\"]\ngaps = \"eight\"\n");
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].starts_with("config.toml:2: "),
+            "unexpected error {:?}",
+            errors[0]
+        );
+    }
+}
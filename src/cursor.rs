@@ -0,0 +1,113 @@
+use std::{io::Read, time::Duration};
+
+use xcursor::{
+    parser::{parse_xcursor, Image},
+    CursorTheme,
+};
+
+const FALLBACK_CURSOR_SIZE: u32 = 16;
+
+pub struct Cursor {
+    icons: Vec<Image>,
+    size: u32,
+}
+
+impl Cursor {
+    pub fn load() -> Cursor {
+        let name = std::env::var("XCURSOR_THEME")
+            .ok()
+            .unwrap_or_else(|| "default".into());
+        let size = std::env::var("XCURSOR_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(24);
+
+        let theme = CursorTheme::load(&name);
+        let icons = load_icon(&theme)
+            .map_err(|err| tracing::warn!("Unable to load xcursor: {}, using fallback cursor", err))
+            .unwrap_or_else(|_| vec![fallback_image()]);
+
+        Cursor { icons, size }
+    }
+
+    pub fn get_image(&self, scale: u32, time: Duration) -> Image {
+        let size = self.size * scale;
+        frame(time.as_millis() as u32, size, &self.icons)
+    }
+}
+
+fn nearest_images(size: u32, images: &[Image]) -> impl Iterator<Item = &Image> {
+    // Follow the nominal size of the cursor to choose the nearest
+    let nearest_image = images
+        .iter()
+        .min_by_key(|image| (size as i32 - image.size as i32).abs())
+        .unwrap();
+
+    images.iter().filter(move |image| {
+        image.width == nearest_image.width && image.height == nearest_image.height
+    })
+}
+
+fn frame(mut millis: u32, size: u32, images: &[Image]) -> Image {
+    let total = nearest_images(size, images).fold(0, |acc, image| acc + image.delay);
+
+    if total == 0 {
+        return nearest_images(size, images).next().unwrap().clone();
+    }
+
+    millis %= total;
+
+    for img in nearest_images(size, images) {
+        if millis < img.delay {
+            return img.clone();
+        }
+        millis -= img.delay;
+    }
+
+    unreachable!()
+}
+
+// A plain square with a dark border, used when no xcursor theme is installed
+fn fallback_image() -> Image {
+    let size = FALLBACK_CURSOR_SIZE;
+    let mut pixels_rgba = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let border = x == 0 || y == 0 || x == size - 1 || y == size - 1;
+            let value = if border { 0x00 } else { 0xff };
+            pixels_rgba.extend_from_slice(&[value, value, value, 0xff]);
+        }
+    }
+    let pixels_argb = pixels_rgba
+        .chunks(4)
+        .flat_map(|p| [p[2], p[1], p[0], p[3]])
+        .collect();
+    Image {
+        size,
+        width: size,
+        height: size,
+        xhot: 0,
+        yhot: 0,
+        delay: 0,
+        pixels_rgba,
+        pixels_argb,
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+enum Error {
+    #[error("Theme has no default cursor")]
+    NoDefaultCursor,
+    #[error("Error opening xcursor file: {0}")]
+    File(#[from] std::io::Error),
+    #[error("Failed to parse XCursor file")]
+    Parse,
+}
+
+fn load_icon(theme: &CursorTheme) -> Result<Vec<Image>, Error> {
+    let icon_path = theme.load_icon("default").ok_or(Error::NoDefaultCursor)?;
+    let mut cursor_file = std::fs::File::open(icon_path)?;
+    let mut cursor_data = Vec::new();
+    cursor_file.read_to_end(&mut cursor_data)?;
+    parse_xcursor(&cursor_data).ok_or(Error::Parse)
+}
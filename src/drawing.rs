@@ -1,6 +1,10 @@
+use std::cell::RefMut;
+
 use smithay::{
     backend::renderer::{
+        damage::OutputDamageTracker,
         element::{
+            solid::{SolidColorBuffer, SolidColorRenderElement},
             surface::WaylandSurfaceRenderElement,
             texture::{TextureBuffer, TextureRenderElement},
             utils::{CropRenderElement, RelocateRenderElement, RescaleRenderElement},
@@ -10,17 +14,80 @@ use smithay::{
     },
     desktop::space::SpaceRenderElements,
     input::pointer::CursorImageStatus,
+    output::Output,
     render_elements,
-    utils::{Physical, Point, Scale},
+    utils::{Physical, Point, Rectangle, Scale},
 };
 
-use crate::{render::CustomRenderElements, shell::WindowRenderElement};
+use crate::{render::CustomRenderElements, shell::WindowRenderElement, state::output_state};
 
 pub static CLEAR_COLOR: [f32; 4] = [0.8, 0.8, 0.9, 1.0];
 
 #[allow(unused)]
 pub static CLEAR_COLOR_FULLSCREEN: [f32; 4] = [0.0, 0.0, 0.0, 0.0];
 
+/// How many frames the tint of a damaged region takes to fade out
+const TINT_FRAMES: usize = 10;
+/// Red at 40%, premultiplied
+const TINT_COLOR: [f32; 4] = [0.4, 0.08, 0.08, 0.4];
+
+/// Translucent rectangles over the regions which were damaged in the last
+/// frames of an output, a debug aid toggled by ToggleTint.
+#[derive(Debug, Default)]
+pub struct DamageTint {
+    /// Tracks the damage of the frames without the tint itself
+    tracker: Option<OutputDamageTracker>,
+    /// The tinted regions with the number of frames they are shown since
+    regions: Vec<(SolidColorBuffer, Rectangle<i32, Physical>, usize)>,
+}
+
+impl DamageTint {
+    /// Age the tint of the former frames and add the damage `elements` have
+    /// against the last frame.
+    pub fn update<R, E>(&mut self, output: &Output, elements: &[E])
+    where
+        R: Renderer,
+        E: RenderElement<R>,
+    {
+        self.regions.retain_mut(|(buffer, rect, age)| {
+            *age += 1;
+            // premultiplied colours fade with all of their channels
+            let fade = 1.0 - *age as f32 / TINT_FRAMES as f32;
+            let color = TINT_COLOR.map(|channel| channel * fade);
+            buffer.update((rect.size.w, rect.size.h), color);
+            *age < TINT_FRAMES
+        });
+
+        let tracker = self
+            .tracker
+            .get_or_insert_with(|| OutputDamageTracker::from_output(output));
+        let damage = match tracker.damage_output(1, elements) {
+            Ok((Some(damage), _)) => damage.to_vec(),
+            Ok((None, _)) => return,
+            Err(err) => {
+                tracing::warn!(?err, "Failed to compute the damage for the tint");
+                return;
+            }
+        };
+        for rect in damage {
+            // the buffer is sized in physical pixels and drawn at scale 1
+            let buffer = SolidColorBuffer::new((rect.size.w, rect.size.h), TINT_COLOR);
+            self.regions.push((buffer, rect, 0));
+        }
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = SolidColorRenderElement> + '_ {
+        self.regions.iter().map(|(buffer, rect, _)| {
+            SolidColorRenderElement::from_buffer(buffer, rect.loc, 1.0, 1.0, Kind::Unspecified)
+        })
+    }
+}
+
+/// The damage tint of `output`
+pub fn damage_tint(output: &Output) -> RefMut<'_, DamageTint> {
+    output_state(output)
+}
+
 pub struct PointerElement<T: Texture> {
     texture: Option<TextureBuffer<T>>,
     status: CursorImageStatus,
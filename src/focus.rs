@@ -0,0 +1,477 @@
+//! The things which take the keyboard, pointer and touch focus of the seat,
+//! windows and layer shell surfaces like panels and launchers.
+
+use smithay::{
+    backend::input::KeyState,
+    desktop::LayerSurface,
+    input::{
+        keyboard::{KeyboardTarget, KeysymHandle, ModifiersState},
+        pointer::{
+            AxisFrame, ButtonEvent, GestureHoldBeginEvent, GestureHoldEndEvent,
+            GesturePinchBeginEvent, GesturePinchEndEvent, GesturePinchUpdateEvent,
+            GestureSwipeBeginEvent, GestureSwipeEndEvent, GestureSwipeUpdateEvent, MotionEvent,
+            PointerTarget, RelativeMotionEvent,
+        },
+        touch::{
+            DownEvent, MotionEvent as TouchMotionEvent, OrientationEvent, ShapeEvent, TouchTarget,
+            UpEvent,
+        },
+        Seat,
+    },
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{IsAlive, Serial},
+    wayland::seat::WaylandFocus,
+};
+
+use crate::{
+    shell::WindowElement,
+    state::{Backend, SmallCageState},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FocusTarget {
+    Window(WindowElement),
+    LayerSurface(LayerSurface),
+}
+
+impl FocusTarget {
+    /// The window, if the focus is on one
+    pub fn window(&self) -> Option<&WindowElement> {
+        match self {
+            FocusTarget::Window(window) => Some(window),
+            FocusTarget::LayerSurface(_) => None,
+        }
+    }
+}
+
+impl From<WindowElement> for FocusTarget {
+    fn from(window: WindowElement) -> Self {
+        FocusTarget::Window(window)
+    }
+}
+
+impl From<LayerSurface> for FocusTarget {
+    fn from(layer: LayerSurface) -> Self {
+        FocusTarget::LayerSurface(layer)
+    }
+}
+
+impl IsAlive for FocusTarget {
+    fn alive(&self) -> bool {
+        match self {
+            FocusTarget::Window(window) => window.alive(),
+            FocusTarget::LayerSurface(layer) => layer.alive(),
+        }
+    }
+}
+
+impl WaylandFocus for FocusTarget {
+    fn wl_surface(&self) -> Option<WlSurface> {
+        match self {
+            FocusTarget::Window(window) => window.wl_surface(),
+            FocusTarget::LayerSurface(layer) => Some(layer.wl_surface().clone()),
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>> for FocusTarget {
+    fn enter(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &MotionEvent,
+    ) {
+        match self {
+            FocusTarget::Window(window) => PointerTarget::enter(window, seat, data, event),
+            FocusTarget::LayerSurface(layer) => {
+                PointerTarget::enter(layer.wl_surface(), seat, data, event)
+            }
+        }
+    }
+
+    fn motion(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &MotionEvent,
+    ) {
+        match self {
+            FocusTarget::Window(window) => PointerTarget::motion(window, seat, data, event),
+            FocusTarget::LayerSurface(layer) => {
+                PointerTarget::motion(layer.wl_surface(), seat, data, event)
+            }
+        }
+    }
+
+    fn relative_motion(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &RelativeMotionEvent,
+    ) {
+        match self {
+            FocusTarget::Window(window) => {
+                PointerTarget::relative_motion(window, seat, data, event)
+            }
+            FocusTarget::LayerSurface(layer) => {
+                PointerTarget::relative_motion(layer.wl_surface(), seat, data, event)
+            }
+        }
+    }
+
+    fn button(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &ButtonEvent,
+    ) {
+        match self {
+            FocusTarget::Window(window) => PointerTarget::button(window, seat, data, event),
+            FocusTarget::LayerSurface(layer) => {
+                PointerTarget::button(layer.wl_surface(), seat, data, event)
+            }
+        }
+    }
+
+    fn axis(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        frame: AxisFrame,
+    ) {
+        match self {
+            FocusTarget::Window(window) => PointerTarget::axis(window, seat, data, frame),
+            FocusTarget::LayerSurface(layer) => {
+                PointerTarget::axis(layer.wl_surface(), seat, data, frame)
+            }
+        }
+    }
+
+    fn frame(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+    ) {
+        match self {
+            FocusTarget::Window(window) => PointerTarget::frame(window, seat, data),
+            FocusTarget::LayerSurface(layer) => {
+                PointerTarget::frame(layer.wl_surface(), seat, data)
+            }
+        }
+    }
+
+    fn leave(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        serial: Serial,
+        time: u32,
+    ) {
+        match self {
+            FocusTarget::Window(window) => PointerTarget::leave(window, seat, data, serial, time),
+            FocusTarget::LayerSurface(layer) => {
+                PointerTarget::leave(layer.wl_surface(), seat, data, serial, time)
+            }
+        }
+    }
+
+    fn gesture_swipe_begin(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &GestureSwipeBeginEvent,
+    ) {
+        match self {
+            FocusTarget::Window(window) => {
+                PointerTarget::gesture_swipe_begin(window, seat, data, event)
+            }
+            FocusTarget::LayerSurface(layer) => {
+                PointerTarget::gesture_swipe_begin(layer.wl_surface(), seat, data, event)
+            }
+        }
+    }
+
+    fn gesture_swipe_update(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &GestureSwipeUpdateEvent,
+    ) {
+        match self {
+            FocusTarget::Window(window) => {
+                PointerTarget::gesture_swipe_update(window, seat, data, event)
+            }
+            FocusTarget::LayerSurface(layer) => {
+                PointerTarget::gesture_swipe_update(layer.wl_surface(), seat, data, event)
+            }
+        }
+    }
+
+    fn gesture_swipe_end(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &GestureSwipeEndEvent,
+    ) {
+        match self {
+            FocusTarget::Window(window) => {
+                PointerTarget::gesture_swipe_end(window, seat, data, event)
+            }
+            FocusTarget::LayerSurface(layer) => {
+                PointerTarget::gesture_swipe_end(layer.wl_surface(), seat, data, event)
+            }
+        }
+    }
+
+    fn gesture_pinch_begin(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &GesturePinchBeginEvent,
+    ) {
+        match self {
+            FocusTarget::Window(window) => {
+                PointerTarget::gesture_pinch_begin(window, seat, data, event)
+            }
+            FocusTarget::LayerSurface(layer) => {
+                PointerTarget::gesture_pinch_begin(layer.wl_surface(), seat, data, event)
+            }
+        }
+    }
+
+    fn gesture_pinch_update(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &GesturePinchUpdateEvent,
+    ) {
+        match self {
+            FocusTarget::Window(window) => {
+                PointerTarget::gesture_pinch_update(window, seat, data, event)
+            }
+            FocusTarget::LayerSurface(layer) => {
+                PointerTarget::gesture_pinch_update(layer.wl_surface(), seat, data, event)
+            }
+        }
+    }
+
+    fn gesture_pinch_end(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &GesturePinchEndEvent,
+    ) {
+        match self {
+            FocusTarget::Window(window) => {
+                PointerTarget::gesture_pinch_end(window, seat, data, event)
+            }
+            FocusTarget::LayerSurface(layer) => {
+                PointerTarget::gesture_pinch_end(layer.wl_surface(), seat, data, event)
+            }
+        }
+    }
+
+    fn gesture_hold_begin(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &GestureHoldBeginEvent,
+    ) {
+        match self {
+            FocusTarget::Window(window) => {
+                PointerTarget::gesture_hold_begin(window, seat, data, event)
+            }
+            FocusTarget::LayerSurface(layer) => {
+                PointerTarget::gesture_hold_begin(layer.wl_surface(), seat, data, event)
+            }
+        }
+    }
+
+    fn gesture_hold_end(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &GestureHoldEndEvent,
+    ) {
+        match self {
+            FocusTarget::Window(window) => {
+                PointerTarget::gesture_hold_end(window, seat, data, event)
+            }
+            FocusTarget::LayerSurface(layer) => {
+                PointerTarget::gesture_hold_end(layer.wl_surface(), seat, data, event)
+            }
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> KeyboardTarget<SmallCageState<BackendData>> for FocusTarget {
+    fn enter(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        keys: Vec<KeysymHandle<'_>>,
+        serial: Serial,
+    ) {
+        match self {
+            FocusTarget::Window(window) => KeyboardTarget::enter(window, seat, data, keys, serial),
+            FocusTarget::LayerSurface(layer) => {
+                KeyboardTarget::enter(layer.wl_surface(), seat, data, keys, serial)
+            }
+        }
+    }
+
+    fn leave(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        serial: Serial,
+    ) {
+        match self {
+            FocusTarget::Window(window) => KeyboardTarget::leave(window, seat, data, serial),
+            FocusTarget::LayerSurface(layer) => {
+                KeyboardTarget::leave(layer.wl_surface(), seat, data, serial)
+            }
+        }
+    }
+
+    fn key(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        key: KeysymHandle<'_>,
+        state: KeyState,
+        serial: Serial,
+        time: u32,
+    ) {
+        match self {
+            FocusTarget::Window(window) => {
+                KeyboardTarget::key(window, seat, data, key, state, serial, time)
+            }
+            FocusTarget::LayerSurface(layer) => {
+                KeyboardTarget::key(layer.wl_surface(), seat, data, key, state, serial, time)
+            }
+        }
+    }
+
+    fn modifiers(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        modifiers: ModifiersState,
+        serial: Serial,
+    ) {
+        match self {
+            FocusTarget::Window(window) => {
+                KeyboardTarget::modifiers(window, seat, data, modifiers, serial)
+            }
+            FocusTarget::LayerSurface(layer) => {
+                KeyboardTarget::modifiers(layer.wl_surface(), seat, data, modifiers, serial)
+            }
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> TouchTarget<SmallCageState<BackendData>> for FocusTarget {
+    fn down(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &DownEvent,
+        seq: Serial,
+    ) {
+        match self {
+            FocusTarget::Window(window) => TouchTarget::down(window, seat, data, event, seq),
+            FocusTarget::LayerSurface(layer) => {
+                TouchTarget::down(layer.wl_surface(), seat, data, event, seq)
+            }
+        }
+    }
+
+    fn up(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &UpEvent,
+        seq: Serial,
+    ) {
+        match self {
+            FocusTarget::Window(window) => TouchTarget::up(window, seat, data, event, seq),
+            FocusTarget::LayerSurface(layer) => {
+                TouchTarget::up(layer.wl_surface(), seat, data, event, seq)
+            }
+        }
+    }
+
+    fn motion(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &TouchMotionEvent,
+        seq: Serial,
+    ) {
+        match self {
+            FocusTarget::Window(window) => TouchTarget::motion(window, seat, data, event, seq),
+            FocusTarget::LayerSurface(layer) => {
+                TouchTarget::motion(layer.wl_surface(), seat, data, event, seq)
+            }
+        }
+    }
+
+    fn frame(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        seq: Serial,
+    ) {
+        match self {
+            FocusTarget::Window(window) => TouchTarget::frame(window, seat, data, seq),
+            FocusTarget::LayerSurface(layer) => {
+                TouchTarget::frame(layer.wl_surface(), seat, data, seq)
+            }
+        }
+    }
+
+    fn cancel(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        seq: Serial,
+    ) {
+        match self {
+            FocusTarget::Window(window) => TouchTarget::cancel(window, seat, data, seq),
+            FocusTarget::LayerSurface(layer) => {
+                TouchTarget::cancel(layer.wl_surface(), seat, data, seq)
+            }
+        }
+    }
+
+    fn shape(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &ShapeEvent,
+        seq: Serial,
+    ) {
+        match self {
+            FocusTarget::Window(window) => TouchTarget::shape(window, seat, data, event, seq),
+            FocusTarget::LayerSurface(layer) => {
+                TouchTarget::shape(layer.wl_surface(), seat, data, event, seq)
+            }
+        }
+    }
+
+    fn orientation(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &OrientationEvent,
+        seq: Serial,
+    ) {
+        match self {
+            FocusTarget::Window(window) => TouchTarget::orientation(window, seat, data, event, seq),
+            FocusTarget::LayerSurface(layer) => {
+                TouchTarget::orientation(layer.wl_surface(), seat, data, event, seq)
+            }
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! `ext_foreign_toplevel_list_v1`, which tells clients about the windows, so
+//! that tools like screen recorders can let the user pick one to capture.
+//!
+//! The list is brought up to date once per iteration of the event loop, with
+//! the windows of every workspace. The handles carry the IPC id of their
+//! window, which is also their identifier.
+
+use smithay::reexports::{
+    wayland_protocols::ext::foreign_toplevel_list::v1::server::{
+        ext_foreign_toplevel_handle_v1::{self, ExtForeignToplevelHandleV1},
+        ext_foreign_toplevel_list_v1::{self, ExtForeignToplevelListV1},
+    },
+    wayland_server::{
+        backend::ClientId, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    },
+};
+
+use crate::{
+    shell::WindowElement,
+    state::{Backend, SmallCageState},
+};
+
+const VERSION: u32 = 1;
+
+/// The lists bound by clients and the windows they were told about
+#[derive(Debug, Default)]
+pub struct ForeignToplevelList {
+    lists: Vec<ExtForeignToplevelListV1>,
+    toplevels: Vec<ForeignToplevel>,
+}
+
+/// A window, with what the clients know about it
+#[derive(Debug)]
+struct ForeignToplevel {
+    window: WindowElement,
+    title: Option<String>,
+    app_id: Option<String>,
+    handles: Vec<ExtForeignToplevelHandleV1>,
+}
+
+impl ForeignToplevel {
+    fn new(window: WindowElement) -> Self {
+        Self {
+            title: window.title(),
+            app_id: window.app_id(),
+            window,
+            handles: Vec::new(),
+        }
+    }
+
+    /// Hand a new handle of the window to the client of `list`
+    fn announce<BackendData: Backend + 'static>(
+        &mut self,
+        dh: &DisplayHandle,
+        list: &ExtForeignToplevelListV1,
+    ) {
+        let Some(client) = list.client() else {
+            return;
+        };
+        let id = self.window.ipc_id();
+        let Ok(handle) = client
+            .create_resource::<ExtForeignToplevelHandleV1, _, SmallCageState<BackendData>>(
+                dh,
+                list.version(),
+                id,
+            )
+        else {
+            return;
+        };
+        list.toplevel(&handle);
+        handle.identifier(id.to_string());
+        if let Some(title) = self.title.clone() {
+            handle.title(title);
+        }
+        if let Some(app_id) = self.app_id.clone() {
+            handle.app_id(app_id);
+        }
+        handle.done();
+        self.handles.push(handle);
+    }
+
+    /// Tell the handles about a new title or app id
+    fn update(&mut self) {
+        let (title, app_id) = (self.window.title(), self.window.app_id());
+        if title == self.title && app_id == self.app_id {
+            return;
+        }
+        for handle in &self.handles {
+            if title != self.title {
+                handle.title(title.clone().unwrap_or_default());
+            }
+            if app_id != self.app_id {
+                handle.app_id(app_id.clone().unwrap_or_default());
+            }
+            handle.done();
+        }
+        self.title = title;
+        self.app_id = app_id;
+    }
+}
+
+/// Menus and tooltips of X11 are windows of smallcage, but no toplevels
+#[cfg(feature = "xwayland")]
+fn is_toplevel(window: &WindowElement) -> bool {
+    !window
+        .x11_surface()
+        .is_some_and(|surface| surface.is_override_redirect())
+}
+
+#[cfg(not(feature = "xwayland"))]
+fn is_toplevel(_window: &WindowElement) -> bool {
+    true
+}
+
+/// Advertise the list of windows to the clients
+pub fn init_foreign_toplevel_list<BackendData: Backend + 'static>(dh: &DisplayHandle) {
+    dh.create_global::<SmallCageState<BackendData>, ExtForeignToplevelListV1, _>(VERSION, ());
+}
+
+impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
+    /// Tell the clients about the windows which were opened, changed or
+    /// closed since the last time
+    pub fn refresh_foreign_toplevels(&mut self) {
+        let windows: Vec<WindowElement> =
+            self.all_windows().into_iter().filter(is_toplevel).collect();
+        let dh = &self.display_handle;
+        let state = &mut self.foreign_toplevel_list;
+
+        state.toplevels.retain(|toplevel| {
+            let open = windows.contains(&toplevel.window);
+            if !open {
+                for handle in &toplevel.handles {
+                    handle.closed();
+                }
+            }
+            open
+        });
+        for window in windows {
+            if let Some(toplevel) = state.toplevels.iter_mut().find(|t| t.window == window) {
+                toplevel.update();
+                continue;
+            }
+            let mut toplevel = ForeignToplevel::new(window);
+            for list in &state.lists {
+                toplevel.announce::<BackendData>(dh, list);
+            }
+            state.toplevels.push(toplevel);
+        }
+    }
+
+    /// The window of `handle`, while it is open
+    pub fn foreign_toplevel_window(
+        &self,
+        handle: &ExtForeignToplevelHandleV1,
+    ) -> Option<WindowElement> {
+        let id = *handle.data::<u64>()?;
+        self.foreign_toplevel_list
+            .toplevels
+            .iter()
+            .find(|toplevel| toplevel.window.ipc_id() == id)
+            .map(|toplevel| toplevel.window.clone())
+    }
+}
+
+impl<BackendData: Backend + 'static> GlobalDispatch<ExtForeignToplevelListV1, ()>
+    for SmallCageState<BackendData>
+{
+    fn bind(
+        state: &mut Self,
+        handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ExtForeignToplevelListV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let list = data_init.init(resource, ());
+        for toplevel in state.foreign_toplevel_list.toplevels.iter_mut() {
+            toplevel.announce::<BackendData>(handle, &list);
+        }
+        state.foreign_toplevel_list.lists.push(list);
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ExtForeignToplevelListV1, ()>
+    for SmallCageState<BackendData>
+{
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        list: &ExtForeignToplevelListV1,
+        request: ext_foreign_toplevel_list_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        use ext_foreign_toplevel_list_v1::Request;
+        match request {
+            // no more windows are announced after the list is stopped
+            Request::Stop => {
+                state.foreign_toplevel_list.lists.retain(|l| l != list);
+                list.finished();
+            }
+            Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut Self, _client: ClientId, list: &ExtForeignToplevelListV1, _data: &()) {
+        state.foreign_toplevel_list.lists.retain(|l| l != list);
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ExtForeignToplevelHandleV1, u64>
+    for SmallCageState<BackendData>
+{
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _handle: &ExtForeignToplevelHandleV1,
+        request: ext_foreign_toplevel_handle_v1::Request,
+        _data: &u64,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        use ext_foreign_toplevel_handle_v1::Request;
+        match request {
+            Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut Self,
+        _client: ClientId,
+        handle: &ExtForeignToplevelHandleV1,
+        _data: &u64,
+    ) {
+        for toplevel in state.foreign_toplevel_list.toplevels.iter_mut() {
+            toplevel.handles.retain(|h| h != handle);
+        }
+    }
+}
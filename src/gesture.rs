@@ -0,0 +1,310 @@
+//! Touchpad gestures. Swipes with three or four fingers switch workspaces
+//! and a pinch with three or more fingers opens or closes the overview,
+//! every other gesture goes to the window under the pointer.
+
+use std::{
+    cell::RefMut,
+    time::{Duration, Instant},
+};
+
+use smithay::{
+    backend::input::{
+        Event, GestureBeginEvent, GestureEndEvent, GesturePinchUpdateEvent as _,
+        GestureSwipeUpdateEvent as _, InputBackend,
+    },
+    input::pointer::{
+        GestureHoldBeginEvent, GestureHoldEndEvent, GesturePinchBeginEvent, GesturePinchEndEvent,
+        GesturePinchUpdateEvent, GestureSwipeBeginEvent, GestureSwipeEndEvent,
+        GestureSwipeUpdateEvent,
+    },
+    output::Output,
+    utils::SERIAL_COUNTER,
+};
+
+use crate::{
+    state::{output_state, Backend, SmallCageState},
+    workspace::{active_workspace, WORKSPACE_COUNT},
+};
+
+/// Swipes with this many fingers switch workspaces
+const SWIPE_FINGERS: [u32; 2] = [3, 4];
+/// Pinches with at least this many fingers open and close the overview,
+/// two finger pinches are left to the clients for zooming
+const PINCH_MIN_FINGERS: u32 = 3;
+/// How far a swipe has to go to switch, relative to the output width
+const SWIPE_THRESHOLD: f64 = 0.25;
+/// The pinch scale below which the overview opens
+const PINCH_IN_THRESHOLD: f64 = 0.75;
+/// The pinch scale above which the overview closes again
+const PINCH_OUT_THRESHOLD: f64 = 1.33;
+/// The windows do not shrink further than this while pinching
+const MIN_ZOOM: f64 = 0.5;
+/// How long the workspace takes to settle once the fingers are lifted
+const SETTLE_DURATION: Duration = Duration::from_millis(200);
+
+/// The gesture in progress
+#[derive(Debug)]
+pub enum Gesture {
+    /// The workspace of `output` follows the fingers by `offset`
+    Swipe { output: Output, offset: f64 },
+    /// The windows of `output` shrink with the fingers, or the overview is
+    /// closed once they spread. `scale` is the distance of the fingers
+    /// relative to where they started.
+    Pinch { output: Output, scale: f64 },
+    /// The gesture belongs to the window under the pointer
+    Forwarded,
+}
+
+/// How the workspace shown on an output is moved and scaled by a gesture,
+/// kept in the user data of the output
+#[derive(Debug)]
+pub struct Transition {
+    /// Horizontal offset in logical coordinates
+    offset: f64,
+    zoom: f64,
+    /// When the transition started going back to rest, it follows the
+    /// fingers while there is none
+    settling: Option<Instant>,
+}
+
+impl Default for Transition {
+    fn default() -> Self {
+        Self {
+            offset: 0.0,
+            zoom: 1.0,
+            settling: None,
+        }
+    }
+}
+
+impl Transition {
+    /// Move the workspace to where the fingers are
+    fn follow(&mut self, offset: f64, zoom: f64) {
+        self.offset = offset;
+        self.zoom = zoom;
+        self.settling = None;
+    }
+
+    /// Animate the workspace from `offset` and `zoom` back into place
+    fn settle(&mut self, offset: f64, zoom: f64) {
+        self.offset = offset;
+        self.zoom = zoom;
+        self.settling = Some(Instant::now());
+    }
+
+    /// The offset and zoom the workspace is drawn with, none once it is at
+    /// rest
+    pub fn current(&mut self) -> Option<(f64, f64)> {
+        let progress = match self.settling {
+            Some(start) => start.elapsed().as_secs_f64() / SETTLE_DURATION.as_secs_f64(),
+            None => 0.0,
+        };
+        if progress >= 1.0 || (self.offset == 0.0 && self.zoom == 1.0) {
+            *self = Self::default();
+            return None;
+        }
+        // ease out, fast at first and slow when arriving
+        let remaining = (1.0 - progress).powi(3);
+        Some((self.offset * remaining, 1.0 + (self.zoom - 1.0) * remaining))
+    }
+}
+
+pub fn output_transition(output: &Output) -> RefMut<'_, Transition> {
+    output_state(output)
+}
+
+impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
+    pub fn gesture_swipe_begin<I: InputBackend>(&mut self, event: I::GestureSwipeBeginEvent) {
+        let fingers = event.fingers();
+        if !SWIPE_FINGERS.contains(&fingers) {
+            self.gesture = Some(Gesture::Forwarded);
+            let pointer = self.pointer.clone();
+            pointer.gesture_swipe_begin(
+                self,
+                &GestureSwipeBeginEvent {
+                    serial: SERIAL_COUNTER.next_serial(),
+                    time: event.time_msec(),
+                    fingers,
+                },
+            );
+            return;
+        }
+        // the overview does not show a single workspace to swipe away
+        self.gesture = self
+            .current_output()
+            .filter(|_| !self.show_window_preview)
+            .map(|output| Gesture::Swipe {
+                output,
+                offset: 0.0,
+            });
+    }
+
+    pub fn gesture_swipe_update<I: InputBackend>(&mut self, event: I::GestureSwipeUpdateEvent) {
+        match &mut self.gesture {
+            Some(Gesture::Swipe { output, offset }) => {
+                let width = self
+                    .space
+                    .output_geometry(output)
+                    .map_or(0, |geo| geo.size.w) as f64;
+                *offset = (*offset + event.delta_x()).clamp(-width, width);
+                output_transition(output).follow(*offset, 1.0);
+            }
+            Some(Gesture::Forwarded) => {
+                let pointer = self.pointer.clone();
+                pointer.gesture_swipe_update(
+                    self,
+                    &GestureSwipeUpdateEvent {
+                        time: event.time_msec(),
+                        delta: event.delta(),
+                    },
+                );
+            }
+            _ => {}
+        }
+    }
+
+    pub fn gesture_swipe_end<I: InputBackend>(&mut self, event: I::GestureSwipeEndEvent) {
+        match self.gesture.take() {
+            Some(Gesture::Swipe { output, offset }) => {
+                let width = self
+                    .space
+                    .output_geometry(&output)
+                    .map_or(0, |geo| geo.size.w) as f64;
+                let current = active_workspace(&output);
+                // the fingers drag the workspace away, the next one follows
+                // from the side it was dragged from
+                let target = if event.cancelled() || offset.abs() < width * SWIPE_THRESHOLD {
+                    None
+                } else if offset < 0.0 {
+                    Some(current + 1).filter(|index| *index < WORKSPACE_COUNT)
+                } else {
+                    current.checked_sub(1)
+                };
+                let Some(target) = target else {
+                    output_transition(&output).settle(offset, 1.0);
+                    return;
+                };
+                self.switch_workspace(&output, target);
+                let from = if target > current {
+                    offset + width
+                } else {
+                    offset - width
+                };
+                output_transition(&output).settle(from, 1.0);
+            }
+            Some(Gesture::Forwarded) => {
+                let pointer = self.pointer.clone();
+                pointer.gesture_swipe_end(
+                    self,
+                    &GestureSwipeEndEvent {
+                        serial: SERIAL_COUNTER.next_serial(),
+                        time: event.time_msec(),
+                        cancelled: event.cancelled(),
+                    },
+                );
+            }
+            _ => {}
+        }
+    }
+
+    pub fn gesture_pinch_begin<I: InputBackend>(&mut self, event: I::GesturePinchBeginEvent) {
+        let fingers = event.fingers();
+        if fingers < PINCH_MIN_FINGERS {
+            self.gesture = Some(Gesture::Forwarded);
+            let pointer = self.pointer.clone();
+            pointer.gesture_pinch_begin(
+                self,
+                &GesturePinchBeginEvent {
+                    serial: SERIAL_COUNTER.next_serial(),
+                    time: event.time_msec(),
+                    fingers,
+                },
+            );
+            return;
+        }
+        self.gesture = self
+            .current_output()
+            .map(|output| Gesture::Pinch { output, scale: 1.0 });
+    }
+
+    pub fn gesture_pinch_update<I: InputBackend>(&mut self, event: I::GesturePinchUpdateEvent) {
+        match &mut self.gesture {
+            Some(Gesture::Pinch { output, scale }) => {
+                *scale = event.scale();
+                // the overview opens at the end, until then the windows
+                // shrink towards it
+                if !self.show_window_preview {
+                    output_transition(output).follow(0.0, scale.clamp(MIN_ZOOM, 1.0));
+                }
+            }
+            Some(Gesture::Forwarded) => {
+                let pointer = self.pointer.clone();
+                pointer.gesture_pinch_update(
+                    self,
+                    &GesturePinchUpdateEvent {
+                        time: event.time_msec(),
+                        delta: event.delta(),
+                        scale: event.scale(),
+                        rotation: event.rotation(),
+                    },
+                );
+            }
+            _ => {}
+        }
+    }
+
+    pub fn gesture_pinch_end<I: InputBackend>(&mut self, event: I::GesturePinchEndEvent) {
+        match self.gesture.take() {
+            Some(Gesture::Pinch { output, scale }) => {
+                if self.show_window_preview {
+                    if !event.cancelled() && scale > PINCH_OUT_THRESHOLD {
+                        self.toggle_preview();
+                    }
+                    return;
+                }
+                if !event.cancelled() && scale < PINCH_IN_THRESHOLD {
+                    *output_transition(&output) = Transition::default();
+                    self.toggle_preview();
+                    return;
+                }
+                output_transition(&output).settle(0.0, scale.clamp(MIN_ZOOM, 1.0));
+            }
+            Some(Gesture::Forwarded) => {
+                let pointer = self.pointer.clone();
+                pointer.gesture_pinch_end(
+                    self,
+                    &GesturePinchEndEvent {
+                        serial: SERIAL_COUNTER.next_serial(),
+                        time: event.time_msec(),
+                        cancelled: event.cancelled(),
+                    },
+                );
+            }
+            _ => {}
+        }
+    }
+
+    pub fn gesture_hold_begin<I: InputBackend>(&mut self, event: I::GestureHoldBeginEvent) {
+        let pointer = self.pointer.clone();
+        pointer.gesture_hold_begin(
+            self,
+            &GestureHoldBeginEvent {
+                serial: SERIAL_COUNTER.next_serial(),
+                time: event.time_msec(),
+                fingers: event.fingers(),
+            },
+        );
+    }
+
+    pub fn gesture_hold_end<I: InputBackend>(&mut self, event: I::GestureHoldEndEvent) {
+        let pointer = self.pointer.clone();
+        pointer.gesture_hold_end(
+            self,
+            &GestureHoldEndEvent {
+                serial: SERIAL_COUNTER.next_serial(),
+                time: event.time_msec(),
+                cancelled: event.cancelled(),
+            },
+        );
+    }
+}
@@ -1,5 +1,9 @@
 mod normal_move_grab;
 pub mod normal_resize_grab;
+mod tile_move_grab;
+mod tile_resize_grab;
 
 pub use normal_move_grab::NormalMoveSurfaceGrab;
 pub use normal_resize_grab::*;
+pub use tile_move_grab::TileMoveSurfaceGrab;
+pub use tile_resize_grab::TileResizeSurfaceGrab;
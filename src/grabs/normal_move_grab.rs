@@ -1,4 +1,4 @@
-use crate::{shell::WindowElement, state::Backend, SmallCageState};
+use crate::{focus::FocusTarget, shell::WindowElement, state::Backend, SmallCageState};
 use smithay::{
     input::pointer::{
         AxisFrame, ButtonEvent, GestureHoldBeginEvent, GestureHoldEndEvent, GesturePinchBeginEvent,
@@ -22,7 +22,7 @@ impl<BackendData: Backend + 'static> PointerGrab<SmallCageState<BackendData>>
         &mut self,
         data: &mut SmallCageState<BackendData>,
         handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
-        _focus: Option<(WindowElement, Point<i32, Logical>)>,
+        _focus: Option<(FocusTarget, Point<i32, Logical>)>,
         event: &MotionEvent,
     ) {
         // While the grab is active, no client has pointer focus
@@ -32,13 +32,14 @@ impl<BackendData: Backend + 'static> PointerGrab<SmallCageState<BackendData>>
         let new_location = self.initial_window_location.to_f64() + delta;
         data.space
             .map_element(self.window.clone(), new_location.to_i32_round(), true);
+        self.window.set_location(new_location.to_i32_round());
     }
 
     fn relative_motion(
         &mut self,
         data: &mut SmallCageState<BackendData>,
         handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
-        focus: Option<(WindowElement, Point<i32, Logical>)>,
+        focus: Option<(FocusTarget, Point<i32, Logical>)>,
         event: &RelativeMotionEvent,
     ) {
         handle.relative_motion(data, focus, event);
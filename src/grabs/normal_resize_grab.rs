@@ -1,4 +1,4 @@
-use crate::{shell::WindowElement, state::Backend, SmallCageState};
+use crate::{focus::FocusTarget, shell::WindowElement, state::Backend, SmallCageState};
 use smithay::{
     desktop::{space::SpaceElement, Space},
     input::pointer::{
@@ -16,6 +16,9 @@ use smithay::{
 };
 use std::cell::RefCell;
 
+#[cfg(feature = "xwayland")]
+use smithay::xwayland::xwm::ResizeEdge as X11ResizeEdge;
+
 bitflags::bitflags! {
     #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
     pub struct ResizeEdge: u32 {
@@ -39,6 +42,22 @@ impl From<xdg_toplevel::ResizeEdge> for ResizeEdge {
     }
 }
 
+#[cfg(feature = "xwayland")]
+impl From<X11ResizeEdge> for ResizeEdge {
+    fn from(edge: X11ResizeEdge) -> Self {
+        match edge {
+            X11ResizeEdge::Top => Self::TOP,
+            X11ResizeEdge::Bottom => Self::BOTTOM,
+            X11ResizeEdge::Left => Self::LEFT,
+            X11ResizeEdge::Right => Self::RIGHT,
+            X11ResizeEdge::TopLeft => Self::TOP_LEFT,
+            X11ResizeEdge::BottomLeft => Self::BOTTOM_LEFT,
+            X11ResizeEdge::TopRight => Self::TOP_RIGHT,
+            X11ResizeEdge::BottomRight => Self::BOTTOM_RIGHT,
+        }
+    }
+}
+
 pub struct ResizeSurfaceGrab<BackendData: Backend + 'static> {
     start_data: PointerGrabStartData<SmallCageState<BackendData>>,
     window: WindowElement,
@@ -58,12 +77,15 @@ impl<BackendData: Backend + 'static> ResizeSurfaceGrab<BackendData> {
     ) -> Self {
         let initial_rect = initial_window_rect;
 
-        ResizeSurfaceState::with(window.toplevel().wl_surface(), |state| {
-            *state = ResizeSurfaceState::Resizing {
-                edges,
-                initial_rect,
-            };
-        });
+        // only xdg toplevels are resized interactively
+        if let Some(toplevel) = window.toplevel() {
+            ResizeSurfaceState::with(toplevel.wl_surface(), |state| {
+                *state = ResizeSurfaceState::Resizing {
+                    edges,
+                    initial_rect,
+                };
+            });
+        }
 
         Self {
             start_data,
@@ -82,7 +104,7 @@ impl<BackendData: Backend + 'static> PointerGrab<SmallCageState<BackendData>>
         &mut self,
         data: &mut SmallCageState<BackendData>,
         handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
-        _focus: Option<(WindowElement, Point<i32, Logical>)>,
+        _focus: Option<(FocusTarget, Point<i32, Logical>)>,
         event: &MotionEvent,
     ) {
         // While the grab is active, no client has pointer focus
@@ -109,11 +131,14 @@ impl<BackendData: Backend + 'static> PointerGrab<SmallCageState<BackendData>>
             new_window_height = (self.initial_rect.size.h as f64 + delta.y) as i32;
         }
 
-        let (min_size, max_size) =
-            compositor::with_states(self.window.toplevel().wl_surface(), |states| {
+        let xdg = self.window.toplevel();
+        let (min_size, max_size) = match xdg {
+            Some(xdg) => compositor::with_states(xdg.wl_surface(), |states| {
                 let data = states.cached_state.current::<SurfaceCachedState>();
                 (data.min_size, data.max_size)
-            });
+            }),
+            None => (self.window.min_size(), self.window.max_size()),
+        };
 
         let min_width = min_size.w.max(1);
         let min_height = min_size.h.max(1);
@@ -126,7 +151,23 @@ impl<BackendData: Backend + 'static> PointerGrab<SmallCageState<BackendData>>
             new_window_height.max(min_height).min(max_height),
         ));
 
-        let xdg = self.window.toplevel();
+        let Some(xdg) = xdg else {
+            // X11 windows do not commit a new size first, they are moved
+            // and resized at once
+            let mut location = self.initial_rect.loc;
+            if self.edges.intersects(ResizeEdge::LEFT) {
+                location.x += self.initial_rect.size.w - self.last_window_size.w;
+            }
+            if self.edges.intersects(ResizeEdge::TOP) {
+                location.y += self.initial_rect.size.h - self.last_window_size.h;
+            }
+            data.space.map_element(self.window.clone(), location, false);
+            self.window.configure(Rectangle::from_loc_and_size(
+                location,
+                self.last_window_size,
+            ));
+            return;
+        };
         xdg.with_pending_state(|state| {
             state.states.set(xdg_toplevel::State::Resizing);
             state.size = Some(self.last_window_size);
@@ -139,7 +180,7 @@ impl<BackendData: Backend + 'static> PointerGrab<SmallCageState<BackendData>>
         &mut self,
         data: &mut SmallCageState<BackendData>,
         handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
-        focus: Option<(WindowElement, Point<i32, Logical>)>,
+        focus: Option<(FocusTarget, Point<i32, Logical>)>,
         event: &RelativeMotionEvent,
     ) {
         handle.relative_motion(data, focus, event);
@@ -161,7 +202,9 @@ impl<BackendData: Backend + 'static> PointerGrab<SmallCageState<BackendData>>
             // No more buttons are pressed, release the grab.
             handle.unset_grab(data, event.serial, event.time, true);
 
-            let xdg = self.window.toplevel();
+            let Some(xdg) = self.window.toplevel() else {
+                return;
+            };
             xdg.with_pending_state(|state| {
                 state.states.unset(xdg_toplevel::State::Resizing);
                 state.size = Some(self.last_window_size);
@@ -328,10 +371,7 @@ impl ResizeSurfaceState {
 
 /// Should be called on `WlSurface::commit`
 pub fn handle_commit(space: &mut Space<WindowElement>, surface: &WlSurface) -> Option<()> {
-    let window = space
-        .elements()
-        .find(|w| w.toplevel().wl_surface() == surface)
-        .cloned()?;
+    let window = space.elements().find(|w| w.has_surface(surface)).cloned()?;
 
     let mut window_loc = space.element_location(&window)?;
     let geometry = window.geometry();
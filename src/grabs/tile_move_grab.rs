@@ -0,0 +1,172 @@
+use crate::{focus::FocusTarget, shell::WindowElement, state::Backend, SmallCageState};
+use smithay::{
+    input::pointer::{
+        AxisFrame, ButtonEvent, GestureHoldBeginEvent, GestureHoldEndEvent, GesturePinchBeginEvent,
+        GesturePinchEndEvent, GesturePinchUpdateEvent, GestureSwipeBeginEvent,
+        GestureSwipeEndEvent, GestureSwipeUpdateEvent, GrabStartData as PointerGrabStartData,
+        MotionEvent, PointerGrab, PointerInnerHandle, RelativeMotionEvent,
+    },
+    output::Output,
+    utils::{Logical, Point},
+};
+
+/// Moves a tiled window into the tile it is dropped on, the tiles are only
+/// laid out again once the button is released
+pub struct TileMoveSurfaceGrab<BackendData: Backend + 'static> {
+    start_data: PointerGrabStartData<SmallCageState<BackendData>>,
+    window: WindowElement,
+    output: Output,
+}
+
+impl<BackendData: Backend + 'static> TileMoveSurfaceGrab<BackendData> {
+    pub fn start(
+        start_data: PointerGrabStartData<SmallCageState<BackendData>>,
+        window: WindowElement,
+        output: Output,
+    ) -> Self {
+        Self {
+            start_data,
+            window,
+            output,
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> PointerGrab<SmallCageState<BackendData>>
+    for TileMoveSurfaceGrab<BackendData>
+{
+    fn motion(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        _focus: Option<(FocusTarget, Point<i32, Logical>)>,
+        event: &MotionEvent,
+    ) {
+        // While the grab is active, no client has pointer focus
+        handle.motion(data, None, event);
+    }
+
+    fn relative_motion(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        focus: Option<(FocusTarget, Point<i32, Logical>)>,
+        event: &RelativeMotionEvent,
+    ) {
+        handle.relative_motion(data, focus, event);
+    }
+
+    fn button(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        event: &ButtonEvent,
+    ) {
+        handle.button(data, event);
+
+        // The button is a button code as defined in the
+        // Linux kernel's linux/input-event-codes.h header file, e.g. BTN_LEFT.
+        const BTN_LEFT: u32 = 0x110;
+
+        if !handle.current_pressed().contains(&BTN_LEFT) {
+            // No more buttons are pressed, drop the window and release the
+            // grab, the pointer focus is restored on the new layout
+            let location = handle.current_location();
+            data.move_tiled_element(&self.window, &self.output, location);
+            handle.unset_grab(data, event.serial, event.time, true);
+        }
+    }
+
+    fn axis(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        details: AxisFrame,
+    ) {
+        handle.axis(data, details)
+    }
+
+    fn frame(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+    ) {
+        handle.frame(data);
+    }
+
+    fn gesture_swipe_begin(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        event: &GestureSwipeBeginEvent,
+    ) {
+        handle.gesture_swipe_begin(data, event)
+    }
+
+    fn gesture_swipe_update(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        event: &GestureSwipeUpdateEvent,
+    ) {
+        handle.gesture_swipe_update(data, event)
+    }
+
+    fn gesture_swipe_end(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        event: &GestureSwipeEndEvent,
+    ) {
+        handle.gesture_swipe_end(data, event)
+    }
+
+    fn gesture_pinch_begin(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        event: &GesturePinchBeginEvent,
+    ) {
+        handle.gesture_pinch_begin(data, event)
+    }
+
+    fn gesture_pinch_update(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        event: &GesturePinchUpdateEvent,
+    ) {
+        handle.gesture_pinch_update(data, event)
+    }
+
+    fn gesture_pinch_end(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        event: &GesturePinchEndEvent,
+    ) {
+        handle.gesture_pinch_end(data, event)
+    }
+
+    fn gesture_hold_begin(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        event: &GestureHoldBeginEvent,
+    ) {
+        handle.gesture_hold_begin(data, event)
+    }
+
+    fn gesture_hold_end(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        event: &GestureHoldEndEvent,
+    ) {
+        handle.gesture_hold_end(data, event)
+    }
+
+    fn start_data(&self) -> &PointerGrabStartData<SmallCageState<BackendData>> {
+        &self.start_data
+    }
+}
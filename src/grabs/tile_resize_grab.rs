@@ -0,0 +1,184 @@
+use crate::{
+    focus::FocusTarget, grabs::ResizeEdge, shell::WindowElement, state::Backend, SmallCageState,
+};
+use smithay::{
+    input::pointer::{
+        AxisFrame, ButtonEvent, GestureHoldBeginEvent, GestureHoldEndEvent, GesturePinchBeginEvent,
+        GesturePinchEndEvent, GesturePinchUpdateEvent, GestureSwipeBeginEvent,
+        GestureSwipeEndEvent, GestureSwipeUpdateEvent, GrabStartData as PointerGrabStartData,
+        MotionEvent, PointerGrab, PointerInnerHandle, RelativeMotionEvent,
+    },
+    output::Output,
+    utils::{Logical, Point},
+};
+
+/// Resizes a tiled window by moving the splits of the tiling tree
+pub struct TileResizeSurfaceGrab<BackendData: Backend + 'static> {
+    start_data: PointerGrabStartData<SmallCageState<BackendData>>,
+    window: WindowElement,
+    output: Output,
+    edges: ResizeEdge,
+    last_location: Point<f64, Logical>,
+}
+
+impl<BackendData: Backend + 'static> TileResizeSurfaceGrab<BackendData> {
+    pub fn start(
+        start_data: PointerGrabStartData<SmallCageState<BackendData>>,
+        window: WindowElement,
+        output: Output,
+        edges: ResizeEdge,
+    ) -> Self {
+        let last_location = start_data.location;
+        Self {
+            start_data,
+            window,
+            output,
+            edges,
+            last_location,
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> PointerGrab<SmallCageState<BackendData>>
+    for TileResizeSurfaceGrab<BackendData>
+{
+    fn motion(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        _focus: Option<(FocusTarget, Point<i32, Logical>)>,
+        event: &MotionEvent,
+    ) {
+        // While the grab is active, no client has pointer focus
+        handle.motion(data, None, event);
+
+        let delta: Point<i32, Logical> = (event.location - self.last_location).to_i32_round();
+        if delta.x == 0 && delta.y == 0 {
+            return;
+        }
+        // keep the part which was rounded away for the next motion
+        self.last_location += delta.to_f64();
+        data.resize_tiled_element(&self.window, &self.output, self.edges, delta);
+    }
+
+    fn relative_motion(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        focus: Option<(FocusTarget, Point<i32, Logical>)>,
+        event: &RelativeMotionEvent,
+    ) {
+        handle.relative_motion(data, focus, event);
+    }
+
+    fn button(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        event: &ButtonEvent,
+    ) {
+        handle.button(data, event);
+
+        // The button is a button code as defined in the
+        // Linux kernel's linux/input-event-codes.h header file, e.g. BTN_LEFT.
+        const BTN_LEFT: u32 = 0x110;
+
+        if !handle.current_pressed().contains(&BTN_LEFT) {
+            // No more buttons are pressed, release the grab.
+            handle.unset_grab(data, event.serial, event.time, true);
+        }
+    }
+
+    fn axis(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        details: AxisFrame,
+    ) {
+        handle.axis(data, details)
+    }
+
+    fn frame(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+    ) {
+        handle.frame(data);
+    }
+
+    fn gesture_swipe_begin(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        event: &GestureSwipeBeginEvent,
+    ) {
+        handle.gesture_swipe_begin(data, event)
+    }
+
+    fn gesture_swipe_update(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        event: &GestureSwipeUpdateEvent,
+    ) {
+        handle.gesture_swipe_update(data, event)
+    }
+
+    fn gesture_swipe_end(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        event: &GestureSwipeEndEvent,
+    ) {
+        handle.gesture_swipe_end(data, event)
+    }
+
+    fn gesture_pinch_begin(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        event: &GesturePinchBeginEvent,
+    ) {
+        handle.gesture_pinch_begin(data, event)
+    }
+
+    fn gesture_pinch_update(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        event: &GesturePinchUpdateEvent,
+    ) {
+        handle.gesture_pinch_update(data, event)
+    }
+
+    fn gesture_pinch_end(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        event: &GesturePinchEndEvent,
+    ) {
+        handle.gesture_pinch_end(data, event)
+    }
+
+    fn gesture_hold_begin(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        event: &GestureHoldBeginEvent,
+    ) {
+        handle.gesture_hold_begin(data, event)
+    }
+
+    fn gesture_hold_end(
+        &mut self,
+        data: &mut SmallCageState<BackendData>,
+        handle: &mut PointerInnerHandle<'_, SmallCageState<BackendData>>,
+        event: &GestureHoldEndEvent,
+    ) {
+        handle.gesture_hold_end(data, event)
+    }
+
+    fn start_data(&self) -> &PointerGrabStartData<SmallCageState<BackendData>> {
+        &self.start_data
+    }
+}
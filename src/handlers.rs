@@ -1,13 +1,21 @@
 mod compositor;
+mod layer_shell;
+mod pointer_constraints;
 mod ssd;
 mod xdg_shell;
 
+use crate::focus::FocusTarget;
+use crate::ipc::Event;
 use crate::shell::WindowElement;
 use crate::state::Backend;
 use crate::SmallCageState;
-use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+use smithay::reexports::wayland_server::Resource;
 use smithay::wayland::output::OutputHandler;
-pub use ssd::{HeaderBar, HEADER_BAR_HEIGHT};
+use smithay::wayland::seat::WaylandFocus;
+pub use ssd::{
+    header_bar_height, HeaderBar, BG_COLOR, CLOSE_COLOR, FULLSCREEN_COLOR, HEADER_BAR_HEIGHT,
+    STATE_CHANGE_COLOR,
+};
 
 //
 // Wl Seat
@@ -18,16 +26,22 @@ use smithay::wayland::selection::data_device::{
     set_data_device_focus, ClientDndGrabHandler, DataDeviceHandler, ServerDndGrabHandler,
 };
 use smithay::wayland::selection::primary_selection::{
-    PrimarySelectionHandler, PrimarySelectionState,
+    set_primary_focus, PrimarySelectionHandler, PrimarySelectionState,
 };
 use smithay::wayland::selection::SelectionHandler;
 use smithay::{delegate_data_device, delegate_output, delegate_primary_selection, delegate_seat};
+#[cfg(feature = "xwayland")]
+use smithay::{
+    input::Seat,
+    wayland::selection::{SelectionSource, SelectionTarget},
+};
+#[cfg(feature = "xwayland")]
+use std::os::fd::OwnedFd;
 
 impl<BackendData: Backend + 'static> SeatHandler for SmallCageState<BackendData> {
-    type KeyboardFocus = WindowElement;
-    type PointerFocus = WindowElement;
-    // TODO:
-    type TouchFocus = WlSurface;
+    type KeyboardFocus = FocusTarget;
+    type PointerFocus = FocusTarget;
+    type TouchFocus = FocusTarget;
 
     fn seat_state(&mut self) -> &mut SeatState<SmallCageState<BackendData>> {
         &mut self.seat_state
@@ -41,14 +55,29 @@ impl<BackendData: Backend + 'static> SeatHandler for SmallCageState<BackendData>
         *self.cursor_status.lock().unwrap() = image;
     }
 
-    fn focus_changed(
-        &mut self,
-        seat: &smithay::input::Seat<Self>,
-        focused: Option<&WindowElement>,
-    ) {
+    fn focus_changed(&mut self, seat: &smithay::input::Seat<Self>, focused: Option<&FocusTarget>) {
         let dh = &self.display_handle;
-        let client = focused.and_then(|s| dh.get_client(s.id()).ok());
-        set_data_device_focus(dh, seat, client)
+        let client = focused
+            .and_then(WaylandFocus::wl_surface)
+            .and_then(|surface| dh.get_client(surface.id()).ok());
+        set_data_device_focus(dh, seat, client.clone());
+        set_primary_focus(dh, seat, client);
+        let window = focused.and_then(FocusTarget::window);
+        // X11 windows stack on their own, the focused one has to come up
+        #[cfg(feature = "xwayland")]
+        if let (Some(xwm), Some(surface)) = (
+            self.xwm.as_mut(),
+            window.and_then(WindowElement::x11_surface),
+        ) {
+            if let Err(err) = xwm.raise_window(surface) {
+                tracing::warn!("Failed to raise the X11 window: {}", err);
+            }
+        }
+        self.release_pointer_constraints(window);
+        self.activate_pointer_constraint();
+        self.ipc_event(Event::Focus {
+            id: window.map(WindowElement::ipc_id),
+        });
     }
 }
 
@@ -67,6 +96,37 @@ delegate_primary_selection!(@<BackendData: Backend + 'static> SmallCageState<Bac
 
 impl<BackendData: Backend + 'static> SelectionHandler for SmallCageState<BackendData> {
     type SelectionUserData = ();
+
+    // selections of Wayland clients are offered to the X11 clients as well
+    #[cfg(feature = "xwayland")]
+    fn new_selection(
+        &mut self,
+        ty: SelectionTarget,
+        source: Option<SelectionSource>,
+        _seat: Seat<Self>,
+    ) {
+        if let Some(xwm) = self.xwm.as_mut() {
+            if let Err(err) = xwm.new_selection(ty, source.map(|source| source.mime_types())) {
+                tracing::warn!(?ty, "Failed to set the X11 selection: {}", err);
+            }
+        }
+    }
+
+    #[cfg(feature = "xwayland")]
+    fn send_selection(
+        &mut self,
+        ty: SelectionTarget,
+        mime_type: String,
+        fd: OwnedFd,
+        _seat: Seat<Self>,
+        _user_data: &(),
+    ) {
+        if let Some(xwm) = self.xwm.as_mut() {
+            if let Err(err) = xwm.send_selection(ty, mime_type, fd, self.handle.clone()) {
+                tracing::warn!(?ty, "Failed to send the X11 selection: {}", err);
+            }
+        }
+    }
 }
 
 impl<BackendData: Backend + 'static> DataDeviceHandler for SmallCageState<BackendData> {
@@ -1,18 +1,20 @@
+#[cfg(feature = "xwayland")]
+use crate::CalloopData;
 use crate::{
+    focus::FocusTarget,
     grabs::normal_resize_grab,
     shell::WindowElement,
     state::{Backend, ClientState},
     SmallCageState,
 };
+#[cfg(feature = "xwayland")]
+use smithay::xwayland::X11Wm;
 use smithay::{
     backend::renderer::utils::on_commit_buffer_handler,
     delegate_compositor, delegate_shm,
-    reexports::{
-        wayland_protocols::xdg::shell::server::xdg_toplevel,
-        wayland_server::{
-            protocol::{wl_buffer, wl_surface::WlSurface},
-            Client,
-        },
+    reexports::wayland_server::{
+        protocol::{wl_buffer, wl_surface::WlSurface},
+        Client, Resource,
     },
     utils::{Logical, Point, SERIAL_COUNTER},
     wayland::{
@@ -37,22 +39,23 @@ impl<BackendData: Backend + 'static> CompositorHandler for SmallCageState<Backen
     }
 
     fn commit(&mut self, surface: &WlSurface) {
+        // the surfaces of X11 windows are matched up with their windows here
+        #[cfg(feature = "xwayland")]
+        X11Wm::commit_hook::<CalloopData<BackendData>>(surface);
         on_commit_buffer_handler::<Self>(surface);
         if !is_sync_subsurface(surface) {
             let mut root = surface.clone();
             while let Some(parent) = get_parent(&root) {
                 root = parent;
             }
-            if let Some(window) = self
-                .space
-                .elements()
-                .find(|w| w.toplevel().wl_surface() == &root)
-            {
+            // windows on hidden workspaces still need to track their state
+            if let Some(window) = self.find_window(&root) {
                 window.on_commit();
             }
         };
 
         self.handle_xdg_commit(surface);
+        self.handle_layer_commit(surface);
         self.handle_popup_commit(surface);
         self.popups.commit(surface);
         normal_resize_grab::handle_commit(&mut self.space, surface);
@@ -73,20 +76,26 @@ delegate_compositor!(@<BackendData: Backend + 'static> SmallCageState<BackendDat
 delegate_shm!(@<BackendData: Backend + 'static> SmallCageState<BackendData>);
 
 impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
+    /// The window under the pointer, none while it is over a panel
     pub fn find_current_select_surface(&self) -> Option<(WindowElement, Point<i32, Logical>)> {
-        self.surface_under_pointer(&self.pointer)
+        match self.surface_under_pointer(&self.pointer)? {
+            (FocusTarget::Window(window), location) => Some((window, location)),
+            (FocusTarget::LayerSurface(_), _) => None,
+        }
     }
 
     pub fn find_current_focus_window(&self) -> Option<&WindowElement> {
-        self.space.elements().find(|w| {
-            w.toplevel()
-                .current_state()
-                .states
-                .contains(xdg_toplevel::State::Activated)
-        })
+        self.space.elements().find(|w| w.is_activated())
     }
 
     pub fn handle_focus_change(&mut self) -> Option<()> {
+        // an exclusive layer surface gets the keyboard back instead
+        if self.keep_exclusive_layer_focus() {
+            return None;
+        }
+        if self.show_window_preview || self.layer_has_keyboard() {
+            return None;
+        }
         if let Some(window_focus) = self.find_current_focus_window() {
             if window_focus.is_untiled_window() {
                 return None;
@@ -97,19 +106,21 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
             return Some(());
         }
         let dh = &self.display_handle;
-        let client = dh.get_client(window.id()).ok();
+        let client = window
+            .wl_surface()
+            .and_then(|surface| dh.get_client(surface.id()).ok());
         set_data_device_focus(dh, &self.seat, client.clone());
         set_primary_focus(dh, &self.seat, client);
-        let keyboard = self.seat.get_keyboard().unwrap();
+        let keyboard = self.seat.get_keyboard()?;
         let serial = SERIAL_COUNTER.next_serial();
 
         self.space.raise_element(&window, true);
         self.space.elements().for_each(|window| {
-            window.toplevel().send_pending_configure();
+            window.send_pending_configure();
         });
         self.raise_untiled_elements();
 
-        keyboard.set_focus(self, Some(window), serial);
+        keyboard.set_focus(self, Some(window.into()), serial);
         Some(())
     }
 }
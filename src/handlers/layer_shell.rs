@@ -0,0 +1,243 @@
+use smithay::{
+    delegate_layer_shell,
+    desktop::{layer_map_for_output, LayerSurface, PopupKind, WindowSurfaceType},
+    output::Output,
+    reexports::wayland_server::protocol::{wl_output::WlOutput, wl_surface::WlSurface},
+    utils::{Rectangle, SERIAL_COUNTER},
+    wayland::{
+        compositor::with_states,
+        shell::{
+            wlr_layer::{
+                KeyboardInteractivity, Layer, LayerSurface as WlrLayerSurface,
+                LayerSurfaceCachedState, LayerSurfaceData, WlrLayerShellHandler,
+                WlrLayerShellState,
+            },
+            xdg::PopupSurface,
+        },
+    },
+};
+
+use crate::{
+    focus::FocusTarget,
+    state::{Backend, SmallCageState},
+};
+
+impl<BackendData: Backend + 'static> WlrLayerShellHandler for SmallCageState<BackendData> {
+    fn shell_state(&mut self) -> &mut WlrLayerShellState {
+        &mut self.layer_shell_state
+    }
+
+    fn new_layer_surface(
+        &mut self,
+        surface: WlrLayerSurface,
+        output: Option<WlOutput>,
+        _layer: Layer,
+        namespace: String,
+    ) {
+        // surfaces which do not care about the output go to the current one
+        let Some(output) = output
+            .as_ref()
+            .and_then(Output::from_resource)
+            .or_else(|| self.current_output())
+        else {
+            tracing::warn!(namespace, "No output for the layer surface");
+            surface.send_close();
+            return;
+        };
+        let layer = LayerSurface::new(surface, namespace);
+        if let Err(err) = layer_map_for_output(&output).map_layer(&layer) {
+            tracing::warn!("Failed to map the layer surface: {}", err);
+        }
+    }
+
+    fn new_popup(&mut self, parent: WlrLayerSurface, popup: PopupSurface) {
+        // keep the popup on the output of its layer surface
+        if let Some((output, layer)) = self.find_layer(&parent) {
+            let map = layer_map_for_output(&output);
+            if let (Some(layer_geo), Some(output_geo)) = (
+                map.layer_geometry(&layer),
+                self.space.output_geometry(&output),
+            ) {
+                let target = Rectangle::from_loc_and_size(
+                    (-layer_geo.loc.x, -layer_geo.loc.y),
+                    output_geo.size,
+                );
+                popup.with_pending_state(|state| {
+                    state.geometry = state.positioner.get_unconstrained_geometry(target);
+                });
+            }
+        }
+        if let Err(err) = self.popups.track_popup(PopupKind::from(popup)) {
+            tracing::warn!("Failed to track the popup of a layer surface: {}", err);
+        }
+    }
+
+    fn layer_destroyed(&mut self, surface: WlrLayerSurface) {
+        let Some((output, layer)) = self.find_layer(&surface) else {
+            return;
+        };
+        layer_map_for_output(&output).unmap_layer(&layer);
+        // the windows get the space of its exclusive zone back
+        self.relayout_output(&output);
+        if self.keyboard_focus() == Some(FocusTarget::LayerSurface(layer)) {
+            self.restore_window_focus();
+        }
+    }
+}
+
+delegate_layer_shell!(@<BackendData: Backend + 'static> SmallCageState<BackendData>);
+
+/// Whether a layer surface wants the keyboard, and if it wants it for itself
+fn keyboard_interactivity(layer: &LayerSurface) -> KeyboardInteractivity {
+    with_states(layer.wl_surface(), |states| {
+        states
+            .cached_state
+            .current::<LayerSurfaceCachedState>()
+            .keyboard_interactivity
+    })
+}
+
+impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
+    /// The output `surface` is mapped on, together with its layer surface
+    fn find_layer(&self, surface: &WlrLayerSurface) -> Option<(Output, LayerSurface)> {
+        self.space.outputs().find_map(|output| {
+            let map = layer_map_for_output(output);
+            let layer = map
+                .layers()
+                .find(|layer| layer.layer_surface() == surface)
+                .cloned()?;
+            Some((output.clone(), layer))
+        })
+    }
+
+    pub fn handle_layer_commit(&mut self, surface: &WlSurface) {
+        let Some(output) = self
+            .space
+            .outputs()
+            .find(|output| {
+                layer_map_for_output(output)
+                    .layer_for_surface(surface, WindowSurfaceType::TOPLEVEL)
+                    .is_some()
+            })
+            .cloned()
+        else {
+            return;
+        };
+        let initial_configure_sent = with_states(surface, |states| {
+            states
+                .data_map
+                .get::<LayerSurfaceData>()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .initial_configure_sent
+        });
+
+        let (layer, zone_changed) = {
+            let mut map = layer_map_for_output(&output);
+            let zone = map.non_exclusive_zone();
+            // arrange before the initial configure, so that it has the size
+            // the client asked for
+            map.arrange();
+            let Some(layer) = map
+                .layer_for_surface(surface, WindowSurfaceType::TOPLEVEL)
+                .cloned()
+            else {
+                return;
+            };
+            if !initial_configure_sent {
+                layer.layer_surface().send_configure();
+            }
+            (layer, map.non_exclusive_zone() != zone)
+        };
+        if zone_changed {
+            self.relayout_output(&output);
+        }
+
+        let target = FocusTarget::LayerSurface(layer.clone());
+        let focused = self.keyboard_focus() == Some(target.clone());
+        match keyboard_interactivity(&layer) {
+            // lock screens and launchers take the keyboard as soon as they
+            // are shown, only the top and overlay layers may do that
+            KeyboardInteractivity::Exclusive
+                if initial_configure_sent
+                    && !focused
+                    && matches!(layer.layer(), Layer::Top | Layer::Overlay) =>
+            {
+                if let Some(keyboard) = self.seat.get_keyboard() {
+                    keyboard.set_focus(self, Some(target), SERIAL_COUNTER.next_serial());
+                }
+            }
+            KeyboardInteractivity::None if focused => self.restore_window_focus(),
+            _ => {}
+        }
+    }
+
+    /// Whether the keyboard is held by a layer surface which wants it, the
+    /// focus does not follow the pointer to the windows then
+    pub fn layer_has_keyboard(&self) -> bool {
+        matches!(
+            self.keyboard_focus(),
+            Some(FocusTarget::LayerSurface(layer))
+                if keyboard_interactivity(&layer) != KeyboardInteractivity::None
+        )
+    }
+
+    /// The layer surface in the top or overlay layer which takes the keyboard
+    /// for itself, like a lock screen or a launcher
+    fn exclusive_layer_surface(&self) -> Option<LayerSurface> {
+        self.space.outputs().find_map(|output| {
+            layer_map_for_output(output)
+                .layers()
+                .filter(|layer| matches!(layer.layer(), Layer::Top | Layer::Overlay))
+                .find(|layer| keyboard_interactivity(layer) == KeyboardInteractivity::Exclusive)
+                .cloned()
+        })
+    }
+
+    /// Keep the keyboard on the layer surface which takes it for itself,
+    /// windows may not take it away while it is shown. Whether there is one.
+    pub fn keep_exclusive_layer_focus(&mut self) -> bool {
+        let Some(layer) = self.exclusive_layer_surface() else {
+            return false;
+        };
+        let target = FocusTarget::LayerSurface(layer);
+        if self.keyboard_focus() != Some(target.clone()) {
+            if let Some(keyboard) = self.seat.get_keyboard() {
+                keyboard.set_focus(self, Some(target), SERIAL_COUNTER.next_serial());
+            }
+        }
+        true
+    }
+
+    /// Give the keyboard to a layer surface clicked on, if it takes it
+    pub fn focus_layer_surface(&mut self, layer: LayerSurface) {
+        if keyboard_interactivity(&layer) == KeyboardInteractivity::None {
+            return;
+        }
+        if let Some(keyboard) = self.seat.get_keyboard() {
+            keyboard.set_focus(
+                self,
+                Some(FocusTarget::LayerSurface(layer)),
+                SERIAL_COUNTER.next_serial(),
+            );
+        }
+    }
+
+    fn keyboard_focus(&self) -> Option<FocusTarget> {
+        self.seat.get_keyboard()?.current_focus()
+    }
+
+    /// Hand the keyboard back to the active window once a layer surface is
+    /// done with it
+    fn restore_window_focus(&mut self) {
+        let window = self.find_current_focus_window().cloned();
+        if let Some(keyboard) = self.seat.get_keyboard() {
+            keyboard.set_focus(
+                self,
+                window.map(FocusTarget::from),
+                SERIAL_COUNTER.next_serial(),
+            );
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use smithay::{
+    delegate_pointer_constraints,
+    desktop::space::SpaceElement,
+    input::pointer::PointerHandle,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Point},
+    wayland::pointer_constraints::{
+        with_pointer_constraint, PointerConstraint, PointerConstraintsHandler,
+    },
+};
+
+use crate::{
+    shell::WindowElement,
+    state::{Backend, SmallCageState},
+};
+
+use super::header_bar_height;
+
+impl<BackendData: Backend + 'static> PointerConstraintsHandler for SmallCageState<BackendData> {
+    fn new_constraint(&mut self, _surface: &WlSurface, _pointer: &PointerHandle<Self>) {
+        self.activate_pointer_constraint();
+    }
+
+    fn cursor_position_hint(
+        &mut self,
+        surface: &WlSurface,
+        pointer: &PointerHandle<Self>,
+        location: Point<f64, Logical>,
+    ) {
+        let active = with_pointer_constraint(surface, pointer, |constraint| {
+            constraint.map_or(false, |constraint| constraint.is_active())
+        });
+        if !active {
+            return;
+        }
+        // the cursor shows up here again once the pointer is unlocked
+        let Some(origin) = self
+            .find_window(surface)
+            .and_then(|window| self.surface_origin(&window))
+        else {
+            return;
+        };
+        pointer.set_location(origin + location);
+    }
+}
+
+delegate_pointer_constraints!(@<BackendData: Backend + 'static> SmallCageState<BackendData>);
+
+impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
+    /// Where the surface of `window` is in the space, below the header bar
+    /// of server side decorated windows
+    fn surface_origin(&self, window: &WindowElement) -> Option<Point<f64, Logical>> {
+        let mut origin = self.space.element_location(window)? - window.geometry().loc;
+        if window.is_ssd() {
+            origin.y += header_bar_height();
+        }
+        Some(origin.to_f64())
+    }
+
+    /// Where the pointer may go on its way to `target`. An active constraint
+    /// of the window under it keeps it in place, or inside the window and
+    /// the region of the constraint.
+    pub fn constrain_pointer(&self, target: Point<f64, Logical>) -> Point<f64, Logical> {
+        let current = self.pointer.current_location();
+        let Some((window, _)) = self.window_under(current) else {
+            return target;
+        };
+        let Some(origin) = self.surface_origin(&window) else {
+            return target;
+        };
+        let Some(surface) = window.wl_surface() else {
+            return target;
+        };
+        with_pointer_constraint(&surface, &self.pointer, |constraint| {
+            let Some(constraint) = constraint.filter(|constraint| constraint.is_active()) else {
+                return target;
+            };
+            match &*constraint {
+                PointerConstraint::Locked(_) => current,
+                PointerConstraint::Confined(confined) => {
+                    let on_window = self
+                        .window_under(target)
+                        .map_or(false, |(under, _)| under == window);
+                    let local = (target - origin).to_i32_round();
+                    let in_region = confined
+                        .region()
+                        .map_or(true, |region| region.contains(local));
+                    if on_window && in_region {
+                        target
+                    } else {
+                        current
+                    }
+                }
+            }
+        })
+    }
+
+    /// Activate the constraint of the focused window once the pointer is in
+    /// its region, constraints of other windows do not hold the pointer
+    pub fn activate_pointer_constraint(&mut self) {
+        let location = self.pointer.current_location();
+        let Some((window, _)) = self.window_under(location) else {
+            return;
+        };
+        if self.find_current_focus_window() != Some(&window) {
+            return;
+        }
+        let Some(origin) = self.surface_origin(&window) else {
+            return;
+        };
+        let Some(surface) = window.wl_surface() else {
+            return;
+        };
+        with_pointer_constraint(&surface, &self.pointer, |constraint| {
+            let Some(constraint) = constraint.filter(|constraint| !constraint.is_active()) else {
+                return;
+            };
+            let local = (location - origin).to_i32_round();
+            if constraint
+                .region()
+                .map_or(true, |region| region.contains(local))
+            {
+                constraint.activate();
+            }
+        });
+    }
+
+    /// Let go of the pointer for all windows but the one with the focus
+    pub fn release_pointer_constraints(&mut self, focused: Option<&WindowElement>) {
+        for window in self.space.elements() {
+            if Some(window) == focused {
+                continue;
+            }
+            let Some(surface) = window.wl_surface() else {
+                continue;
+            };
+            with_pointer_constraint(&surface, &self.pointer, |constraint| {
+                if let Some(constraint) = constraint.filter(|constraint| constraint.is_active()) {
+                    constraint.deactivate();
+                }
+            });
+        }
+    }
+}
@@ -11,6 +11,7 @@ use smithay::{
 };
 
 use crate::{
+    config::config,
     shell::WindowElement,
     state::{Backend, SmallCageState},
 };
@@ -28,18 +29,23 @@ pub struct HeaderBar {
     pub fullscreen_button: SolidColorBuffer,
 }
 
-const BG_COLOR: [f32; 4] = [0.75f32, 0.9f32, 0.78f32, 1f32];
-const FULLSCREEN_COLOR: [f32; 4] = [1f32, 0.965f32, 0.71f32, 1f32];
-const STATE_CHANGE_COLOR: [f32; 4] = [0.85f32, 0.665f32, 0.71f32, 1f32];
-const CLOSE_COLOR: [f32; 4] = [1f32, 0.66f32, 0.612f32, 1f32];
+pub const BG_COLOR: [f32; 4] = [0.75f32, 0.9f32, 0.78f32, 1f32];
+pub const FULLSCREEN_COLOR: [f32; 4] = [1f32, 0.965f32, 0.71f32, 1f32];
+pub const STATE_CHANGE_COLOR: [f32; 4] = [0.85f32, 0.665f32, 0.71f32, 1f32];
+pub const CLOSE_COLOR: [f32; 4] = [1f32, 0.66f32, 0.612f32, 1f32];
 const FULLSCREEN_COLOR_HOVER: [f32; 4] = [0.71f32, 0.424f32, 0f32, 1f32];
 const STATE_CHANGE_COLOR_HOVER: [f32; 4] = [0.71f32, 0.624f32, 0f32, 1f32];
 const CLOSE_COLOR_HOVER: [f32; 4] = [0.75f32, 0.11f32, 0.016f32, 1f32];
 
+/// The default height of the header bar, the config can change it
 pub const HEADER_BAR_HEIGHT: i32 = 25;
-const BUTTON_HEIGHT: u32 = HEADER_BAR_HEIGHT as u32;
 const BUTTON_WIDTH: u32 = 25;
 
+/// The height of the header bar of server side decorated windows
+pub fn header_bar_height() -> i32 {
+    config().theme.header_bar_height
+}
+
 impl HeaderBar {
     pub fn pointer_enter(&mut self, loc: Point<f64, Logical>) {
         self.pointer_loc = Some(loc);
@@ -58,7 +64,7 @@ impl HeaderBar {
     ) {
         match self.pointer_loc.as_ref() {
             Some(loc) if loc.x > (self.width - BUTTON_WIDTH) as f64 => {
-                window.toplevel().send_close();
+                window.send_close();
             }
             Some(loc) if loc.x <= BUTTON_WIDTH as f64 => {
                 let window = window.clone();
@@ -67,8 +73,10 @@ impl HeaderBar {
                 });
             }
             Some(_) => {
+                let Some(toplevel) = window.toplevel().cloned() else {
+                    return;
+                };
                 let seat = seat.clone();
-                let toplevel = window.toplevel().clone();
                 state
                     .handle
                     .insert_idle(move |data| data.state.move_request_xdg(&toplevel, seat, serial));
@@ -77,14 +85,22 @@ impl HeaderBar {
         }
     }
 
+    /// Make the next redraw update every button, after the theme changed
+    pub fn invalidate(&mut self) {
+        self.width = 0;
+    }
+
     pub fn redraw(&mut self, width: u32) {
         if width == 0 {
             self.width = 0;
             return;
         }
 
+        let theme = config().theme.clone();
+        let button_height = theme.header_bar_height;
+
         self.background
-            .update((width as i32, HEADER_BAR_HEIGHT), BG_COLOR);
+            .update((width as i32, theme.header_bar_height), theme.header_bar);
 
         let mut needs_redraw_buttons = false;
         if width != self.width {
@@ -100,7 +116,7 @@ impl HeaderBar {
             && (needs_redraw_buttons || !self.state_button_hover)
         {
             self.state_button.update(
-                (BUTTON_WIDTH as i32, BUTTON_HEIGHT as i32),
+                (BUTTON_WIDTH as i32, button_height),
                 STATE_CHANGE_COLOR_HOVER,
             );
             self.state_button_hover = true;
@@ -111,10 +127,8 @@ impl HeaderBar {
             .unwrap_or(false)
             && (needs_redraw_buttons || self.state_button_hover)
         {
-            self.state_button.update(
-                (BUTTON_WIDTH as i32, BUTTON_HEIGHT as i32),
-                STATE_CHANGE_COLOR,
-            );
+            self.state_button
+                .update((BUTTON_WIDTH as i32, button_height), theme.state_button);
             self.state_button_hover = false;
         }
 
@@ -125,10 +139,8 @@ impl HeaderBar {
             .unwrap_or(false)
             && (needs_redraw_buttons || !self.close_button_hover)
         {
-            self.close_button.update(
-                (BUTTON_WIDTH as i32, BUTTON_HEIGHT as i32),
-                CLOSE_COLOR_HOVER,
-            );
+            self.close_button
+                .update((BUTTON_WIDTH as i32, button_height), CLOSE_COLOR_HOVER);
             self.close_button_hover = true;
         } else if !self
             .pointer_loc
@@ -138,7 +150,7 @@ impl HeaderBar {
             && (needs_redraw_buttons || self.close_button_hover)
         {
             self.close_button
-                .update((BUTTON_WIDTH as i32, BUTTON_HEIGHT as i32), CLOSE_COLOR);
+                .update((BUTTON_WIDTH as i32, button_height), theme.close_button);
             self.close_button_hover = false;
         }
 
@@ -151,10 +163,8 @@ impl HeaderBar {
             .unwrap_or(false)
             && (needs_redraw_buttons || !self.min_button_hover)
         {
-            self.fullscreen_button.update(
-                (BUTTON_WIDTH as i32, BUTTON_HEIGHT as i32),
-                FULLSCREEN_COLOR_HOVER,
-            );
+            self.fullscreen_button
+                .update((BUTTON_WIDTH as i32, button_height), FULLSCREEN_COLOR_HOVER);
             self.min_button_hover = true;
         } else if !self
             .pointer_loc
@@ -166,8 +176,8 @@ impl HeaderBar {
             && (needs_redraw_buttons || self.min_button_hover)
         {
             self.fullscreen_button.update(
-                (BUTTON_WIDTH as i32, BUTTON_HEIGHT as i32),
-                FULLSCREEN_COLOR,
+                (BUTTON_WIDTH as i32, button_height),
+                theme.fullscreen_button,
             );
             self.min_button_hover = false;
         }
@@ -5,6 +5,7 @@ use smithay::{
         pointer::{Focus, GrabStartData as PointerGrabStartData},
         Seat,
     },
+    output::Output,
     reexports::{
         wayland_protocols::xdg::{decoration as xdg_decoration, shell::server::xdg_toplevel},
         wayland_server::{
@@ -12,9 +13,10 @@ use smithay::{
             Resource,
         },
     },
-    utils::{Logical, Point, Rectangle, Serial, Size},
+    utils::{Logical, Point, Rectangle, Serial},
     wayland::{
         compositor::with_states,
+        seat::WaylandFocus,
         shell::xdg::{
             Configure, PopupSurface, PositionerState, SurfaceCachedState, ToplevelSurface,
             XdgPopupSurfaceData, XdgShellHandler, XdgShellState, XdgToplevelSurfaceData,
@@ -23,13 +25,17 @@ use smithay::{
 };
 
 use crate::{
-    grabs::{NormalMoveSurfaceGrab, ResizeSurfaceGrab},
+    config::config,
+    grabs::{NormalMoveSurfaceGrab, ResizeEdge, ResizeSurfaceGrab, TileResizeSurfaceGrab},
+    ipc::Event,
+    layout::inset,
     shell::{ElementState, WindowElement},
     state::{Backend, SplitState},
+    workspace::{active_workspace, output_tiling},
     SmallCageState,
 };
 
-use super::HEADER_BAR_HEIGHT;
+use super::header_bar_height;
 
 impl<BackendData: Backend + 'static> XdgShellHandler for SmallCageState<BackendData> {
     fn xdg_shell_state(&mut self) -> &mut XdgShellState {
@@ -38,18 +44,18 @@ impl<BackendData: Backend + 'static> XdgShellHandler for SmallCageState<BackendD
 
     fn new_toplevel(&mut self, surface: ToplevelSurface) {
         let window = WindowElement::new(surface);
+        let id = window.ipc_id();
         self.space.map_element(window, (0, 0), false);
+        self.ipc_event(Event::WindowOpened { id });
     }
 
     fn toplevel_destroyed(&mut self, surface: ToplevelSurface) {
-        let Some(window) = self
-            .space
-            .elements()
-            .find(|w| w.toplevel().wl_surface() == surface.wl_surface())
-            .cloned()
-        else {
+        let Some(window) = self.find_window(surface.wl_surface()) else {
             return;
         };
+        self.ipc_event(Event::WindowClosed {
+            id: window.ipc_id(),
+        });
         self.handle_dead_window(&window);
     }
 
@@ -62,6 +68,11 @@ impl<BackendData: Backend + 'static> XdgShellHandler for SmallCageState<BackendD
             // an example
             state.geometry = positioner.get_geometry();
         });
+        // popups of layer surfaces only get their parent later, they are
+        // tracked in the new_popup of the layer shell
+        if surface.get_parent_surface().is_none() {
+            return;
+        }
         if let Err(err) = self.popups.track_popup(PopupKind::from(surface)) {
             tracing::warn!("Failed to track popup: {}", err);
         }
@@ -78,12 +89,14 @@ impl<BackendData: Backend + 'static> XdgShellHandler for SmallCageState<BackendD
         serial: Serial,
         edges: xdg_toplevel::ResizeEdge,
     ) {
-        let Some(window) = self.space.elements().find(|w| w.toplevel() == &surface) else {
+        let Some(window) = self
+            .space
+            .elements()
+            .find(|w| w.toplevel() == Some(&surface))
+            .cloned()
+        else {
             return;
         };
-        if !window.is_untiled_window() {
-            return;
-        }
 
         let seat: Seat<Self> = Seat::from_resource(&seat).unwrap();
 
@@ -94,10 +107,20 @@ impl<BackendData: Backend + 'static> XdgShellHandler for SmallCageState<BackendD
         };
         let pointer = seat.get_pointer().unwrap();
 
+        // tiled windows are resized by moving the splits around them
+        if !window.is_untiled_window() {
+            let Some(output) = window.output() else {
+                return;
+            };
+            let grab = TileResizeSurfaceGrab::start(start_data, window, output, edges.into());
+            pointer.set_grab(self, grab, serial, Focus::Clear);
+            return;
+        }
+
         let window = self
             .space
             .elements()
-            .find(|w| w.toplevel().wl_surface() == wl_surface)
+            .find(|w| w.has_surface(wl_surface))
             .unwrap()
             .clone();
         let initial_window_location = self.space.element_location(&window).unwrap();
@@ -140,11 +163,7 @@ impl<BackendData: Backend + 'static> XdgShellHandler for SmallCageState<BackendD
         };
         use xdg_decoration::zv1::server::zxdg_toplevel_decoration_v1::Mode;
 
-        let Some(window) = self
-            .space
-            .elements()
-            .find(|w| w.toplevel().wl_surface() == &surface)
-        else {
+        let Some(window) = self.space.elements().find(|w| w.has_surface(&surface)) else {
             return;
         };
         let is_ssd = configure
@@ -172,7 +191,7 @@ fn check_grab<BackendData: Backend + 'static>(
 
     let (focus, _) = start_data.focus.as_ref()?;
     // If the focus was for a different surface, ignore the request.
-    if !focus.id().same_client_as(&surface.id()) {
+    if !focus.wl_surface()?.id().same_client_as(&surface.id()) {
         return None;
     }
 
@@ -197,7 +216,7 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
         let window = self
             .space
             .elements()
-            .find(|w| w.toplevel().wl_surface() == wl_surface)
+            .find(|w| w.has_surface(wl_surface))
             .unwrap()
             .clone();
         // TODO: not handle tiled window now
@@ -236,11 +255,13 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
         }
     }
     pub fn handle_xdg_commit(&mut self, surface: &WlSurface) -> Option<()> {
-        let mut window = self
+        let window = self
             .space
             .elements()
-            .find(|w| w.toplevel().wl_surface() == surface)
+            .find(|w| w.has_surface(surface))
             .cloned()?;
+        // X11 windows are mapped by the window manager instead
+        let toplevel = window.toplevel()?.clone();
 
         let initial_configure_sent = with_states(surface, |states| {
             states
@@ -271,15 +292,11 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
 
         let is_fixed_size = (max_size == min_size) && max_size != (0, 0).into();
 
-        if window.has_pedding_size() {
-            window.set_pedding_size(None);
-        }
-
         if !initial_configure_sent {
             if is_fixed_size {
                 window.set_is_fixed_window();
             }
-            window.toplevel().send_configure();
+            toplevel.send_configure();
         } else if isconfigured && !window.is_init() {
             window.set_inited();
             if window.is_fixed_window() {
@@ -306,12 +323,11 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
         }
     }
 
-    fn resize_element_commit(&mut self, window: &WindowElement) -> Option<()> {
-        let surface = window.toplevel().wl_surface();
-        match self.current_active_window_rectangle(surface) {
-            Some(element) => self.map_with_split(window, element),
-            None => self.map_one_element(window),
-        }
+    pub fn resize_element_commit(&mut self, window: &WindowElement) -> Option<()> {
+        let output = self.window_output(window)?;
+        window.set_output(Some(output.clone()));
+        window.set_workspace(active_workspace(&output));
+        self.map_tiled_element(window, &output)
     }
 
     pub fn handle_popup_commit(&self, surface: &WlSurface) {
@@ -339,106 +355,150 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
     }
 }
 
-// This is the logic of tile, here need to find current surface under pointer
-// with the split direction, split the space for new window
-//
-// TODO: I need a new element to mark if it is just init
+// This is the logic of tile, every output keeps a tree of its tiled windows.
+// A new window splits the focused one with the current split direction,
+// closing a window gives its space back to its sibling in the tree.
 impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
-    fn map_untitled_element(&mut self, window: &WindowElement) -> Option<()> {
-        let current_screen = self.current_screen_rectangle()?;
+    pub fn map_untitled_element(&mut self, window: &WindowElement) -> Option<()> {
+        let output = self.window_output(window)?;
+        let location = self.untiled_location(window, &output)?;
+        window.set_workspace(active_workspace(&output));
+        window.set_output(Some(output));
+        window.set_location(location);
+        self.space.map_element(window.clone(), location, true);
+        Some(())
+    }
+
+    /// Where an untiled window goes on `output`, centered in its tiling area
+    pub fn untiled_location(
+        &self,
+        window: &WindowElement,
+        output: &Output,
+    ) -> Option<Point<i32, Logical>> {
+        let current_screen = self.tiling_area(output)?;
         let max_size = window.to_untile_property_size();
         let mut screen_size = current_screen.size;
         if window.window_state().is_ssd {
-            screen_size.h += HEADER_BAR_HEIGHT;
+            screen_size.h += header_bar_height();
         }
-        let (x, y) = (
-            (screen_size.w - max_size.w) / 2,
-            (screen_size.h - max_size.h) / 2,
-        );
-        self.space.map_element(window.clone(), (x, y), true);
-        Some(())
+        Some(Point::from((
+            current_screen.loc.x + (screen_size.w - max_size.w) / 2,
+            current_screen.loc.y + (screen_size.h - max_size.h) / 2,
+        )))
     }
 
-    fn map_one_element(&mut self, window: &WindowElement) -> Option<()> {
-        let current_screen = self.current_screen_rectangle()?;
-        let loc = current_screen.loc;
-        let (w, h) = current_screen.size.into();
-        if !window.max_size().is_empty() && (w > window.max_size().w || h > window.max_size().h) {
-            window.set_is_fixed_window();
-            self.map_untitled_element(window);
-            return None;
-        }
-        window.toplevel().with_pending_state(|state| {
-            state.size = Some((w, h).into());
-        });
-        let mut current_screen_size = current_screen.size;
-        if window.window_state().is_ssd {
-            current_screen_size.h -= HEADER_BAR_HEIGHT;
+    fn map_tiled_element(&mut self, window: &WindowElement, output: &Output) -> Option<()> {
+        let area = self.tiling_area(output)?;
+        if !output_tiling(output).contains(window) {
+            let target = self.current_active_window_rectangle(window, output);
+            let mut tiles = output_tiling(output);
+            tiles.insert(window.clone(), target.as_ref(), self.splitstate);
+            let size = tiles.geometry(window, area)?.size;
+
+            // if the space is not enough, return to single map
+            let max_size = window.max_size();
+            if !max_size.is_empty() && (size.w > max_size.w || size.h > max_size.h) {
+                tiles.remove(window);
+                drop(tiles);
+                window.set_is_fixed_window();
+                self.map_untitled_element(window);
+                return None;
+            }
         }
-        window.toplevel().send_configure();
-        window.set_output_size(current_screen_size);
-        window.set_element_size(current_screen_size);
-        window.set_origin_pos(loc);
-        self.space.map_element(window.clone(), loc, true);
-
+        self.relayout_output(output);
         Some(())
     }
 
-    fn map_with_split(&mut self, window: &WindowElement, windowpre: WindowElement) -> Option<()> {
-        let current_screen = self.current_screen_rectangle()?;
-        let (x, y) = self.space.element_location(&windowpre)?.into();
-        let (w, h) = windowpre.geometry().size.into();
-
-        let (point, mut size): (Point<i32, Logical>, Size<i32, Logical>) = match self.splitstate {
-            SplitState::HSplit => {
-                let width = w / 2;
-                let height = h;
-                ((x + width, y).into(), (width, height).into())
-            }
-            SplitState::VSplit => {
-                let width = w;
-                let height = h / 2;
-                ((x, y + height).into(), (width, height).into())
-            }
+    /// Give every tiled window of the output the geometry its tree asks for
+    pub fn relayout_output(&mut self, output: &Output) {
+        let Some(area) = self.tiling_area(output) else {
+            return;
         };
-
-        // if the space is not enough, return to single map
-        if !window.max_size().is_empty()
-            && (size.w > window.max_size().w || size.h > window.max_size().h)
+        // half of the gap around the area and half around every window, so
+        // that windows are a full gap apart from each other and the border
+        let gaps = config().gaps;
+        let area = inset(area, gaps / 2);
+        let geometries = output_tiling(output).arrange(area);
+        for (window, geometry) in geometries {
+            let geometry = inset(geometry, gaps - gaps / 2);
+            let mut size = geometry.size;
+            if window.is_ssd() {
+                size.h -= header_bar_height();
+            }
+            window.configure(Rectangle::from_loc_and_size(geometry.loc, size));
+            self.space.map_element(window, geometry.loc, false);
+        }
+        // layouts may stack windows, keep the focused one visible
+        if let Some(focus) = self
+            .find_current_focus_window()
+            .filter(|w| !w.is_untiled_window() && w.output().as_ref() == Some(output))
+            .cloned()
         {
-            window.set_is_fixed_window();
-            self.map_untitled_element(window);
-            return None;
+            self.space.raise_element(&focus, false);
         }
+        self.raise_untiled_elements();
+    }
 
-        let mut afterwindowsize = size;
-
-        if window.window_state().is_ssd {
-            afterwindowsize.h -= HEADER_BAR_HEIGHT;
+    pub fn resize_tiled_element(
+        &mut self,
+        window: &WindowElement,
+        output: &Output,
+        edges: ResizeEdge,
+        delta: Point<i32, Logical>,
+    ) {
+        let Some(area) = self.tiling_area(output) else {
+            return;
+        };
+        {
+            let mut tiles = output_tiling(output);
+            if edges.intersects(ResizeEdge::LEFT | ResizeEdge::RIGHT) {
+                tiles.move_edge(
+                    window,
+                    SplitState::HSplit,
+                    edges.intersects(ResizeEdge::RIGHT),
+                    delta.x,
+                    area,
+                );
+            }
+            if edges.intersects(ResizeEdge::TOP | ResizeEdge::BOTTOM) {
+                tiles.move_edge(
+                    window,
+                    SplitState::VSplit,
+                    edges.intersects(ResizeEdge::BOTTOM),
+                    delta.y,
+                    area,
+                );
+            }
         }
+        self.relayout_output(output);
+    }
 
-        window.toplevel().with_pending_state(|state| {
-            state.size = Some(afterwindowsize);
-        });
-        window.toplevel().send_configure();
-
-        window.set_element_size(afterwindowsize);
-        window.set_output_size(current_screen.size);
-        window.set_origin_pos(point);
-        self.space.map_element(window.clone(), point, false);
-
-        if windowpre.window_state().is_ssd {
-            size.h -= HEADER_BAR_HEIGHT;
+    /// Put a tiled window right after the tiled window under `location`,
+    /// it splits that one like a new window would
+    pub fn move_tiled_element(
+        &mut self,
+        window: &WindowElement,
+        output: &Output,
+        location: Point<f64, Logical>,
+    ) {
+        let Some((target, _)) = self.window_under(location) else {
+            return;
+        };
+        if target == *window
+            || target.is_untiled_window()
+            || target.output().as_ref() != Some(output)
+        {
+            return;
         }
-
-        windowpre.toplevel().with_pending_state(|state| {
-            state.size = Some(size);
-        });
-        windowpre.toplevel().send_configure();
-        windowpre.set_output_size(current_screen.size);
-        windowpre.set_element_size(size);
-
-        Some(())
+        {
+            let mut tiles = output_tiling(output);
+            if !tiles.contains(window) || !tiles.contains(&target) {
+                return;
+            }
+            tiles.remove(window);
+            tiles.insert(window.clone(), Some(&target), self.splitstate);
+        }
+        self.relayout_output(output);
     }
 
     #[allow(unused)]
@@ -447,303 +507,41 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
         self.space
             .elements()
             .filter(|e| e.bbox().to_f64().contains(point))
-            .find(|w| w.toplevel().wl_surface() != surface)
+            .find(|w| !w.has_surface(surface))
     }
 
-    fn find_current_focused_element(&self, surface: &WlSurface) -> Option<&WindowElement> {
+    fn find_current_focused_element(
+        &self,
+        window: &WindowElement,
+        output: &Output,
+    ) -> Option<&WindowElement> {
         self.space.elements().find(|w| {
-            w.toplevel()
-                .current_state()
-                .states
-                .contains(xdg_toplevel::State::Activated)
-                && w.toplevel().wl_surface() != surface
+            w.is_activated()
+                && *w != window
                 && !w.is_untiled_window()
+                && w.output().as_ref() == Some(output)
         })
     }
 
-    fn current_active_window_rectangle(&self, surface: &WlSurface) -> Option<WindowElement> {
-        match self.find_current_focused_element(surface) {
+    fn current_active_window_rectangle(
+        &self,
+        window: &WindowElement,
+        output: &Output,
+    ) -> Option<WindowElement> {
+        match self.find_current_focused_element(window, output) {
             None => self
                 .space
                 .elements()
-                .filter(|w| !w.is_untiled_window() && w.toplevel().wl_surface() != surface)
+                .filter(|w| {
+                    !w.is_untiled_window() && *w != window && w.output().as_ref() == Some(output)
+                })
                 .last()
                 .cloned(),
             value => value.cloned(),
         }
     }
 
-    // TODO:?
-    fn current_screen_rectangle(&self) -> Option<Rectangle<i32, Logical>> {
-        let output = self
-            .space
-            .output_under(self.pointer.current_location())
-            .next()?;
-        self.space.output_geometry(output)
-    }
-
-    // TODO: very base
     fn handle_dead_window(&mut self, window: &WindowElement) {
-        let Some(current_screen) = self.current_screen_rectangle() else {
-            return;
-        };
-        let screen_size = current_screen.size;
-        let Some(pos) = self.space.element_location(window) else {
-            return;
-        };
-        let (x, y) = pos.into();
-        let (w, h) = window.get_pedding_size().into();
-        let (rb_x, rb_y) = (x + w, y + h);
-        if let Some(mut elements) = self.find_up_element((x, y), (rb_x, rb_y)) {
-            for element in elements.iter_mut() {
-                let Some(ori_pos) = self.space.element_location(element) else {
-                    continue;
-                };
-                let (ow, oh) = element.get_pedding_size().into();
-                let size: Size<i32, Logical> = (ow, oh + h).into();
-                let mut newsize = size;
-                if element.window_state().is_ssd {
-                    newsize.h -= HEADER_BAR_HEIGHT;
-                }
-                element.set_output_size(screen_size);
-                element.set_element_size(newsize);
-                element.set_pedding_size(Some(size));
-                element.set_origin_pos(ori_pos);
-                element.toplevel().with_pending_state(|state| {
-                    state.size = Some(newsize);
-                });
-                element.toplevel().send_configure();
-            }
-            return;
-        }
-        if let Some(mut elements) = self.find_down_element((x, y), (rb_x, rb_y)) {
-            for element in elements.iter_mut() {
-                let Some(ori_pos) = self.space.element_location(element) else {
-                    continue;
-                };
-                let (o_x, _) = ori_pos.into();
-                let (ow, oh) = element.get_pedding_size().into();
-                let size: Size<i32, Logical> = (ow, oh + h).into();
-                let mut newsize = size;
-                if element.window_state().is_ssd {
-                    newsize.h -= HEADER_BAR_HEIGHT;
-                }
-                element.set_output_size(screen_size);
-                element.set_element_size(newsize);
-                element.set_pedding_size(Some(size));
-                element.set_origin_pos((o_x, y).into());
-                element.toplevel().with_pending_state(|state| {
-                    state.size = Some(newsize);
-                });
-                element.toplevel().send_configure();
-                self.space.map_element(element.clone(), (o_x, y), true);
-            }
-            self.raise_untiled_elements();
-            return;
-        }
-        if let Some(mut elements) = self.find_left_element((x, y), (rb_x, rb_y)) {
-            for element in elements.iter_mut() {
-                let Some(ori_pos) = self.space.element_location(element) else {
-                    continue;
-                };
-                let (ow, oh) = element.get_pedding_size().into();
-                let size: Size<i32, Logical> = (ow + w, oh).into();
-                let mut newsize = size;
-                if element.window_state().is_ssd {
-                    newsize.h -= HEADER_BAR_HEIGHT;
-                }
-                element.set_output_size(screen_size);
-                element.set_element_size(newsize);
-                element.set_pedding_size(Some(size));
-                element.set_origin_pos(ori_pos);
-                element.toplevel().with_pending_state(|state| {
-                    state.size = Some(newsize);
-                });
-                element.toplevel().send_configure();
-            }
-            return;
-        }
-        if let Some(mut elements) = self.find_right_element((x, y), (rb_x, rb_y)) {
-            for element in elements.iter_mut() {
-                let Some(ori_pos) = self.space.element_location(element) else {
-                    continue;
-                };
-                let (_, o_y) = ori_pos.into();
-                let (ow, oh) = element.get_pedding_size().into();
-                let size: Size<i32, Logical> = (ow + w, oh).into();
-                let mut newsize = size;
-                if element.window_state().is_ssd {
-                    newsize.h -= HEADER_BAR_HEIGHT;
-                }
-                element.set_output_size(screen_size);
-                element.set_element_size(newsize);
-                element.set_pedding_size(Some(size));
-                element.set_origin_pos((x, o_y).into());
-                element.toplevel().with_pending_state(|state| {
-                    state.size = Some(newsize);
-                });
-                element.toplevel().send_configure();
-                self.space.map_element(element.clone(), (x, o_y), true);
-            }
-            self.raise_untiled_elements();
-        }
-    }
-}
-
-impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
-    fn find_up_element(
-        &self,
-        (start_x, start_y): (i32, i32),
-        (end_x, _end_y): (i32, i32),
-    ) -> Option<Vec<WindowElement>> {
-        let elements: Vec<WindowElement> = self
-            .space
-            .elements()
-            .filter(|w| {
-                if w.is_untiled_window() {
-                    return false;
-                }
-                let Some(Point { x, y, .. }) = self.space.element_location(w) else {
-                    return false;
-                };
-                let (w, h) = w.get_pedding_size().into();
-                x >= start_x - 5 && x + w <= end_x + 5 && (y + h - start_y).abs() < 5
-            })
-            .cloned()
-            .collect();
-        let has_start_pos = elements.iter().any(|w| {
-            let Some(Point { x, .. }) = self.space.element_location(w) else {
-                return false;
-            };
-            (x - start_x).abs() < 5
-        });
-        let has_end_pos = elements.iter().any(|w| {
-            let Some(Point { x, .. }) = self.space.element_location(w) else {
-                return false;
-            };
-            let (w, _) = w.get_pedding_size().into();
-            (x + w - end_x).abs() < 5
-        });
-        if !(has_start_pos && has_end_pos) {
-            return None;
-        }
-        Some(elements)
-    }
-
-    fn find_down_element(
-        &self,
-        (start_x, _start_y): (i32, i32),
-        (end_x, end_y): (i32, i32),
-    ) -> Option<Vec<WindowElement>> {
-        let elements: Vec<WindowElement> = self
-            .space
-            .elements()
-            .filter(|w| {
-                if w.is_untiled_window() {
-                    return false;
-                }
-                let Some(Point { x, y, .. }) = self.space.element_location(w) else {
-                    return false;
-                };
-                let (w, _) = w.get_pedding_size().into();
-                x >= start_x - 5 && x + w <= end_x + 5 && (y - end_y).abs() < 5
-            })
-            .cloned()
-            .collect();
-        let has_start_pos = elements.iter().any(|w| {
-            let Some(Point { x, .. }) = self.space.element_location(w) else {
-                return false;
-            };
-            (x - start_x).abs() < 5
-        });
-        let has_end_pos = elements.iter().any(|w| {
-            let Some(Point { x, .. }) = self.space.element_location(w) else {
-                return false;
-            };
-            let (w, _) = w.get_pedding_size().into();
-            (x + w - end_x).abs() < 5
-        });
-        if !(has_start_pos && has_end_pos) {
-            return None;
-        }
-        Some(elements)
-    }
-
-    fn find_left_element(
-        &self,
-        (start_x, start_y): (i32, i32),
-        (_end_x, end_y): (i32, i32),
-    ) -> Option<Vec<WindowElement>> {
-        let elements: Vec<WindowElement> = self
-            .space
-            .elements()
-            .filter(|w| {
-                if w.is_untiled_window() {
-                    return false;
-                }
-                let Some(Point { x, y, .. }) = self.space.element_location(w) else {
-                    return false;
-                };
-                let (w, h) = w.get_pedding_size().into();
-                y >= start_y - 5 && y + h <= end_y + 5 && (x + w - start_x).abs() < 5
-            })
-            .cloned()
-            .collect();
-        let has_start_pos = elements.iter().any(|w| {
-            let Some(Point { y, .. }) = self.space.element_location(w) else {
-                return false;
-            };
-            (y - start_y).abs() < 5
-        });
-        let has_end_pos = elements.iter().any(|w| {
-            let Some(Point { y, .. }) = self.space.element_location(w) else {
-                return false;
-            };
-            let (_, h) = w.get_pedding_size().into();
-            (y + h - end_y).abs() < 5
-        });
-        if !(has_start_pos && has_end_pos) {
-            return None;
-        }
-        Some(elements)
-    }
-
-    fn find_right_element(
-        &self,
-        (_start_x, start_y): (i32, i32),
-        (end_x, end_y): (i32, i32),
-    ) -> Option<Vec<WindowElement>> {
-        let elements: Vec<WindowElement> = self
-            .space
-            .elements()
-            .filter(|w| {
-                if w.is_untiled_window() {
-                    return false;
-                }
-                let Some(Point { x, y, .. }) = self.space.element_location(w) else {
-                    return false;
-                };
-                let (_, h) = w.get_pedding_size().into();
-                y >= start_y - 5 && y + h <= end_y + 5 && (x - end_x).abs() < 5
-            })
-            .cloned()
-            .collect();
-        let has_start_pos = elements.iter().any(|w| {
-            let Some(Point { y, .. }) = self.space.element_location(w) else {
-                return false;
-            };
-            (y - start_y).abs() < 5
-        });
-        let has_end_pos = elements.iter().any(|w| {
-            let Some(Point { y, .. }) = self.space.element_location(w) else {
-                return false;
-            };
-            let (_, h) = w.get_pedding_size().into();
-            (y + h - end_y).abs() < 5
-        });
-        if !(has_start_pos && has_end_pos) {
-            return None;
-        }
-        Some(elements)
+        self.remove_from_workspace(window);
     }
 }
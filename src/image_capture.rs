@@ -0,0 +1,704 @@
+//! `ext_image_copy_capture_v1` with the sources of
+//! `ext_image_capture_source_v1`, which capture whole outputs or single
+//! windows. Windows are picked by their handle of
+//! `ext_foreign_toplevel_list_v1`.
+//!
+//! Like the frames of screencopy, the frames wait in the user data of the
+//! output showing their source, for windows on a hidden workspace the output
+//! of that workspace, and are rendered offscreen the next time the output is
+//! drawn. Windows are rendered on their own, upright and at the scale of
+//! their output. Every frame is damaged all over.
+//!
+//! The pointer is painted onto the frames of sessions with the
+//! `paint_cursors` option. Cursor sessions are accepted, but their capture
+//! sessions are stopped right away.
+
+mod protocol;
+
+use std::{
+    cell::RefMut,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use smithay::{
+    backend::renderer::{
+        element::{
+            utils::{Relocate, RelocateRenderElement},
+            AsRenderElements, RenderElement,
+        },
+        gles::GlesTexture,
+        Bind, ExportMem, ImportAll, ImportMem, Offscreen, Renderer,
+    },
+    desktop::{space::SpaceElement, Space},
+    output::Output,
+    reexports::wayland_server::{
+        backend::ClientId,
+        protocol::{wl_buffer::WlBuffer, wl_shm},
+        Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+    },
+    utils::{Clock, Logical, Monotonic, Physical, Point, Rectangle, Scale, Size, Transform},
+    wayland::shm::with_buffer_contents,
+};
+
+use crate::{
+    render::{output_elements, CustomRenderElements},
+    screencopy::{copy_to_shm, render_to_memory},
+    shell::{WindowElement, WindowRenderElement},
+    state::{output_state, Backend, SmallCageState},
+};
+
+use protocol::{
+    image_capture_source::{
+        ext_foreign_toplevel_image_capture_source_manager_v1::{
+            self, ExtForeignToplevelImageCaptureSourceManagerV1,
+        },
+        ext_image_capture_source_v1::{self, ExtImageCaptureSourceV1},
+        ext_output_image_capture_source_manager_v1::{self, ExtOutputImageCaptureSourceManagerV1},
+    },
+    image_copy_capture::{
+        ext_image_copy_capture_cursor_session_v1::{self, ExtImageCopyCaptureCursorSessionV1},
+        ext_image_copy_capture_frame_v1::{self, ExtImageCopyCaptureFrameV1, FailureReason},
+        ext_image_copy_capture_manager_v1::{self, ExtImageCopyCaptureManagerV1, Options},
+        ext_image_copy_capture_session_v1::{self, ExtImageCopyCaptureSessionV1},
+    },
+};
+
+const VERSION: u32 = 1;
+
+smithay::backend::renderer::element::render_elements! {
+    WindowCaptureElements<R> where R: ImportAll + ImportMem;
+    Window=WindowRenderElement<R>,
+    Cursor=RelocateRenderElement<CustomRenderElements<R>>,
+}
+
+/// What a source captures
+#[derive(Debug, Clone)]
+pub enum CaptureSource {
+    Output(Output),
+    /// The IPC id of a window
+    Toplevel(u64),
+}
+
+pub struct SessionData {
+    /// `None` for sources which were gone when they were created and for
+    /// the sessions of cursors, they are stopped right away
+    source: Option<CaptureSource>,
+    paint_cursors: bool,
+    /// Only one frame of a session may exist at a time
+    has_frame: AtomicBool,
+}
+
+#[derive(Default)]
+pub struct CursorSessionData {
+    has_session: AtomicBool,
+}
+
+pub struct FrameData {
+    session: ExtImageCopyCaptureSessionV1,
+    buffer: Mutex<Option<WlBuffer>>,
+    captured: AtomicBool,
+}
+
+/// What a frame renders, the window is looked up when it is captured
+enum Target {
+    Output,
+    Window(WindowElement),
+}
+
+/// A frame with the buffer to copy it into, waiting for its output to be
+/// drawn
+struct PendingCapture {
+    frame: ExtImageCopyCaptureFrameV1,
+    buffer: WlBuffer,
+    target: Target,
+    paint_cursors: bool,
+}
+
+fn pending_captures(output: &Output) -> RefMut<'_, Vec<PendingCapture>> {
+    output_state(output)
+}
+
+/// Advertise the capture sources and the manager of the sessions
+pub fn init_image_capture<BackendData: Backend + 'static>(dh: &DisplayHandle) {
+    dh.create_global::<SmallCageState<BackendData>, ExtOutputImageCaptureSourceManagerV1, _>(
+        VERSION,
+        (),
+    );
+    dh.create_global::<SmallCageState<BackendData>, ExtForeignToplevelImageCaptureSourceManagerV1, _>(
+        VERSION,
+        (),
+    );
+    dh.create_global::<SmallCageState<BackendData>, ExtImageCopyCaptureManagerV1, _>(VERSION, ());
+}
+
+/// The size of the buffers of `target` shown on `output`
+fn buffer_size(
+    space: &Space<WindowElement>,
+    output: &Output,
+    target: &Target,
+) -> Option<Size<i32, Physical>> {
+    let scale = output.current_scale().fractional_scale();
+    match target {
+        Target::Output => {
+            let geometry = space.output_geometry(output)?;
+            let size = geometry.size.to_physical_precise_round(scale);
+            Some(output.current_transform().transform_size(size))
+        }
+        Target::Window(window) => Some(window.geometry().size.to_physical_precise_round(scale)),
+    }
+}
+
+/// Whether `buffer` is a shm buffer frames of `size` can be copied into
+fn buffer_matches(buffer: &WlBuffer, size: Size<i32, Physical>) -> bool {
+    with_buffer_contents(buffer, |_, _, info| {
+        matches!(
+            info.format,
+            wl_shm::Format::Xrgb8888 | wl_shm::Format::Argb8888
+        ) && info.width == size.w
+            && info.height == size.h
+            && info.stride >= size.w * 4
+    })
+    .unwrap_or(false)
+}
+
+/// Tell a session which buffers to attach to its frames
+fn send_constraints(session: &ExtImageCopyCaptureSessionV1, size: Size<i32, Physical>) {
+    session.buffer_size(size.w as u32, size.h as u32);
+    session.shm_format(wl_shm::Format::Argb8888);
+    session.shm_format(wl_shm::Format::Xrgb8888);
+    session.done();
+}
+
+impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
+    /// Take the frames waiting for `output`, which has gone away. The frames
+    /// of the output fail, the frames of windows wait for the output the
+    /// windows are moved to.
+    pub fn move_image_captures(&mut self, output: &Output) {
+        let mut windows = Vec::new();
+        for capture in std::mem::take(&mut *pending_captures(output)) {
+            match capture.target {
+                Target::Output => capture.frame.failed(FailureReason::Unknown),
+                Target::Window(_) => windows.push(capture),
+            }
+        }
+        if windows.is_empty() {
+            return;
+        }
+        // the windows are on their new output once the removal is done
+        self.handle.insert_idle(move |data| {
+            for capture in windows {
+                let output = match &capture.target {
+                    Target::Window(window) => data.state.window_output(window),
+                    Target::Output => None,
+                };
+                match output {
+                    Some(output) => pending_captures(&output).push(capture),
+                    None => capture.frame.failed(FailureReason::Unknown),
+                }
+            }
+        });
+    }
+
+    /// The output `source` is rendered with and what is rendered, `None`
+    /// once the source is gone
+    fn capture_target(&self, source: &CaptureSource) -> Option<(Output, Target)> {
+        match source {
+            CaptureSource::Output(output) => self
+                .space
+                .outputs()
+                .any(|o| o == output)
+                .then(|| (output.clone(), Target::Output)),
+            CaptureSource::Toplevel(id) => {
+                let window = self.find_window_by(|w| w.ipc_id() == *id)?;
+                let output = self.window_output(&window)?;
+                Some((output, Target::Window(window)))
+            }
+        }
+    }
+
+    fn capture_frame(&mut self, frame: &ExtImageCopyCaptureFrameV1, data: &FrameData) {
+        use ext_image_copy_capture_frame_v1::Error;
+        if data.captured.swap(true, Ordering::SeqCst) {
+            frame.post_error(Error::AlreadyCaptured, "the frame was already captured");
+            return;
+        }
+        let Some(buffer) = data.buffer.lock().unwrap().clone() else {
+            frame.post_error(Error::NoBuffer, "no buffer was attached");
+            return;
+        };
+        let Some(session) = data.session.data::<SessionData>() else {
+            frame.failed(FailureReason::Unknown);
+            return;
+        };
+        let Some((output, target)) = session
+            .source
+            .as_ref()
+            .and_then(|source| self.capture_target(source))
+        else {
+            data.session.stopped();
+            frame.failed(FailureReason::Stopped);
+            return;
+        };
+        let Some(size) = buffer_size(&self.space, &output, &target) else {
+            frame.failed(FailureReason::Unknown);
+            return;
+        };
+        if !buffer_matches(&buffer, size) {
+            // the source was resized, the client has to attach a new buffer
+            send_constraints(&data.session, size);
+            frame.failed(FailureReason::BufferConstraints);
+            return;
+        }
+        pending_captures(&output).push(PendingCapture {
+            frame: frame.clone(),
+            buffer,
+            target,
+            paint_cursors: session.paint_cursors,
+        });
+    }
+}
+
+/// Render `elements` into `buffer`, which has to be `size` still
+fn render_into<R, E>(
+    renderer: &mut R,
+    buffer: &WlBuffer,
+    size: Size<i32, Physical>,
+    scale: f64,
+    transform: Transform,
+    elements: &[E],
+    clear_color: [f32; 4],
+) -> Result<(), FailureReason>
+where
+    R: Renderer + Offscreen<GlesTexture> + Bind<GlesTexture> + ExportMem,
+    E: RenderElement<R>,
+{
+    if !buffer_matches(buffer, size) {
+        return Err(FailureReason::BufferConstraints);
+    }
+    let region = Rectangle::from_loc_and_size((0, 0), (size.w, size.h));
+    let pixels = render_to_memory(
+        renderer,
+        size,
+        Scale::from(scale),
+        transform,
+        elements,
+        clear_color,
+        region,
+    )
+    .map_err(|err| {
+        tracing::warn!("Failed to render the captured frame: {}", err);
+        FailureReason::Unknown
+    })?;
+    copy_to_shm(buffer, &pixels, size.w).map_err(|err| {
+        tracing::warn!("Failed to copy into the capture buffer: {}", err);
+        FailureReason::Unknown
+    })
+}
+
+/// Fulfill the frames waiting for `output`. `cursor_elements` draws the
+/// pointer where it is on the output, for the frames which include it.
+pub fn render_image_captures<R>(
+    renderer: &mut R,
+    output: &Output,
+    space: &Space<WindowElement>,
+    show_window_preview: bool,
+    mut cursor_elements: impl FnMut(&mut R) -> Vec<CustomRenderElements<R>>,
+) where
+    R: Renderer + ImportAll + ImportMem + Offscreen<GlesTexture> + Bind<GlesTexture> + ExportMem,
+    R::TextureId: Clone + 'static,
+{
+    let pending = std::mem::take(&mut *pending_captures(output));
+    if pending.is_empty() {
+        return;
+    }
+    let Some(output_geometry) = space.output_geometry(output) else {
+        for capture in pending {
+            capture.frame.failed(FailureReason::Unknown);
+        }
+        return;
+    };
+    let scale = output.current_scale().fractional_scale();
+
+    for capture in pending {
+        if !capture.frame.is_alive() {
+            continue;
+        }
+        let Some(size) = buffer_size(space, output, &capture.target) else {
+            capture.frame.failed(FailureReason::Unknown);
+            continue;
+        };
+        let cursor = if capture.paint_cursors {
+            cursor_elements(renderer)
+        } else {
+            Vec::new()
+        };
+
+        let (transform, result) = match &capture.target {
+            Target::Output => {
+                let transform = output.current_transform();
+                let (elements, clear_color) =
+                    output_elements(output, space, cursor, renderer, show_window_preview, false);
+                let result = render_into(
+                    renderer,
+                    &capture.buffer,
+                    size,
+                    scale,
+                    transform,
+                    &elements,
+                    clear_color,
+                );
+                (transform, result)
+            }
+            Target::Window(window) => {
+                // the window geometry starts at the top left of the buffer
+                let geometry = window.geometry();
+                let location = Point::<i32, Logical>::from((-geometry.loc.x, -geometry.loc.y))
+                    .to_physical_precise_round(scale);
+                let mut elements: Vec<WindowCaptureElements<R>> = Vec::new();
+                // the pointer is only on windows which are shown
+                if let Some(window_location) = space.element_location(window) {
+                    let offset =
+                        (output_geometry.loc - window_location).to_physical_precise_round(scale);
+                    elements.extend(cursor.into_iter().map(|element| {
+                        WindowCaptureElements::Cursor(RelocateRenderElement::from_element(
+                            element,
+                            offset,
+                            Relocate::Relative,
+                        ))
+                    }));
+                }
+                elements.extend(AsRenderElements::<R>::render_elements::<
+                    WindowCaptureElements<R>,
+                >(
+                    window, renderer, location, Scale::from(scale), 1.0
+                ));
+                let result = render_into(
+                    renderer,
+                    &capture.buffer,
+                    size,
+                    scale,
+                    Transform::Normal,
+                    &elements,
+                    [0.0, 0.0, 0.0, 0.0],
+                );
+                (Transform::Normal, result)
+            }
+        };
+
+        let frame = &capture.frame;
+        if let Err(reason) = result {
+            // the source was resized, the client has to attach a new buffer
+            if reason == FailureReason::BufferConstraints {
+                if let Some(data) = frame.data::<FrameData>() {
+                    send_constraints(&data.session, size);
+                }
+            }
+            frame.failed(reason);
+            continue;
+        }
+        frame.transform(transform.into());
+        frame.damage(0, 0, size.w, size.h);
+        let time = Duration::from(Clock::<Monotonic>::new().now());
+        let secs = time.as_secs();
+        frame.presentation_time((secs >> 32) as u32, secs as u32, time.subsec_nanos());
+        frame.ready();
+    }
+}
+
+impl<BackendData: Backend + 'static> GlobalDispatch<ExtOutputImageCaptureSourceManagerV1, ()>
+    for SmallCageState<BackendData>
+{
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ExtOutputImageCaptureSourceManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ExtOutputImageCaptureSourceManagerV1, ()>
+    for SmallCageState<BackendData>
+{
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _manager: &ExtOutputImageCaptureSourceManagerV1,
+        request: ext_output_image_capture_source_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        use ext_output_image_capture_source_manager_v1::Request;
+        match request {
+            Request::CreateSource { source, output } => {
+                let output = Output::from_resource(&output);
+                data_init.init(source, output.map(CaptureSource::Output));
+            }
+            Request::Destroy => {}
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static>
+    GlobalDispatch<ExtForeignToplevelImageCaptureSourceManagerV1, ()>
+    for SmallCageState<BackendData>
+{
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ExtForeignToplevelImageCaptureSourceManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ExtForeignToplevelImageCaptureSourceManagerV1, ()>
+    for SmallCageState<BackendData>
+{
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _manager: &ExtForeignToplevelImageCaptureSourceManagerV1,
+        request: ext_foreign_toplevel_image_capture_source_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        use ext_foreign_toplevel_image_capture_source_manager_v1::Request;
+        match request {
+            Request::CreateSource {
+                source,
+                toplevel_handle,
+            } => {
+                let window = state.foreign_toplevel_window(&toplevel_handle);
+                data_init.init(
+                    source,
+                    window.map(|window| CaptureSource::Toplevel(window.ipc_id())),
+                );
+            }
+            Request::Destroy => {}
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ExtImageCaptureSourceV1, Option<CaptureSource>>
+    for SmallCageState<BackendData>
+{
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _source: &ExtImageCaptureSourceV1,
+        request: ext_image_capture_source_v1::Request,
+        _data: &Option<CaptureSource>,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        use ext_image_capture_source_v1::Request;
+        match request {
+            Request::Destroy => {}
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> GlobalDispatch<ExtImageCopyCaptureManagerV1, ()>
+    for SmallCageState<BackendData>
+{
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ExtImageCopyCaptureManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ExtImageCopyCaptureManagerV1, ()>
+    for SmallCageState<BackendData>
+{
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        manager: &ExtImageCopyCaptureManagerV1,
+        request: ext_image_copy_capture_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        use ext_image_copy_capture_manager_v1::Request;
+        match request {
+            Request::CreateSession {
+                session,
+                source,
+                options,
+            } => {
+                let source = source.data::<Option<CaptureSource>>().cloned().flatten();
+                let paint_cursors = match options {
+                    WEnum::Value(options) => options.contains(Options::PaintCursors),
+                    WEnum::Unknown(_) => false,
+                };
+                let session = data_init.init(
+                    session,
+                    SessionData {
+                        source: source.clone(),
+                        paint_cursors,
+                        has_frame: AtomicBool::new(false),
+                    },
+                );
+                if let WEnum::Unknown(options) = options {
+                    manager.post_error(
+                        ext_image_copy_capture_manager_v1::Error::InvalidOption,
+                        format!("unknown options {:#x}", options),
+                    );
+                    return;
+                }
+                let size = source
+                    .and_then(|source| state.capture_target(&source))
+                    .and_then(|(output, target)| buffer_size(&state.space, &output, &target));
+                match size {
+                    Some(size) => send_constraints(&session, size),
+                    None => session.stopped(),
+                }
+            }
+            Request::CreatePointerCursorSession { session, .. } => {
+                data_init.init(session, CursorSessionData::default());
+            }
+            Request::Destroy => {}
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ExtImageCopyCaptureCursorSessionV1, CursorSessionData>
+    for SmallCageState<BackendData>
+{
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        cursor_session: &ExtImageCopyCaptureCursorSessionV1,
+        request: ext_image_copy_capture_cursor_session_v1::Request,
+        data: &CursorSessionData,
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        use ext_image_copy_capture_cursor_session_v1::Request;
+        match request {
+            Request::GetCaptureSession { session } => {
+                let session = data_init.init(
+                    session,
+                    SessionData {
+                        source: None,
+                        paint_cursors: false,
+                        has_frame: AtomicBool::new(false),
+                    },
+                );
+                if data.has_session.swap(true, Ordering::SeqCst) {
+                    cursor_session.post_error(
+                        ext_image_copy_capture_cursor_session_v1::Error::DuplicateSession,
+                        "the capture session was already created",
+                    );
+                    return;
+                }
+                session.stopped();
+            }
+            Request::Destroy => {}
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ExtImageCopyCaptureSessionV1, SessionData>
+    for SmallCageState<BackendData>
+{
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        session: &ExtImageCopyCaptureSessionV1,
+        request: ext_image_copy_capture_session_v1::Request,
+        data: &SessionData,
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        use ext_image_copy_capture_session_v1::Request;
+        match request {
+            Request::CreateFrame { frame } => {
+                data_init.init(
+                    frame,
+                    FrameData {
+                        session: session.clone(),
+                        buffer: Mutex::new(None),
+                        captured: AtomicBool::new(false),
+                    },
+                );
+                if data.has_frame.swap(true, Ordering::SeqCst) {
+                    session.post_error(
+                        ext_image_copy_capture_session_v1::Error::DuplicateFrame,
+                        "the previous frame was not destroyed",
+                    );
+                }
+            }
+            Request::Destroy => {}
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ExtImageCopyCaptureFrameV1, FrameData>
+    for SmallCageState<BackendData>
+{
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        frame: &ExtImageCopyCaptureFrameV1,
+        request: ext_image_copy_capture_frame_v1::Request,
+        data: &FrameData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        use ext_image_copy_capture_frame_v1::{Error, Request};
+        let captured = data.captured.load(Ordering::SeqCst);
+        match request {
+            Request::AttachBuffer { .. } | Request::DamageBuffer { .. } if captured => {
+                frame.post_error(Error::AlreadyCaptured, "the frame was already captured");
+            }
+            Request::AttachBuffer { buffer } => {
+                *data.buffer.lock().unwrap() = Some(buffer);
+            }
+            // frames are rendered all over, the damage only has to be valid
+            Request::DamageBuffer {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                if x < 0 || y < 0 || width <= 0 || height <= 0 {
+                    frame.post_error(Error::InvalidBufferDamage, "the damage is invalid");
+                }
+            }
+            Request::Capture => state.capture_frame(frame, data),
+            Request::Destroy => {}
+        }
+    }
+
+    fn destroyed(
+        _state: &mut Self,
+        _client: ClientId,
+        _frame: &ExtImageCopyCaptureFrameV1,
+        data: &FrameData,
+    ) {
+        // the session may create its next frame
+        if let Some(session) = data.session.data::<SessionData>() {
+            session.has_frame.store(false, Ordering::SeqCst);
+        }
+    }
+}
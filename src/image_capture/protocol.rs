@@ -0,0 +1,39 @@
+//! The interfaces of `ext_image_capture_source_v1` and
+//! `ext_image_copy_capture_v1`, generated from the files in `protocols/`
+//! since the wayland-protocols smithay depends on does not ship them yet.
+
+#![allow(dead_code, non_camel_case_types, unused_unsafe, unused_variables)]
+#![allow(non_upper_case_globals, non_snake_case, unused_imports)]
+#![allow(missing_docs, static_mut_refs, clippy::all)]
+
+pub mod image_capture_source {
+    use smithay::reexports::wayland_protocols::ext::foreign_toplevel_list::v1::server::*;
+    use smithay::reexports::wayland_server;
+    use smithay::reexports::wayland_server::protocol::*;
+
+    pub mod __interfaces {
+        use smithay::reexports::wayland_protocols::ext::foreign_toplevel_list::v1::server::__interfaces::*;
+        use smithay::reexports::wayland_server::backend as wayland_backend;
+        use smithay::reexports::wayland_server::protocol::__interfaces::*;
+        wayland_scanner::generate_interfaces!("protocols/ext-image-capture-source-v1.xml");
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_server_code!("protocols/ext-image-capture-source-v1.xml");
+}
+
+pub mod image_copy_capture {
+    use super::image_capture_source::*;
+    use smithay::reexports::wayland_server;
+    use smithay::reexports::wayland_server::protocol::*;
+
+    pub mod __interfaces {
+        use super::super::image_capture_source::__interfaces::*;
+        use smithay::reexports::wayland_server::backend as wayland_backend;
+        use smithay::reexports::wayland_server::protocol::__interfaces::*;
+        wayland_scanner::generate_interfaces!("protocols/ext-image-copy-capture-v1.xml");
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_server_code!("protocols/ext-image-copy-capture-v1.xml");
+}
@@ -1,23 +1,33 @@
+use std::sync::atomic::Ordering;
+
 use smithay::{
     backend::input::{
-        AbsolutePositionEvent, Axis, AxisSource, ButtonState, Event, InputBackend, InputEvent,
-        KeyState, KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent,
+        AbsolutePositionEvent, Axis, AxisSource, ButtonState, Device, Event, InputBackend,
+        InputEvent, KeyState, KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent,
+        PointerMotionEvent, TouchEvent,
     },
     input::{
         keyboard::{keysyms as xkb, FilterResult, Keysym, ModifiersState},
-        pointer::{AxisFrame, ButtonEvent, MotionEvent},
+        pointer::{AxisFrame, ButtonEvent, MotionEvent, RelativeMotionEvent},
+        touch::{DownEvent, MotionEvent as TouchMotionEvent, UpEvent},
     },
-    utils::SERIAL_COUNTER,
+    output::Output,
+    utils::{Logical, Point, SERIAL_COUNTER},
 };
 
 use crate::{
-    shell::WindowElement,
-    state::{Backend, SmallCageState, SplitState},
+    config::{config, DeviceType},
+    drawing::damage_tint,
+    focus::FocusTarget,
+    preview::PreviewDirection,
+    screenshot::ScreenshotMode,
+    state::{output_rotation, Backend, SmallCageState, SplitState, SCALE_STEP},
+    workspace::output_tiling,
 };
 
 #[allow(dead_code)]
-#[derive(Debug)]
-enum KeyAction {
+#[derive(Debug, Clone)]
+pub enum KeyAction {
     /// Quit the compositor
     Quit,
     /// Trigger a vt-switch
@@ -28,11 +38,27 @@ enum KeyAction {
     ChangeSplitSate(SplitState),
     /// Switch the current screen
     Screen(usize),
+    /// Move the focused window to another screen
+    MoveToScreen(usize),
     ScaleUp,
     ScaleDown,
     TogglePreview,
     RotateOutput,
     ToggleTint,
+    /// Switch the current output to its next layout
+    CycleLayout,
+    /// Load the config file again
+    ReloadConfig,
+    /// Switch to the next of the configured keyboard layouts
+    NextKeyboardLayout,
+    /// Move the selection of the overview
+    PreviewMove(PreviewDirection),
+    /// Leave the overview with the selected window
+    PreviewSelect,
+    /// Save a PNG of the output, the focused window or a selected region
+    Screenshot(ScreenshotMode),
+    /// Stop selecting the region of a screenshot
+    CancelScreenshot,
     /// Do nothing more
     None,
 }
@@ -41,46 +67,70 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
     pub fn process_input_event<I: InputBackend>(&mut self, event: InputEvent<I>) {
         match event {
             InputEvent::Keyboard { event, .. } => {
-                match self.keyboard_key_to_action::<I>(event) {
-                    KeyAction::Run(cmd) => {
-                        if let Err(e) = std::process::Command::new(&cmd)
-                            .env("WAYLAND_DISPLAY", self.socket_name.clone())
-                            .spawn()
-                        {
-                            tracing::error!(cmd, err = %e, "Failed to start program");
-                        }
-                    }
-                    KeyAction::ChangeElementState => {
-                        let Some(window) = self.find_current_focus_window().cloned() else {
-                            return;
-                        };
-                        if window.is_fixed_window() {
-                            return;
-                        }
-                        self.handle.insert_idle(move |data| {
-                            data.state.handle_element_state_change(&window);
-                        });
-                        //self.wmstatus.status_change();
-                    }
-                    KeyAction::ChangeSplitSate(state) => {
-                        self.splitstate = state;
-                    }
-                    _ => {}
-                }
+                let action = self.keyboard_key_to_action::<I>(event);
+                self.process_key_action(action);
             }
-            InputEvent::PointerMotion { .. } => {}
-            InputEvent::PointerMotionAbsolute { event, .. } => {
-                let output = self.space.outputs().next().unwrap();
+            InputEvent::PointerMotion { event, .. } => {
+                let pointer = self.seat.get_pointer().unwrap();
+                let pos = self.clamp_to_outputs(pointer.current_location() + event.delta());
+                let pos = self.constrain_pointer(pos);
+
+                let serial = SERIAL_COUNTER.next_serial();
+
+                // the windows do not get the pointer while the overview is
+                // shown or a screenshot region is selected
+                let under = if self.show_window_preview || self.region_selection.is_some() {
+                    None
+                } else {
+                    self.surface_under(pos)
+                };
 
-                let output_geo = self.space.output_geometry(output).unwrap();
+                pointer.motion(
+                    self,
+                    under.clone(),
+                    &MotionEvent {
+                        location: pos,
+                        serial,
+                        time: event.time_msec(),
+                    },
+                );
+                // the unclamped motion, for clients which use the pointer
+                // to look around instead of to point
+                pointer.relative_motion(
+                    self,
+                    under,
+                    &RelativeMotionEvent {
+                        delta: event.delta(),
+                        delta_unaccel: event.delta_unaccel(),
+                        utime: event.time(),
+                    },
+                );
+                pointer.frame(self);
+                self.activate_pointer_constraint();
+                self.update_region_selection(pos);
+            }
+            InputEvent::PointerMotionAbsolute { event, .. } => {
+                // absolute devices are mapped onto the output the pointer is on
+                let Some(output) = self.current_output() else {
+                    return;
+                };
 
-                let pos = event.position_transformed(output_geo.size) + output_geo.loc.to_f64();
+                let Some(pos) = self.absolute_position::<I, _>(&output, &event) else {
+                    return;
+                };
+                let pos = self.constrain_pointer(pos);
 
                 let serial = SERIAL_COUNTER.next_serial();
 
                 let pointer = self.seat.get_pointer().unwrap();
 
-                let under = self.surface_under_pointer(&pointer);
+                // the windows do not get the pointer while the overview is
+                // shown or a screenshot region is selected
+                let under = if self.show_window_preview || self.region_selection.is_some() {
+                    None
+                } else {
+                    self.surface_under(pos)
+                };
 
                 pointer.motion(
                     self,
@@ -91,10 +141,21 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
                         time: event.time_msec(),
                     },
                 );
+                pointer.frame(self);
+                self.activate_pointer_constraint();
+                self.update_region_selection(pos);
+            }
+            InputEvent::PointerButton { event, .. } if self.region_selection.is_some() => {
+                self.region_selection_button(event.state());
+            }
+            InputEvent::PointerButton { event, .. } if self.show_window_preview => {
+                if event.state() == ButtonState::Pressed {
+                    self.click_preview();
+                }
             }
             InputEvent::PointerButton { event, .. } => {
                 let pointer = self.seat.get_pointer().unwrap();
-                let keyboard = self.seat.get_keyboard().unwrap();
+                let keyboard = self.seat.get_keyboard();
 
                 let serial = SERIAL_COUNTER.next_serial();
 
@@ -102,16 +163,28 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
 
                 let button_state = event.state();
 
-                if ButtonState::Pressed == button_state && !pointer.is_grabbed() {
-                    if let Some((window, _loc)) = self
+                // lock screens and launchers keep the keyboard while they are
+                // shown, clicks do not move it
+                if ButtonState::Pressed == button_state
+                    && !pointer.is_grabbed()
+                    && !self.keep_exclusive_layer_focus()
+                {
+                    if let Some((FocusTarget::LayerSurface(layer), _)) =
+                        self.surface_under(pointer.current_location())
+                    {
+                        // panels do not change which window is active
+                        self.focus_layer_surface(layer);
+                    } else if let Some((window, _loc)) = self
                         .space
                         .element_under(pointer.current_location())
                         .map(|(w, l)| (w.clone(), l))
                     {
                         self.space.raise_element(&window, true);
-                        keyboard.set_focus(self, Some(window.clone()), serial);
+                        if let Some(keyboard) = keyboard {
+                            keyboard.set_focus(self, Some(window.clone().into()), serial);
+                        }
                         self.space.elements().for_each(|window| {
-                            window.toplevel().send_pending_configure();
+                            window.send_pending_configure();
                         });
                         if !window.is_untiled_window() {
                             self.raise_untiled_elements();
@@ -119,9 +192,11 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
                     } else {
                         self.space.elements().for_each(|window| {
                             window.set_activated(false);
-                            window.toplevel().send_pending_configure();
+                            window.send_pending_configure();
                         });
-                        keyboard.set_focus(self, Option::<WindowElement>::None, serial);
+                        if let Some(keyboard) = keyboard {
+                            keyboard.set_focus(self, Option::<FocusTarget>::None, serial);
+                        }
                     }
                 };
 
@@ -135,6 +210,7 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
                     },
                 );
             }
+            InputEvent::PointerAxis { .. } if self.show_window_preview => {}
             InputEvent::PointerAxis { event, .. } => {
                 let source = event.source();
 
@@ -174,6 +250,89 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
                 pointer.axis(self, frame);
                 pointer.frame(self);
             }
+            InputEvent::TouchDown { event, .. } => {
+                let Some(touch) = self.seat.get_touch() else {
+                    return;
+                };
+                let Some(output) = self.touch_output(&event.device()) else {
+                    return;
+                };
+                let Some(pos) = self.absolute_position::<I, _>(&output, &event) else {
+                    return;
+                };
+                // the windows do not get touched while the overview is shown
+                let under = if self.show_window_preview {
+                    None
+                } else {
+                    self.surface_under(pos)
+                };
+                touch.down(
+                    self,
+                    under,
+                    &DownEvent {
+                        slot: event.slot(),
+                        location: pos,
+                        serial: SERIAL_COUNTER.next_serial(),
+                        time: event.time_msec(),
+                    },
+                );
+            }
+            InputEvent::TouchMotion { event, .. } => {
+                let Some(touch) = self.seat.get_touch() else {
+                    return;
+                };
+                let Some(output) = self.touch_output(&event.device()) else {
+                    return;
+                };
+                let Some(pos) = self.absolute_position::<I, _>(&output, &event) else {
+                    return;
+                };
+                let under = if self.show_window_preview {
+                    None
+                } else {
+                    self.surface_under(pos)
+                };
+                touch.motion(
+                    self,
+                    under,
+                    &TouchMotionEvent {
+                        slot: event.slot(),
+                        location: pos,
+                        time: event.time_msec(),
+                    },
+                );
+            }
+            InputEvent::TouchUp { event, .. } => {
+                let Some(touch) = self.seat.get_touch() else {
+                    return;
+                };
+                touch.up(
+                    self,
+                    &UpEvent {
+                        slot: event.slot(),
+                        serial: SERIAL_COUNTER.next_serial(),
+                        time: event.time_msec(),
+                    },
+                );
+            }
+            InputEvent::TouchFrame { .. } => {
+                if let Some(touch) = self.seat.get_touch() {
+                    touch.frame(self);
+                }
+            }
+            InputEvent::TouchCancel { .. } => {
+                if let Some(touch) = self.seat.get_touch() {
+                    touch.cancel(self);
+                }
+            }
+            InputEvent::GestureSwipeBegin { event, .. } => self.gesture_swipe_begin::<I>(event),
+            InputEvent::GestureSwipeUpdate { event, .. } => self.gesture_swipe_update::<I>(event),
+            InputEvent::GestureSwipeEnd { event, .. } => self.gesture_swipe_end::<I>(event),
+            InputEvent::GesturePinchBegin { event, .. } => self.gesture_pinch_begin::<I>(event),
+            InputEvent::GesturePinchUpdate { event, .. } => self.gesture_pinch_update::<I>(event),
+            InputEvent::GesturePinchEnd { event, .. } => self.gesture_pinch_end::<I>(event),
+            InputEvent::GestureHoldBegin { event, .. } => self.gesture_hold_begin::<I>(event),
+            InputEvent::GestureHoldEnd { event, .. } => self.gesture_hold_end::<I>(event),
 
             _ => {}
         }
@@ -181,13 +340,175 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
 }
 
 impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
+    /// Carry out the action of a keybinding, or one asked for over IPC
+    pub fn process_key_action(&mut self, action: KeyAction) {
+        match action {
+            KeyAction::Quit => {
+                tracing::info!("Quitting.");
+                self.running.store(false, Ordering::SeqCst);
+                self.loop_signal.stop();
+            }
+            KeyAction::VtSwitch(vt) => {
+                tracing::info!(to = vt, "Trying to switch vt");
+                self.backend_data.change_vt(vt);
+            }
+            KeyAction::Run(cmd) => self.spawn(&cmd),
+            KeyAction::ChangeElementState => {
+                let Some(window) = self.find_current_focus_window().cloned() else {
+                    return;
+                };
+                if window.is_fixed_window() {
+                    return;
+                }
+                self.handle.insert_idle(move |data| {
+                    data.state.handle_element_state_change(&window);
+                });
+                //self.wmstatus.status_change();
+            }
+            KeyAction::ChangeSplitSate(state) => {
+                self.splitstate = state;
+            }
+            KeyAction::Screen(index) => {
+                let Some(output) = self.current_output() else {
+                    return;
+                };
+                self.switch_workspace(&output, index);
+            }
+            KeyAction::MoveToScreen(index) => {
+                let Some(window) = self.find_current_focus_window().cloned() else {
+                    return;
+                };
+                let Some(output) = self.window_output(&window) else {
+                    return;
+                };
+                self.move_window_to_workspace(&window, &output, index);
+            }
+            KeyAction::CycleLayout => {
+                let Some(output) = self.current_output() else {
+                    return;
+                };
+                let name = output_tiling(&output).cycle_layout();
+                tracing::info!(layout = name, "Switch layout");
+                self.relayout_output(&output);
+            }
+            KeyAction::ScaleUp => {
+                let Some(output) = self.current_output() else {
+                    return;
+                };
+                self.change_output_scale(&output, SCALE_STEP);
+            }
+            KeyAction::ScaleDown => {
+                let Some(output) = self.current_output() else {
+                    return;
+                };
+                self.change_output_scale(&output, -SCALE_STEP);
+            }
+            KeyAction::RotateOutput => {
+                let Some(output) = self.current_output() else {
+                    return;
+                };
+                self.rotate_output(&output);
+            }
+            KeyAction::TogglePreview => self.toggle_preview(),
+            KeyAction::ToggleTint => {
+                self.show_damage_tint = !self.show_damage_tint;
+                tracing::info!(enabled = self.show_damage_tint, "Toggle damage tint");
+                if !self.show_damage_tint {
+                    // start over without the old damage next time
+                    for output in self.space.outputs() {
+                        *damage_tint(output) = Default::default();
+                    }
+                }
+            }
+            KeyAction::ReloadConfig => self.reload_config(),
+            KeyAction::NextKeyboardLayout => self.next_keyboard_layout(),
+            KeyAction::PreviewMove(direction) => self.move_preview_selection(direction),
+            KeyAction::PreviewSelect => self.select_preview(),
+            KeyAction::Screenshot(mode) => self.take_screenshot(mode),
+            KeyAction::CancelScreenshot => self.cancel_region_selection(),
+            _ => {}
+        }
+    }
+
+    /// Keep `pos` on the outputs. Outside of them it goes to the closest
+    /// point of the nearest output, so the pointer slides along the edges
+    /// and only crosses over where outputs touch.
+    fn clamp_to_outputs(&self, pos: Point<f64, Logical>) -> Point<f64, Logical> {
+        let distance = |p: &Point<f64, Logical>| (p.x - pos.x).powi(2) + (p.y - pos.y).powi(2);
+        self.space
+            .outputs()
+            .filter_map(|output| self.space.output_geometry(output))
+            .map(|geometry| {
+                let geometry = geometry.to_f64();
+                Point::from((
+                    pos.x
+                        .clamp(geometry.loc.x, geometry.loc.x + geometry.size.w - 1.0),
+                    pos.y
+                        .clamp(geometry.loc.y, geometry.loc.y + geometry.size.h - 1.0),
+                ))
+            })
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .unwrap_or(pos)
+    }
+
+    /// The output a touchscreen is mapped onto, the one named by the config,
+    /// else the built in panel, else the current output
+    fn touch_output<D: Device>(&self, device: &D) -> Option<Output> {
+        let name = device.name();
+        let config = config();
+        let configured = config
+            .input
+            .iter()
+            .filter(|settings| settings.matches(&name, &[DeviceType::Touch]))
+            .filter_map(|settings| settings.map_to_output.as_deref())
+            .last();
+        if let Some(configured) = configured {
+            return self
+                .space
+                .outputs()
+                .find(|output| output.name() == configured)
+                .cloned();
+        }
+        self.space
+            .outputs()
+            .find(|output| {
+                let name = output.name();
+                ["eDP", "LVDS", "DSI"]
+                    .iter()
+                    .any(|panel| name.starts_with(panel))
+            })
+            .cloned()
+            .or_else(|| self.current_output())
+    }
+
+    /// Map the position of an absolute device onto `output` in the space.
+    ///
+    /// The device reports positions on the panel, which does not turn with
+    /// the rotation of the output.
+    fn absolute_position<I: InputBackend, E: AbsolutePositionEvent<I>>(
+        &self,
+        output: &Output,
+        event: &E,
+    ) -> Option<Point<f64, Logical>> {
+        let output_geo = self.space.output_geometry(output)?;
+        let rotation = output_rotation(output);
+        let panel_size = rotation.transform_size(output_geo.size);
+        let position = event.position_transformed(panel_size);
+        let position = rotation
+            .invert()
+            .transform_point_in(position, &panel_size.to_f64());
+        Some(position + output_geo.loc.to_f64())
+    }
+
     fn keyboard_key_to_action<B: InputBackend>(&mut self, evt: B::KeyboardKeyEvent) -> KeyAction {
         let keycode = evt.key_code();
         let state = evt.state();
         tracing::debug!(keycode, ?state, "key");
         let serial = SERIAL_COUNTER.next_serial();
         let time = Event::time_msec(&evt);
-        let keyboard = self.seat.get_keyboard().unwrap();
+        let Some(keyboard) = self.seat.get_keyboard() else {
+            return KeyAction::None;
+        };
         keyboard
             .input(
                 self,
@@ -195,59 +516,77 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
                 state,
                 serial,
                 time,
-                |_, modifiers, handle| {
+                |data, modifiers, handle| {
+                    // a release only reaches the client if the press did,
+                    // also for the keys held while the overview opens
+                    if let KeyState::Released = state {
+                        return if data.intercepted_keys.remove(&keycode) {
+                            FilterResult::Intercept(KeyAction::None)
+                        } else {
+                            FilterResult::Forward
+                        };
+                    }
                     let keysym = handle.modified_sym();
-                    if let KeyState::Pressed = state {
-                        let action = process_keyboard_shortcut(*modifiers, keysym);
-                        action
-                            .map(FilterResult::Intercept)
-                            .unwrap_or(FilterResult::Forward)
+                    // shift changes the digits to symbols, which depend on the layout
+                    let raw_keysym = handle.raw_syms().first().copied().unwrap_or(keysym);
+                    let action = if data.region_selection.is_some()
+                        && u32::from(keysym) == xkb::KEY_Escape
+                    {
+                        // escape stops selecting the region of a screenshot
+                        Some(KeyAction::CancelScreenshot)
+                    } else if data.show_window_preview {
+                        // the overview takes all the pressed keys, none reach the windows
+                        Some(process_preview_key(*modifiers, keysym, raw_keysym))
                     } else {
-                        FilterResult::Forward
+                        process_keyboard_shortcut(*modifiers, keysym, raw_keysym)
+                    };
+                    match action {
+                        Some(action) => {
+                            data.intercepted_keys.insert(keycode);
+                            FilterResult::Intercept(action)
+                        }
+                        None => FilterResult::Forward,
                     }
                 },
             )
             .unwrap_or(KeyAction::None)
     }
 }
-fn process_keyboard_shortcut(modifiers: ModifiersState, keysym: Keysym) -> Option<KeyAction> {
+fn process_preview_key(modifiers: ModifiersState, keysym: Keysym, raw_keysym: Keysym) -> KeyAction {
+    match process_keyboard_shortcut(modifiers, keysym, raw_keysym) {
+        Some(action @ (KeyAction::Quit | KeyAction::VtSwitch(_) | KeyAction::TogglePreview)) => {
+            return action;
+        }
+        _ => {}
+    }
+    let keysym: u32 = keysym.into();
+    match keysym {
+        xkb::KEY_Left => KeyAction::PreviewMove(PreviewDirection::Left),
+        xkb::KEY_Right => KeyAction::PreviewMove(PreviewDirection::Right),
+        xkb::KEY_Up => KeyAction::PreviewMove(PreviewDirection::Up),
+        xkb::KEY_Down => KeyAction::PreviewMove(PreviewDirection::Down),
+        xkb::KEY_Return | xkb::KEY_KP_Enter => KeyAction::PreviewSelect,
+        xkb::KEY_Escape => KeyAction::TogglePreview,
+        _ => KeyAction::None,
+    }
+}
+
+fn process_keyboard_shortcut(
+    modifiers: ModifiersState,
+    keysym: Keysym,
+    raw_keysym: Keysym,
+) -> Option<KeyAction> {
     let keysym: u32 = keysym.into();
-    if modifiers.ctrl && modifiers.alt && keysym == xkb::KEY_BackSpace
-        || modifiers.logo && keysym == xkb::KEY_q
-    {
-        // ctrl+alt+backspace = quit
-        // logo + q = quit
-        Some(KeyAction::Quit)
-    } else if (xkb::KEY_XF86Switch_VT_1..=xkb::KEY_XF86Switch_VT_12).contains(&keysym) {
-        // VTSwitch
-        Some(KeyAction::VtSwitch(
+    let raw_keysym: u32 = raw_keysym.into();
+    if (xkb::KEY_XF86Switch_VT_1..=xkb::KEY_XF86Switch_VT_12).contains(&keysym) {
+        // VTSwitch, this one can not be changed by the config
+        return Some(KeyAction::VtSwitch(
             (keysym - xkb::KEY_XF86Switch_VT_1 + 1) as i32,
-        ))
-    } else if modifiers.logo && keysym == xkb::KEY_Return {
-        // run terminal
-        Some(KeyAction::Run("kitty".into()))
-    } else if modifiers.logo && keysym == xkb::KEY_l {
-        // run terminal
-        Some(KeyAction::Run("utena".into()))
-    } else if modifiers.logo && (xkb::KEY_1..=xkb::KEY_9).contains(&keysym) {
-        Some(KeyAction::Screen((keysym - xkb::KEY_1) as usize))
-    } else if modifiers.logo && modifiers.shift && keysym == xkb::KEY_M {
-        Some(KeyAction::ScaleDown)
-    } else if modifiers.logo && keysym == xkb::KEY_P {
-        Some(KeyAction::ChangeElementState)
-    } else if modifiers.logo && modifiers.shift && keysym == xkb::KEY_P {
-        Some(KeyAction::ScaleUp)
-    } else if modifiers.logo && modifiers.shift && keysym == xkb::KEY_W {
-        Some(KeyAction::TogglePreview)
-    } else if modifiers.logo && modifiers.shift && keysym == xkb::KEY_R {
-        Some(KeyAction::RotateOutput)
-    } else if modifiers.logo && modifiers.shift && keysym == xkb::KEY_T {
-        Some(KeyAction::ToggleTint)
-    } else if modifiers.logo && keysym == xkb::KEY_v {
-        Some(KeyAction::ChangeSplitSate(SplitState::VSplit))
-    } else if modifiers.logo && keysym == xkb::KEY_b {
-        Some(KeyAction::ChangeSplitSate(SplitState::HSplit))
-    } else {
-        None
+        ));
     }
+    config()
+        .keybindings
+        .iter()
+        .find(|(combo, _)| combo.matches(&modifiers, raw_keysym))
+        .map(|(_, action)| action.clone())
 }
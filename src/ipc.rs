@@ -0,0 +1,471 @@
+mod protocol;
+
+use std::{
+    ffi::OsStr,
+    io::{ErrorKind, Read, Write},
+    net::Shutdown,
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+};
+
+use serde::Serialize;
+use smithay::{
+    input::pointer::MotionEvent,
+    reexports::calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction},
+    utils::{Point, SERIAL_COUNTER},
+};
+
+pub use protocol::{
+    socket_path, Event, Geometry, Reply, Request, SplitDirection, WindowInfo, WindowState,
+    SOCKET_ENV,
+};
+
+use crate::{
+    config::parse_action,
+    input::KeyAction,
+    shell::{ElementState, WindowElement},
+    state::{Backend, SmallCageState, SplitState},
+    workspace::WORKSPACE_COUNT,
+    CalloopData,
+};
+
+/// A client which does not read what is written to it is disconnected once
+/// this much waits for it
+const MAX_PENDING_OUTPUT: usize = 1 << 20;
+/// A client is disconnected once it sent this much without ending the line
+/// of its request
+const MAX_PENDING_INPUT: usize = 1 << 20;
+
+/// The listening IPC socket and the connected clients
+#[derive(Debug)]
+pub struct IpcState {
+    path: PathBuf,
+    clients: Vec<IpcClient>,
+    next_client_id: u64,
+}
+
+/// What is written to a client waits in `output` until its socket takes it
+#[derive(Debug)]
+struct IpcClient {
+    id: u64,
+    stream: UnixStream,
+    output: Vec<u8>,
+    /// The client asked for events
+    subscribed: bool,
+    /// A source waits for the socket to become writable
+    flushing: bool,
+}
+
+impl IpcClient {
+    /// Write as much of the output as the socket takes without blocking
+    fn flush(&mut self) -> std::io::Result<()> {
+        while !self.output.is_empty() {
+            match (&self.stream).write(&self.output) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.output.drain(..n);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for IpcState {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl IpcState {
+    /// Listen on the IPC socket which belongs to the Wayland socket
+    /// `socket_name`, and tell the clients about it through [`SOCKET_ENV`]
+    pub fn new<BackendData: Backend + 'static>(
+        handle: &LoopHandle<'static, CalloopData<BackendData>>,
+        socket_name: &OsStr,
+    ) -> std::io::Result<Self> {
+        let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .ok_or_else(|| {
+                std::io::Error::new(ErrorKind::NotFound, "XDG_RUNTIME_DIR is not set")
+            })?;
+        let path = socket_path(&runtime_dir, socket_name);
+        // the Wayland socket with the same name is ours, so is this one
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+
+        handle
+            .insert_source(
+                Generic::new(listener, Interest::READ, Mode::Level),
+                |_, listener, data| {
+                    loop {
+                        match listener.as_ref().accept() {
+                            Ok((stream, _)) => data.state.add_ipc_client(stream),
+                            Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                            Err(err) => {
+                                tracing::warn!("Failed to accept an IPC client: {}", err);
+                                break;
+                            }
+                        }
+                    }
+                    Ok(PostAction::Continue)
+                },
+            )
+            .map_err(|err| err.error)?;
+
+        std::env::set_var(SOCKET_ENV, &path);
+        tracing::info!(path = %path.display(), "Listening for IPC clients");
+        Ok(Self {
+            path,
+            clients: Vec::new(),
+            next_client_id: 0,
+        })
+    }
+
+    fn client_mut(&mut self, id: u64) -> Option<&mut IpcClient> {
+        self.clients.iter_mut().find(|client| client.id == id)
+    }
+
+    /// Close the connection, the source reading from it sees the end
+    fn disconnect(&mut self, id: u64) {
+        self.clients.retain(|client| {
+            if client.id != id {
+                return true;
+            }
+            let _ = client.stream.shutdown(Shutdown::Both);
+            false
+        });
+    }
+
+    /// Write what waits for the client with `id` as far as its socket takes
+    /// it, returns whether anything is left. A client which fails the write
+    /// or falls too far behind is disconnected.
+    fn flush(&mut self, id: u64) -> bool {
+        let Some(client) = self.client_mut(id) else {
+            return false;
+        };
+        match client.flush() {
+            Ok(()) if client.output.len() <= MAX_PENDING_OUTPUT => !client.output.is_empty(),
+            Ok(()) => {
+                tracing::warn!(id, "IPC client does not read its events, disconnecting it");
+                self.disconnect(id);
+                false
+            }
+            Err(err) => {
+                tracing::debug!(id, "Failed to write to an IPC client: {}", err);
+                self.disconnect(id);
+                false
+            }
+        }
+    }
+}
+
+/// `message` as one line of JSON
+fn to_line<T: Serialize>(message: &T) -> Vec<u8> {
+    let mut line = serde_json::to_vec(message).expect("IPC messages always serialize");
+    line.push(b'\n');
+    line
+}
+
+impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
+    fn add_ipc_client(&mut self, stream: UnixStream) {
+        let Some(ipc) = self.ipc.as_mut() else {
+            return;
+        };
+        // the source reads from the stream, the clone is written to
+        let writer = match stream
+            .set_nonblocking(true)
+            .and_then(|()| stream.try_clone())
+        {
+            Ok(writer) => writer,
+            Err(err) => {
+                tracing::warn!("Failed to set up an IPC client: {}", err);
+                return;
+            }
+        };
+        let id = ipc.next_client_id;
+        ipc.next_client_id += 1;
+        ipc.clients.push(IpcClient {
+            id,
+            stream: writer,
+            output: Vec::new(),
+            subscribed: false,
+            flushing: false,
+        });
+
+        let mut buffer = Vec::new();
+        let result = self.handle.insert_source(
+            Generic::new(stream, Interest::READ, Mode::Level),
+            move |_, stream, data| {
+                let mut reader: &UnixStream = stream.as_ref();
+                let mut chunk = [0u8; 4096];
+                let closed = loop {
+                    match reader.read(&mut chunk) {
+                        Ok(0) => break true,
+                        Ok(n) => {
+                            buffer.extend_from_slice(&chunk[..n]);
+                            // the requests read so far are handled first,
+                            // the source is called again for the rest
+                            if buffer.len() > MAX_PENDING_INPUT {
+                                break false;
+                            }
+                        }
+                        Err(err) if err.kind() == ErrorKind::WouldBlock => break false,
+                        Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                        Err(_) => break true,
+                    }
+                };
+                let Some(ipc) = data.state.ipc.as_mut() else {
+                    return Ok(PostAction::Remove);
+                };
+                if closed || ipc.client_mut(id).is_none() {
+                    ipc.disconnect(id);
+                    return Ok(PostAction::Remove);
+                }
+
+                while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=end).collect();
+                    let reply = match serde_json::from_slice::<Request>(&line) {
+                        Ok(Request::Subscribe) => {
+                            if let Some(client) =
+                                data.state.ipc.as_mut().and_then(|ipc| ipc.client_mut(id))
+                            {
+                                client.subscribed = true;
+                            }
+                            Reply::Ok
+                        }
+                        Ok(request) => data.state.handle_ipc_request(request),
+                        Err(err) => Reply::Error {
+                            message: format!("Invalid request: {}", err),
+                        },
+                    };
+                    data.state.ipc_write(id, &to_line(&reply));
+                }
+                if buffer.len() > MAX_PENDING_INPUT {
+                    tracing::warn!(id, "IPC client does not end its request, disconnecting it");
+                    if let Some(ipc) = data.state.ipc.as_mut() {
+                        ipc.disconnect(id);
+                    }
+                    return Ok(PostAction::Remove);
+                }
+                Ok(PostAction::Continue)
+            },
+        );
+        if let Err(err) = result {
+            tracing::warn!("Failed to add an IPC client: {}", err.error);
+            if let Some(ipc) = self.ipc.as_mut() {
+                ipc.disconnect(id);
+            }
+        }
+    }
+
+    /// Tell the subscribed IPC clients about `event`
+    pub fn ipc_event(&mut self, event: Event) {
+        let Some(ipc) = self.ipc.as_ref() else {
+            return;
+        };
+        let subscribers: Vec<u64> = ipc
+            .clients
+            .iter()
+            .filter(|client| client.subscribed)
+            .map(|client| client.id)
+            .collect();
+        let line = to_line(&event);
+        for id in subscribers {
+            self.ipc_write(id, &line);
+        }
+    }
+
+    /// Queue `line` for the client with `id` and write as much of it as the
+    /// socket takes, the rest is written once the socket is writable again
+    fn ipc_write(&mut self, id: u64, line: &[u8]) {
+        let Some(ipc) = self.ipc.as_mut() else {
+            return;
+        };
+        let Some(client) = ipc.client_mut(id) else {
+            return;
+        };
+        client.output.extend_from_slice(line);
+        if client.flushing || !ipc.flush(id) {
+            return;
+        }
+
+        let Some(client) = ipc.client_mut(id) else {
+            return;
+        };
+        let writer = match client.stream.try_clone() {
+            Ok(writer) => writer,
+            Err(err) => {
+                tracing::warn!("Failed to wait for an IPC client: {}", err);
+                ipc.disconnect(id);
+                return;
+            }
+        };
+        client.flushing = true;
+        let result = self.handle.insert_source(
+            Generic::new(writer, Interest::WRITE, Mode::Level),
+            move |_, _, data| {
+                let Some(ipc) = data.state.ipc.as_mut() else {
+                    return Ok(PostAction::Remove);
+                };
+                if ipc.flush(id) {
+                    return Ok(PostAction::Continue);
+                }
+                if let Some(client) = ipc.client_mut(id) {
+                    client.flushing = false;
+                }
+                Ok(PostAction::Remove)
+            },
+        );
+        if let Err(err) = result {
+            tracing::warn!("Failed to wait for an IPC client: {}", err.error);
+            if let Some(ipc) = self.ipc.as_mut() {
+                ipc.disconnect(id);
+            }
+        }
+    }
+
+    fn handle_ipc_request(&mut self, request: Request) -> Reply {
+        let unknown_window = |id| Reply::Error {
+            message: format!("No window with id {}", id),
+        };
+        match request {
+            Request::Windows => Reply::Windows {
+                windows: self.ipc_windows(),
+            },
+            Request::Focus { id } => match self.ipc_window(id) {
+                Some(window) => {
+                    self.focus_ipc_window(&window);
+                    Reply::Ok
+                }
+                None => unknown_window(id),
+            },
+            Request::Close { id } => match self.ipc_window(id) {
+                Some(window) => {
+                    window.send_close();
+                    Reply::Ok
+                }
+                None => unknown_window(id),
+            },
+            Request::Split { direction } => {
+                let split = match direction {
+                    SplitDirection::Horizontal => SplitState::HSplit,
+                    SplitDirection::Vertical => SplitState::VSplit,
+                };
+                self.process_key_action(KeyAction::ChangeSplitSate(split));
+                Reply::Ok
+            }
+            Request::Workspace { index } if (1..=WORKSPACE_COUNT).contains(&index) => {
+                self.process_key_action(KeyAction::Screen(index - 1));
+                Reply::Ok
+            }
+            Request::Workspace { .. } => Reply::Error {
+                message: format!("The workspace has to be from 1 to {}", WORKSPACE_COUNT),
+            },
+            Request::Action { action } => match parse_action(&action) {
+                Ok(action) => {
+                    self.process_key_action(action);
+                    Reply::Ok
+                }
+                Err(message) => Reply::Error { message },
+            },
+            Request::KeyboardLayout => match self.keyboard_layout() {
+                Some((index, name)) => Reply::KeyboardLayout { index, name },
+                None => Reply::Error {
+                    message: "There is no keyboard".to_owned(),
+                },
+            },
+            Request::Subscribe => Reply::Ok,
+        }
+    }
+
+    /// The shown windows and the ones on hidden workspaces
+    pub fn all_windows(&self) -> Vec<WindowElement> {
+        let mut windows: Vec<WindowElement> = self.space.elements().cloned().collect();
+        for output in self.space.outputs() {
+            windows.extend(
+                self.hidden_workspace_windows(output)
+                    .into_iter()
+                    .map(|(_, window)| window),
+            );
+        }
+        windows
+    }
+
+    fn ipc_window(&self, id: u64) -> Option<WindowElement> {
+        self.all_windows()
+            .into_iter()
+            .find(|window| window.ipc_id() == id)
+    }
+
+    fn ipc_windows(&self) -> Vec<WindowInfo> {
+        let focus = self.find_current_focus_window();
+        self.all_windows()
+            .iter()
+            .map(|window| WindowInfo {
+                id: window.ipc_id(),
+                title: window.title(),
+                app_id: window.app_id(),
+                geometry: self
+                    .space
+                    .element_geometry(window)
+                    .map(|geometry| Geometry {
+                        x: geometry.loc.x,
+                        y: geometry.loc.y,
+                        width: geometry.size.w,
+                        height: geometry.size.h,
+                    }),
+                state: match window.element_state() {
+                    ElementState::Tile => WindowState::Tile,
+                    ElementState::TileToUnTile => WindowState::TileToUntile,
+                    ElementState::Untile => WindowState::Untile,
+                    ElementState::UnTileToTile => WindowState::UntileToTile,
+                },
+                output: window.output().map(|output| output.name()),
+                workspace: window.workspace() + 1,
+                focused: focus == Some(window),
+            })
+            .collect()
+    }
+
+    /// Show the workspace of `window` and focus it, the pointer is moved
+    /// onto it since the focus follows the pointer
+    fn focus_ipc_window(&mut self, window: &WindowElement) {
+        if let Some(output) = window.output() {
+            self.switch_workspace(&output, window.workspace());
+        }
+        let Some(geometry) = self.space.element_geometry(window) else {
+            return;
+        };
+        self.space.raise_element(window, true);
+        if !window.is_untiled_window() {
+            self.raise_untiled_elements();
+        }
+        self.space.elements().for_each(|window| {
+            window.send_pending_configure();
+        });
+        if let Some(keyboard) = self.seat.get_keyboard() {
+            keyboard.set_focus(
+                self,
+                Some(window.clone().into()),
+                SERIAL_COUNTER.next_serial(),
+            );
+        }
+
+        let location = geometry.loc + Point::from((geometry.size.w / 2, geometry.size.h / 2));
+        let pointer = self.pointer.clone();
+        pointer.motion(
+            self,
+            None,
+            &MotionEvent {
+                location: location.to_f64(),
+                serial: SERIAL_COUNTER.next_serial(),
+                time: self.start_time.elapsed().as_millis() as u32,
+            },
+        );
+    }
+}
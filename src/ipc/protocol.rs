@@ -0,0 +1,119 @@
+//! The messages of the IPC socket, every request, reply and event is one
+//! line of JSON. `smallcagectl` includes this file as well.
+
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Set for the clients of the compositor to the path of the IPC socket
+pub const SOCKET_ENV: &str = "SMALLCAGE_SOCKET";
+
+/// The IPC socket belonging to the Wayland socket `socket_name`, it is put
+/// next to it in `$XDG_RUNTIME_DIR`
+pub fn socket_path(runtime_dir: &Path, socket_name: &OsStr) -> PathBuf {
+    let mut name = socket_name.to_os_string();
+    name.push(".smallcage.sock");
+    runtime_dir.join(name)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    /// List the windows of every output and workspace
+    Windows,
+    Focus {
+        id: u64,
+    },
+    Close {
+        id: u64,
+    },
+    /// Set the direction the next tiled window is split off in
+    Split {
+        direction: SplitDirection,
+    },
+    /// Switch the current output to a workspace, counted from 1
+    Workspace {
+        index: usize,
+    },
+    /// Run an action, written like the actions of the keybindings in the
+    /// config file
+    Action {
+        action: String,
+    },
+    /// The active keyboard layout
+    KeyboardLayout,
+    /// Keep the connection open and send an [`Event`] for every change
+    Subscribe,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SplitDirection {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "reply", rename_all = "kebab-case")]
+pub enum Reply {
+    Ok,
+    Error { message: String },
+    Windows { windows: Vec<WindowInfo> },
+    KeyboardLayout { index: usize, name: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowInfo {
+    pub id: u64,
+    pub title: Option<String>,
+    pub app_id: Option<String>,
+    /// Where the window is in the space, there is none while its workspace
+    /// is not shown
+    pub geometry: Option<Geometry>,
+    pub state: WindowState,
+    pub output: Option<String>,
+    /// Counted from 1
+    pub workspace: usize,
+    pub focused: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Geometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Whether a window is tiled, or on its way into or out of the tiling
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WindowState {
+    Tile,
+    TileToUntile,
+    Untile,
+    UntileToTile,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    /// The keyboard focus moved to another window, or to none
+    Focus {
+        id: Option<u64>,
+    },
+    WindowOpened {
+        id: u64,
+    },
+    WindowClosed {
+        id: u64,
+    },
+    /// Another keyboard layout was switched to
+    KeyboardLayout {
+        index: usize,
+        name: String,
+    },
+}
@@ -0,0 +1,58 @@
+use smithay::input::{keyboard::Layout, Seat};
+
+use crate::{
+    config::config,
+    ipc::Event,
+    state::{Backend, SmallCageState},
+};
+
+/// Give `seat` a keyboard with the keymap of the config, or the default
+/// keymap if that one fails to load
+pub fn add_keyboard<BackendData: Backend + 'static>(seat: &mut Seat<SmallCageState<BackendData>>) {
+    let keyboard = config().keyboard.clone();
+    let (delay, rate) = (keyboard.repeat_delay, keyboard.repeat_rate);
+    if let Err(err) = seat.add_keyboard(keyboard.xkb_config(), delay, rate) {
+        tracing::error!("Failed to load the keymap, using the default: {:?}", err);
+        seat.add_keyboard(Default::default(), delay, rate).unwrap();
+    }
+}
+
+impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
+    /// Give the keyboard the keymap and repeat settings of the config
+    pub fn apply_keyboard_config(&mut self) {
+        let config = config();
+        let Some(keyboard) = self.seat.get_keyboard() else {
+            return;
+        };
+        if let Err(err) = keyboard.set_xkb_config(self, config.keyboard.xkb_config()) {
+            tracing::error!("Failed to load the keymap: {:?}", err);
+        }
+        keyboard.change_repeat_info(config.keyboard.repeat_rate, config.keyboard.repeat_delay);
+    }
+
+    /// The index and name of the active keyboard layout, there is none while
+    /// no keyboard is plugged in
+    pub fn keyboard_layout(&mut self) -> Option<(usize, String)> {
+        let keyboard = self.seat.get_keyboard()?;
+        let Layout(index) = keyboard.with_xkb_state(self, |context| context.active_layout());
+        let index = index as usize;
+        let name = config()
+            .keyboard
+            .layout_name(index)
+            .map(str::to_owned)
+            .unwrap_or_else(|| index.to_string());
+        Some((index, name))
+    }
+
+    pub fn next_keyboard_layout(&mut self) {
+        let Some(keyboard) = self.seat.get_keyboard() else {
+            return;
+        };
+        keyboard.with_xkb_state(self, |mut context| context.cycle_next_layout());
+        let Some((index, name)) = self.keyboard_layout() else {
+            return;
+        };
+        tracing::info!(layout = name, "Switch keyboard layout");
+        self.ipc_event(Event::KeyboardLayout { index, name });
+    }
+}
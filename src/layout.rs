@@ -0,0 +1,174 @@
+mod grid;
+mod master_stack;
+mod monocle;
+mod split;
+mod tree;
+
+use smithay::utils::{Logical, Rectangle};
+
+use crate::state::SplitState;
+
+pub use grid::GridLayout;
+pub use master_stack::MasterStackLayout;
+pub use monocle::MonocleLayout;
+pub use split::SplitLayout;
+pub use tree::TileTree;
+
+/// Decides where the tiled windows of an output go.
+///
+/// `arrange` gets the tiled windows in the order they were opened in and
+/// returns one geometry for each of them. Layouts which keep their own state
+/// about the windows are told about new and closed windows.
+pub trait Layout<T> {
+    fn name(&self) -> &'static str;
+
+    /// `window` was opened while `focus` was the focused window
+    fn window_added(&mut self, _window: &T, _focus: Option<&T>, _split: SplitState) {}
+
+    fn window_removed(&mut self, _window: &T) {}
+
+    /// Move an edge of `window`, one of the tiled `windows`, see
+    /// [`TileTree::move_edge`]. Returns false if the layout has no such edge.
+    fn move_edge(
+        &mut self,
+        _windows: &[T],
+        _window: &T,
+        _direction: SplitState,
+        _trailing: bool,
+        _delta: i32,
+        _area: Rectangle<i32, Logical>,
+    ) -> bool {
+        false
+    }
+
+    fn arrange(&self, windows: &[T], area: Rectangle<i32, Logical>)
+        -> Vec<Rectangle<i32, Logical>>;
+}
+
+/// The tiled windows of an output together with the layouts to choose from
+pub struct Tiling<T> {
+    windows: Vec<T>,
+    layouts: Vec<Box<dyn Layout<T>>>,
+    current: usize,
+}
+
+impl<T: Clone + PartialEq + 'static> Default for Tiling<T> {
+    fn default() -> Self {
+        Self {
+            windows: Vec::new(),
+            layouts: vec![
+                Box::<SplitLayout<T>>::default(),
+                Box::<MasterStackLayout>::default(),
+                Box::<MonocleLayout>::default(),
+                Box::<GridLayout>::default(),
+            ],
+            current: 0,
+        }
+    }
+}
+
+impl<T: Clone + PartialEq + 'static> Tiling<T> {
+    pub fn windows(&self) -> &[T] {
+        &self.windows
+    }
+
+    pub fn contains(&self, window: &T) -> bool {
+        self.windows.contains(window)
+    }
+
+    #[allow(unused)]
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// Add `window` right after `focus`, or at the end if there is no focus
+    pub fn insert(&mut self, window: T, focus: Option<&T>, split: SplitState) {
+        let index = focus
+            .and_then(|focus| self.windows.iter().position(|w| w == focus))
+            .map(|index| index + 1)
+            .unwrap_or(self.windows.len());
+        // every layout hears about it, so switching back finds the state up to date
+        for layout in self.layouts.iter_mut() {
+            layout.window_added(&window, focus, split);
+        }
+        self.windows.insert(index, window);
+    }
+
+    pub fn remove(&mut self, window: &T) -> bool {
+        let Some(index) = self.windows.iter().position(|w| w == window) else {
+            return false;
+        };
+        self.windows.remove(index);
+        for layout in self.layouts.iter_mut() {
+            layout.window_removed(window);
+        }
+        true
+    }
+
+    pub fn move_edge(
+        &mut self,
+        window: &T,
+        direction: SplitState,
+        trailing: bool,
+        delta: i32,
+        area: Rectangle<i32, Logical>,
+    ) -> bool {
+        self.layouts[self.current].move_edge(
+            &self.windows,
+            window,
+            direction,
+            trailing,
+            delta,
+            area,
+        )
+    }
+
+    pub fn arrange(&self, area: Rectangle<i32, Logical>) -> Vec<(T, Rectangle<i32, Logical>)> {
+        let geometries = self.layouts[self.current].arrange(&self.windows, area);
+        self.windows.iter().cloned().zip(geometries).collect()
+    }
+
+    pub fn geometry(
+        &self,
+        window: &T,
+        area: Rectangle<i32, Logical>,
+    ) -> Option<Rectangle<i32, Logical>> {
+        self.arrange(area)
+            .into_iter()
+            .find(|(w, _)| w == window)
+            .map(|(_, geometry)| geometry)
+    }
+
+    pub fn layout_name(&self) -> &'static str {
+        self.layouts[self.current].name()
+    }
+
+    /// Switch to the next layout and return its name
+    pub fn cycle_layout(&mut self) -> &'static str {
+        self.current = (self.current + 1) % self.layouts.len();
+        self.layout_name()
+    }
+}
+
+/// `rect` made smaller by `by` on every side
+pub fn inset(rect: Rectangle<i32, Logical>, by: i32) -> Rectangle<i32, Logical> {
+    Rectangle::from_loc_and_size(
+        (rect.loc.x + by, rect.loc.y + by),
+        ((rect.size.w - by * 2).max(1), (rect.size.h - by * 2).max(1)),
+    )
+}
+
+/// Rectangles for the tests of the layouts
+#[cfg(test)]
+mod testing {
+    use smithay::utils::{Logical, Rectangle};
+
+    pub fn rect(x: i32, y: i32, w: i32, h: i32) -> Rectangle<i32, Logical> {
+        Rectangle::from_loc_and_size((x, y), (w, h))
+    }
+
+    /// The area the windows are laid out in
+    pub fn area() -> Rectangle<i32, Logical> {
+        rect(0, 0, 100, 60)
+    }
+}
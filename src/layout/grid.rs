@@ -0,0 +1,97 @@
+use smithay::utils::{Logical, Rectangle};
+
+use super::Layout;
+
+/// Puts the windows in rows of the same height, with as many columns as rows
+/// and the last row spread over the whole width
+#[derive(Debug, Default)]
+pub struct GridLayout;
+
+impl<T: Clone + PartialEq> Layout<T> for GridLayout {
+    fn name(&self) -> &'static str {
+        "grid"
+    }
+
+    fn arrange(
+        &self,
+        windows: &[T],
+        area: Rectangle<i32, Logical>,
+    ) -> Vec<Rectangle<i32, Logical>> {
+        let count = windows.len() as i32;
+        if count == 0 {
+            return Vec::new();
+        }
+        let (x, y) = area.loc.into();
+        let (w, h) = area.size.into();
+        let columns = (count as f64).sqrt().ceil() as i32;
+        let rows = (count + columns - 1) / columns;
+        (0..count)
+            .map(|index| {
+                let row = index / columns;
+                // the last row may not be full
+                let in_row = if row == rows - 1 {
+                    count - row * columns
+                } else {
+                    columns
+                };
+                let column = index % columns;
+                let top = h * row / rows;
+                let bottom = h * (row + 1) / rows;
+                let left = w * column / in_row;
+                let right = w * (column + 1) / in_row;
+                Rectangle::from_loc_and_size((x + left, y + top), (right - left, bottom - top))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::testing::{area, rect};
+
+    #[test]
+    fn arrange_one_or_no_window() {
+        assert!(GridLayout.arrange(&[] as &[u32], area()).is_empty());
+        assert_eq!(GridLayout.arrange(&[1], area()), vec![area()]);
+    }
+
+    #[test]
+    fn arrange_full_grid() {
+        assert_eq!(
+            GridLayout.arrange(&[1, 2, 3, 4], rect(10, 20, 100, 60)),
+            vec![
+                rect(10, 20, 50, 30),
+                rect(60, 20, 50, 30),
+                rect(10, 50, 50, 30),
+                rect(60, 50, 50, 30),
+            ]
+        );
+    }
+
+    #[test]
+    fn last_row_spreads_over_width() {
+        assert_eq!(
+            GridLayout.arrange(&[1, 2, 3], area()),
+            vec![
+                rect(0, 0, 50, 30),
+                rect(50, 0, 50, 30),
+                rect(0, 30, 100, 30)
+            ]
+        );
+    }
+
+    #[test]
+    fn remainder_goes_to_later_cells() {
+        assert_eq!(
+            GridLayout.arrange(&[1, 2, 3, 4, 5], rect(0, 0, 100, 91)),
+            vec![
+                rect(0, 0, 33, 45),
+                rect(33, 0, 33, 45),
+                rect(66, 0, 34, 45),
+                rect(0, 45, 50, 46),
+                rect(50, 45, 50, 46),
+            ]
+        );
+    }
+}
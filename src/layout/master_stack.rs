@@ -0,0 +1,142 @@
+use smithay::utils::{Logical, Rectangle};
+
+use super::Layout;
+use crate::state::SplitState;
+
+const MIN_RATIO: f64 = 0.1;
+const MAX_RATIO: f64 = 0.9;
+
+/// dwm style layout, the first window is the master on the left and all the
+/// others are stacked on top of each other on the right
+#[derive(Debug)]
+pub struct MasterStackLayout {
+    ratio: f64,
+}
+
+impl Default for MasterStackLayout {
+    fn default() -> Self {
+        Self { ratio: 0.55 }
+    }
+}
+
+impl<T: Clone + PartialEq> Layout<T> for MasterStackLayout {
+    fn name(&self) -> &'static str {
+        "master-stack"
+    }
+
+    fn move_edge(
+        &mut self,
+        windows: &[T],
+        window: &T,
+        direction: SplitState,
+        trailing: bool,
+        delta: i32,
+        area: Rectangle<i32, Logical>,
+    ) -> bool {
+        // the only edge to move is the one between master and stack: the
+        // right edge of the master or the left edge of a stacked window
+        if direction != SplitState::HSplit || area.size.w <= 0 || windows.len() < 2 {
+            return false;
+        }
+        let is_master = windows.first() == Some(window);
+        if is_master != trailing || !windows.contains(window) {
+            return false;
+        }
+        let master_w = (area.size.w as f64 * self.ratio).round() as i32;
+        self.ratio = ((master_w + delta) as f64 / area.size.w as f64).clamp(MIN_RATIO, MAX_RATIO);
+        true
+    }
+
+    fn arrange(
+        &self,
+        windows: &[T],
+        area: Rectangle<i32, Logical>,
+    ) -> Vec<Rectangle<i32, Logical>> {
+        let (x, y) = area.loc.into();
+        let (w, h) = area.size.into();
+        if windows.len() <= 1 {
+            return windows.iter().map(|_| area).collect();
+        }
+        let master_w = (w as f64 * self.ratio).round() as i32;
+        let stack_count = windows.len() as i32 - 1;
+        let mut geometries = vec![Rectangle::from_loc_and_size((x, y), (master_w, h))];
+        geometries.extend((0..stack_count).map(|index| {
+            let top = h * index / stack_count;
+            let bottom = h * (index + 1) / stack_count;
+            Rectangle::from_loc_and_size((x + master_w, y + top), (w - master_w, bottom - top))
+        }));
+        geometries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::testing::{area, rect};
+
+    #[test]
+    fn arrange_without_stack() {
+        let layout = MasterStackLayout::default();
+        assert!(layout.arrange(&[] as &[u32], area()).is_empty());
+        assert_eq!(layout.arrange(&[1], area()), vec![area()]);
+    }
+
+    #[test]
+    fn arrange_stacks_the_others() {
+        let layout = MasterStackLayout::default();
+        assert_eq!(
+            layout.arrange(&[1, 2], area()),
+            vec![rect(0, 0, 55, 60), rect(55, 0, 45, 60)]
+        );
+        assert_eq!(
+            layout.arrange(&[1, 2, 3], rect(10, 20, 100, 60)),
+            vec![
+                rect(10, 20, 55, 60),
+                rect(65, 20, 45, 30),
+                rect(65, 50, 45, 30),
+            ]
+        );
+    }
+
+    #[test]
+    fn remainder_goes_to_later_rows() {
+        let layout = MasterStackLayout::default();
+        assert_eq!(
+            layout.arrange(&[1, 2, 3, 4], rect(0, 0, 100, 100)),
+            vec![
+                rect(0, 0, 55, 100),
+                rect(55, 0, 45, 33),
+                rect(55, 33, 45, 33),
+                rect(55, 66, 45, 34),
+            ]
+        );
+    }
+
+    #[test]
+    fn move_edge_between_master_and_stack() {
+        let mut layout = MasterStackLayout::default();
+        let windows = [1, 2, 3];
+
+        assert!(layout.move_edge(&windows, &1, SplitState::HSplit, true, 10, area()));
+        assert_eq!(layout.arrange(&windows, area())[0], rect(0, 0, 65, 60));
+
+        assert!(layout.move_edge(&windows, &3, SplitState::HSplit, false, -20, area()));
+        assert_eq!(layout.arrange(&windows, area())[0], rect(0, 0, 45, 60));
+
+        assert!(layout.move_edge(&windows, &2, SplitState::HSplit, false, 1000, area()));
+        assert_eq!(layout.arrange(&windows, area())[0], rect(0, 0, 90, 60));
+    }
+
+    #[test]
+    fn move_edge_ignores_other_edges() {
+        let mut layout = MasterStackLayout::default();
+        let windows = [1, 2, 3];
+
+        assert!(!layout.move_edge(&windows, &1, SplitState::HSplit, false, 10, area()));
+        assert!(!layout.move_edge(&windows, &2, SplitState::HSplit, true, 10, area()));
+        assert!(!layout.move_edge(&windows, &2, SplitState::VSplit, true, 10, area()));
+        assert!(!layout.move_edge(&windows, &9, SplitState::HSplit, false, 10, area()));
+        assert!(!layout.move_edge(&[1], &1, SplitState::HSplit, true, 10, area()));
+        assert_eq!(layout.arrange(&windows, area())[0], rect(0, 0, 55, 60));
+    }
+}
@@ -0,0 +1,35 @@
+use smithay::utils::{Logical, Rectangle};
+
+use super::Layout;
+
+/// Every window takes the whole area, the focused one is raised on top
+#[derive(Debug, Default)]
+pub struct MonocleLayout;
+
+impl<T: Clone + PartialEq> Layout<T> for MonocleLayout {
+    fn name(&self) -> &'static str {
+        "monocle"
+    }
+
+    fn arrange(
+        &self,
+        windows: &[T],
+        area: Rectangle<i32, Logical>,
+    ) -> Vec<Rectangle<i32, Logical>> {
+        windows.iter().map(|_| area).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::testing::rect;
+
+    #[test]
+    fn every_window_fills_area() {
+        let area = rect(10, 20, 100, 60);
+        assert!(MonocleLayout.arrange(&[] as &[u32], area).is_empty());
+        assert_eq!(MonocleLayout.arrange(&[1], area), vec![area]);
+        assert_eq!(MonocleLayout.arrange(&[1, 2, 3], area), vec![area; 3]);
+    }
+}
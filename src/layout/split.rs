@@ -0,0 +1,119 @@
+use smithay::utils::{Logical, Rectangle};
+
+use super::{Layout, TileTree};
+use crate::state::SplitState;
+
+/// Splits the focused window in two for every new window, with the split
+/// direction chosen by the user
+#[derive(Debug)]
+pub struct SplitLayout<T> {
+    tree: TileTree<T>,
+}
+
+impl<T> Default for SplitLayout<T> {
+    fn default() -> Self {
+        Self {
+            tree: TileTree::default(),
+        }
+    }
+}
+
+impl<T: Clone + PartialEq> Layout<T> for SplitLayout<T> {
+    fn name(&self) -> &'static str {
+        "split"
+    }
+
+    fn window_added(&mut self, window: &T, focus: Option<&T>, split: SplitState) {
+        self.tree.insert(window.clone(), focus, split);
+    }
+
+    fn window_removed(&mut self, window: &T) {
+        self.tree.remove(window);
+    }
+
+    fn move_edge(
+        &mut self,
+        _windows: &[T],
+        window: &T,
+        direction: SplitState,
+        trailing: bool,
+        delta: i32,
+        area: Rectangle<i32, Logical>,
+    ) -> bool {
+        self.tree
+            .move_edge(window, direction, trailing, delta, area)
+    }
+
+    fn arrange(
+        &self,
+        windows: &[T],
+        area: Rectangle<i32, Logical>,
+    ) -> Vec<Rectangle<i32, Logical>> {
+        let geometries = self.tree.layout(area);
+        windows
+            .iter()
+            .map(|window| {
+                geometries
+                    .iter()
+                    .find(|(w, _)| w == window)
+                    .map(|(_, geometry)| *geometry)
+                    .unwrap_or(area)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::testing::{area, rect};
+
+    #[test]
+    fn arrange_follows_tree() {
+        let mut layout = SplitLayout::default();
+        assert!(layout.arrange(&[] as &[u32], area()).is_empty());
+
+        layout.window_added(&1, None, SplitState::HSplit);
+        assert_eq!(layout.arrange(&[1], area()), vec![area()]);
+
+        layout.window_added(&2, Some(&1), SplitState::HSplit);
+        layout.window_added(&3, Some(&1), SplitState::VSplit);
+        // the geometries are in the order of the windows, not of the tree
+        assert_eq!(
+            layout.arrange(&[1, 2, 3], area()),
+            vec![rect(0, 0, 50, 30), rect(50, 0, 50, 60), rect(0, 30, 50, 30)]
+        );
+
+        layout.window_removed(&1);
+        assert_eq!(
+            layout.arrange(&[2, 3], area()),
+            vec![rect(50, 0, 50, 60), rect(0, 0, 50, 60)]
+        );
+    }
+
+    #[test]
+    fn unknown_window_fills_area() {
+        let mut layout = SplitLayout::default();
+        layout.window_added(&1, None, SplitState::HSplit);
+        layout.window_added(&2, Some(&1), SplitState::HSplit);
+        assert_eq!(
+            layout.arrange(&[1, 2, 9], area()),
+            vec![rect(0, 0, 50, 60), rect(50, 0, 50, 60), area()]
+        );
+    }
+
+    #[test]
+    fn move_edge_moves_tree_split() {
+        let mut layout = SplitLayout::default();
+        layout.window_added(&1, None, SplitState::HSplit);
+        layout.window_added(&2, Some(&1), SplitState::HSplit);
+        let windows = [1, 2];
+
+        assert!(layout.move_edge(&windows, &2, SplitState::HSplit, false, 20, area()));
+        assert_eq!(
+            layout.arrange(&windows, area()),
+            vec![rect(0, 0, 70, 60), rect(70, 0, 30, 60)]
+        );
+        assert!(!layout.move_edge(&windows, &2, SplitState::HSplit, true, 20, area()));
+    }
+}
@@ -0,0 +1,450 @@
+use smithay::utils::{Logical, Rectangle};
+
+use crate::state::SplitState;
+
+// A split never gives less than this part of its area to one side
+const MIN_RATIO: f64 = 0.1;
+const MAX_RATIO: f64 = 0.9;
+
+#[derive(Debug, Clone)]
+enum Node<T> {
+    Window(T),
+    Split {
+        state: SplitState,
+        /// The part of the area which is given to `first`
+        ratio: f64,
+        first: Box<Node<T>>,
+        second: Box<Node<T>>,
+    },
+}
+
+/// A binary container tree of tiled windows.
+///
+/// Every split node divides its area between two children, horizontally for
+/// [`SplitState::HSplit`] (first child on the left) and vertically for
+/// [`SplitState::VSplit`] (first child on the top).
+#[derive(Debug, Clone)]
+pub struct TileTree<T> {
+    root: Option<Node<T>>,
+}
+
+impl<T> Default for TileTree<T> {
+    fn default() -> Self {
+        Self { root: None }
+    }
+}
+
+impl<T: Clone + PartialEq> TileTree<T> {
+    /// Split `target` with `state` and put `window` in the second half.
+    ///
+    /// If `target` is not part of the tree, the last window is split instead.
+    pub fn insert(&mut self, window: T, target: Option<&T>, state: SplitState) {
+        let Some(root) = self.root.as_mut() else {
+            self.root = Some(Node::Window(window));
+            return;
+        };
+        let target = target
+            .filter(|target| root.contains(target))
+            .cloned()
+            .unwrap_or_else(|| root.last().clone());
+        let Some(leaf) = root.find_mut(&target) else {
+            return;
+        };
+        let old = std::mem::replace(leaf, Node::Window(window.clone()));
+        *leaf = Node::Split {
+            state,
+            ratio: 0.5,
+            first: Box::new(old),
+            second: Box::new(Node::Window(window)),
+        };
+    }
+
+    /// Take `window` out of the tree, its sibling takes over the area of the
+    /// split they shared. Returns false if the window was not in the tree.
+    pub fn remove(&mut self, window: &T) -> bool {
+        let Some(root) = self.root.take() else {
+            return false;
+        };
+        let (root, removed) = root.remove(window);
+        self.root = root;
+        removed
+    }
+
+    /// Move the edge of `window` which runs across `state` by `delta`.
+    ///
+    /// `trailing` picks the right or bottom edge, otherwise the left or top
+    /// edge is moved. The nearest split which owns that edge is changed,
+    /// returns false if there is none, as for edges on the border of `area`.
+    pub fn move_edge(
+        &mut self,
+        window: &T,
+        state: SplitState,
+        trailing: bool,
+        delta: i32,
+        area: Rectangle<i32, Logical>,
+    ) -> bool {
+        match self.root.as_mut() {
+            Some(root) => root.move_edge(window, state, trailing, delta, area),
+            None => false,
+        }
+    }
+
+    /// The geometry of every window when the tree fills `area`
+    pub fn layout(&self, area: Rectangle<i32, Logical>) -> Vec<(T, Rectangle<i32, Logical>)> {
+        let mut geometries = Vec::new();
+        if let Some(root) = &self.root {
+            root.layout(area, &mut geometries);
+        }
+        geometries
+    }
+}
+
+impl<T: Clone + PartialEq> Node<T> {
+    fn contains(&self, window: &T) -> bool {
+        match self {
+            Node::Window(w) => w == window,
+            Node::Split { first, second, .. } => first.contains(window) || second.contains(window),
+        }
+    }
+
+    fn last(&self) -> &T {
+        match self {
+            Node::Window(w) => w,
+            Node::Split { second, .. } => second.last(),
+        }
+    }
+
+    fn find_mut(&mut self, window: &T) -> Option<&mut Node<T>> {
+        if matches!(self, Node::Window(w) if w == window) {
+            return Some(self);
+        }
+        match self {
+            Node::Window(_) => None,
+            Node::Split { first, second, .. } => {
+                if first.contains(window) {
+                    first.find_mut(window)
+                } else {
+                    second.find_mut(window)
+                }
+            }
+        }
+    }
+
+    fn remove(self, window: &T) -> (Option<Node<T>>, bool) {
+        match self {
+            Node::Window(ref w) if w == window => (None, true),
+            node @ Node::Window(_) => (Some(node), false),
+            Node::Split {
+                state,
+                ratio,
+                first,
+                second,
+            } => match (*first).remove(window) {
+                (None, _) => (Some(*second), true),
+                (Some(first), true) => (
+                    Some(Node::Split {
+                        state,
+                        ratio,
+                        first: Box::new(first),
+                        second,
+                    }),
+                    true,
+                ),
+                (Some(first), false) => match (*second).remove(window) {
+                    (None, _) => (Some(first), true),
+                    (Some(second), removed) => (
+                        Some(Node::Split {
+                            state,
+                            ratio,
+                            first: Box::new(first),
+                            second: Box::new(second),
+                        }),
+                        removed,
+                    ),
+                },
+            },
+        }
+    }
+
+    fn move_edge(
+        &mut self,
+        window: &T,
+        direction: SplitState,
+        trailing: bool,
+        delta: i32,
+        area: Rectangle<i32, Logical>,
+    ) -> bool {
+        let Node::Split {
+            state,
+            ratio,
+            first,
+            second,
+        } = self
+        else {
+            return false;
+        };
+        let (first_area, second_area) = split_area(area, *state, *ratio);
+        let in_first = first.contains(window);
+        let handled = if in_first {
+            first.move_edge(window, direction, trailing, delta, first_area)
+        } else if second.contains(window) {
+            second.move_edge(window, direction, trailing, delta, second_area)
+        } else {
+            return false;
+        };
+        if handled {
+            return true;
+        }
+        // the edge between the children is the trailing edge of the first one
+        // and the leading edge of the second one
+        if *state != direction || in_first != trailing {
+            return false;
+        }
+        let (first_len, total) = match state {
+            SplitState::HSplit => (first_area.size.w, area.size.w),
+            SplitState::VSplit => (first_area.size.h, area.size.h),
+        };
+        if total <= 0 {
+            return false;
+        }
+        *ratio = ((first_len + delta) as f64 / total as f64).clamp(MIN_RATIO, MAX_RATIO);
+        true
+    }
+
+    fn layout(
+        &self,
+        area: Rectangle<i32, Logical>,
+        geometries: &mut Vec<(T, Rectangle<i32, Logical>)>,
+    ) {
+        match self {
+            Node::Window(w) => geometries.push((w.clone(), area)),
+            Node::Split {
+                state,
+                ratio,
+                first,
+                second,
+            } => {
+                let (first_area, second_area) = split_area(area, *state, *ratio);
+                first.layout(first_area, geometries);
+                second.layout(second_area, geometries);
+            }
+        }
+    }
+}
+
+fn split_area(
+    area: Rectangle<i32, Logical>,
+    state: SplitState,
+    ratio: f64,
+) -> (Rectangle<i32, Logical>, Rectangle<i32, Logical>) {
+    let (x, y) = area.loc.into();
+    let (w, h) = area.size.into();
+    match state {
+        SplitState::HSplit => {
+            let first_w = (w as f64 * ratio).round() as i32;
+            (
+                Rectangle::from_loc_and_size((x, y), (first_w, h)),
+                Rectangle::from_loc_and_size((x + first_w, y), (w - first_w, h)),
+            )
+        }
+        SplitState::VSplit => {
+            let first_h = (h as f64 * ratio).round() as i32;
+            (
+                Rectangle::from_loc_and_size((x, y), (w, first_h)),
+                Rectangle::from_loc_and_size((x, y + first_h), (w, h - first_h)),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::testing::{area, rect};
+
+    /// 1 on the left, 2 above 3 on the right
+    fn three_windows() -> TileTree<u32> {
+        let mut tree = TileTree::default();
+        tree.insert(1, None, SplitState::HSplit);
+        tree.insert(2, Some(&1), SplitState::HSplit);
+        tree.insert(3, Some(&2), SplitState::VSplit);
+        tree
+    }
+
+    #[test]
+    fn empty_tree_has_no_layout() {
+        let tree = TileTree::<u32>::default();
+        assert!(tree.layout(area()).is_empty());
+    }
+
+    #[test]
+    fn first_window_fills_area() {
+        let mut tree = TileTree::default();
+        tree.insert(1, None, SplitState::VSplit);
+        assert_eq!(tree.layout(area()), vec![(1, area())]);
+    }
+
+    #[test]
+    fn insert_splits_target() {
+        let mut tree = TileTree::default();
+        tree.insert(1, None, SplitState::HSplit);
+        tree.insert(2, Some(&1), SplitState::HSplit);
+        assert_eq!(
+            tree.layout(area()),
+            vec![(1, rect(0, 0, 50, 60)), (2, rect(50, 0, 50, 60))]
+        );
+
+        tree.insert(3, Some(&1), SplitState::VSplit);
+        assert_eq!(
+            tree.layout(area()),
+            vec![
+                (1, rect(0, 0, 50, 30)),
+                (3, rect(0, 30, 50, 30)),
+                (2, rect(50, 0, 50, 60)),
+            ]
+        );
+    }
+
+    #[test]
+    fn insert_without_target_splits_last_window() {
+        let mut tree = TileTree::default();
+        tree.insert(1, None, SplitState::HSplit);
+        tree.insert(2, None, SplitState::HSplit);
+        tree.insert(3, Some(&9), SplitState::VSplit);
+        assert_eq!(
+            tree.layout(area()),
+            vec![
+                (1, rect(0, 0, 50, 60)),
+                (2, rect(50, 0, 50, 30)),
+                (3, rect(50, 30, 50, 30)),
+            ]
+        );
+    }
+
+    #[test]
+    fn layout_is_offset_by_area() {
+        let mut tree = TileTree::default();
+        tree.insert(1, None, SplitState::HSplit);
+        tree.insert(2, Some(&1), SplitState::VSplit);
+        assert_eq!(
+            tree.layout(rect(10, 20, 100, 61)),
+            vec![(1, rect(10, 20, 100, 31)), (2, rect(10, 51, 100, 30))]
+        );
+    }
+
+    #[test]
+    fn remove_gives_area_to_sibling() {
+        let mut tree = three_windows();
+        assert!(!tree.remove(&9));
+
+        assert!(tree.remove(&2));
+        assert_eq!(
+            tree.layout(area()),
+            vec![(1, rect(0, 0, 50, 60)), (3, rect(50, 0, 50, 60))]
+        );
+
+        assert!(tree.remove(&1));
+        assert_eq!(tree.layout(area()), vec![(3, area())]);
+
+        assert!(tree.remove(&3));
+        assert!(tree.layout(area()).is_empty());
+        assert!(!tree.remove(&3));
+    }
+
+    #[test]
+    fn remove_keeps_ratio_of_other_splits() {
+        let mut tree = three_windows();
+        assert!(tree.move_edge(&1, SplitState::HSplit, true, 20, area()));
+        assert!(tree.remove(&3));
+        assert_eq!(
+            tree.layout(area()),
+            vec![(1, rect(0, 0, 70, 60)), (2, rect(70, 0, 30, 60))]
+        );
+    }
+
+    #[test]
+    fn move_edge_moves_shared_edge() {
+        let mut tree = TileTree::default();
+        tree.insert(1, None, SplitState::HSplit);
+        tree.insert(2, Some(&1), SplitState::HSplit);
+
+        assert!(tree.move_edge(&1, SplitState::HSplit, true, 10, area()));
+        assert_eq!(
+            tree.layout(area()),
+            vec![(1, rect(0, 0, 60, 60)), (2, rect(60, 0, 40, 60))]
+        );
+
+        assert!(tree.move_edge(&2, SplitState::HSplit, false, -20, area()));
+        assert_eq!(
+            tree.layout(area()),
+            vec![(1, rect(0, 0, 40, 60)), (2, rect(40, 0, 60, 60))]
+        );
+    }
+
+    #[test]
+    fn move_edge_ignores_border_edges() {
+        let mut tree = TileTree::default();
+        assert!(!tree.move_edge(&1, SplitState::HSplit, true, 10, area()));
+
+        tree.insert(1, None, SplitState::HSplit);
+        assert!(!tree.move_edge(&1, SplitState::HSplit, true, 10, area()));
+
+        tree.insert(2, Some(&1), SplitState::HSplit);
+        assert!(!tree.move_edge(&1, SplitState::HSplit, false, 10, area()));
+        assert!(!tree.move_edge(&2, SplitState::HSplit, true, 10, area()));
+        assert!(!tree.move_edge(&1, SplitState::VSplit, true, 10, area()));
+        assert!(!tree.move_edge(&9, SplitState::HSplit, true, 10, area()));
+        assert_eq!(
+            tree.layout(area()),
+            vec![(1, rect(0, 0, 50, 60)), (2, rect(50, 0, 50, 60))]
+        );
+    }
+
+    #[test]
+    fn move_edge_clamps_ratio() {
+        let mut tree = TileTree::default();
+        tree.insert(1, None, SplitState::HSplit);
+        tree.insert(2, Some(&1), SplitState::HSplit);
+
+        assert!(tree.move_edge(&1, SplitState::HSplit, true, 1000, area()));
+        assert_eq!(
+            tree.layout(area()),
+            vec![(1, rect(0, 0, 90, 60)), (2, rect(90, 0, 10, 60))]
+        );
+
+        assert!(tree.move_edge(&1, SplitState::HSplit, true, -1000, area()));
+        assert_eq!(
+            tree.layout(area()),
+            vec![(1, rect(0, 0, 10, 60)), (2, rect(10, 0, 90, 60))]
+        );
+    }
+
+    #[test]
+    fn move_edge_changes_nearest_split() {
+        let mut tree = three_windows();
+
+        // the vertical split of 2 and 3 has no edge across HSplit, so the
+        // outer split is moved
+        assert!(tree.move_edge(&3, SplitState::HSplit, false, -10, area()));
+        assert_eq!(
+            tree.layout(area()),
+            vec![
+                (1, rect(0, 0, 40, 60)),
+                (2, rect(40, 0, 60, 30)),
+                (3, rect(40, 30, 60, 30)),
+            ]
+        );
+
+        assert!(tree.move_edge(&2, SplitState::VSplit, true, 6, area()));
+        assert_eq!(
+            tree.layout(area()),
+            vec![
+                (1, rect(0, 0, 40, 60)),
+                (2, rect(40, 0, 60, 36)),
+                (3, rect(40, 36, 60, 24)),
+            ]
+        );
+
+        assert!(!tree.move_edge(&3, SplitState::VSplit, true, 6, area()));
+    }
+}
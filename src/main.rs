@@ -1,18 +1,32 @@
 mod handlers;
 
+mod config;
+mod cursor;
 mod drawing;
+mod focus;
+mod foreign_toplevel;
+mod gesture;
 mod grabs;
+mod image_capture;
 mod input;
+mod ipc;
+mod keyboard;
+mod layout;
+mod preview;
 mod render;
+mod screencopy;
+mod screenshot;
 mod shell;
 mod state;
 mod udev;
 mod winit;
+mod workspace;
+#[cfg(feature = "xwayland")]
+mod xwayland;
 
 static POSSIBLE_BACKENDS: &[&str] = &[
-    "--winit : Run anvil as a X11 or Wayland client using winit.",
-    "--tty-udev : Run anvil as a tty udev client (requires root if without logind).",
-    "--x11 : Run anvil as an X11 client.",
+    "--winit : Run smallcage as a X11 or Wayland client using winit.",
+    "--tty-udev : Run smallcage as a tty udev client (requires root if without logind).",
 ];
 
 use smithay::reexports::wayland_server::DisplayHandle;
@@ -31,19 +45,28 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         tracing_subscriber::fmt().init();
     }
 
+    match config::Config::load() {
+        Ok(config) => config::set_config(config),
+        Err(err) => tracing::error!("Invalid config, using the defaults:\n{}", err),
+    }
+
     let arg = ::std::env::args().nth(1);
     match arg.as_ref().map(|s| &s[..]) {
         Some("--winit") => {
             tracing::info!("Start with winit backend");
             winit::run_winit()?;
         }
+        Some("--tty-udev") => {
+            tracing::info!("Start with tty udev backend");
+            udev::run_udev()?;
+        }
         Some(other) => {
             tracing::error!("Unknown backend: {}", other);
         }
         None => {
             #[allow(clippy::disallowed_macros)]
             {
-                println!("USAGE: anvil --backend");
+                println!("USAGE: smallcage --backend");
                 println!();
                 println!("Possible backends are:");
                 for b in POSSIBLE_BACKENDS {
@@ -0,0 +1,168 @@
+use std::cell::RefMut;
+
+use smithay::{
+    backend::renderer::element::solid::SolidColorBuffer,
+    output::Output,
+    utils::{Logical, Point, Rectangle, Size, SERIAL_COUNTER},
+};
+
+use crate::{
+    shell::WindowElement,
+    state::{output_state, Backend, SmallCageState},
+};
+
+pub const PREVIEW_PADDING: i32 = 10;
+const MAX_PREVIEWS_PER_ROW: usize = 4;
+pub const HIGHLIGHT_COLOR: [f32; 4] = [0.35, 0.55, 0.85, 1.0];
+
+/// The window selected in the overview of an output
+#[derive(Debug, Default)]
+pub struct PreviewState {
+    pub selected: usize,
+    pub highlight: SolidColorBuffer,
+}
+
+pub fn preview_state(output: &Output) -> RefMut<'_, PreviewState> {
+    output_state(output)
+}
+
+/// Where the previews of `count` windows go, relative to the output
+pub fn preview_geometries(output: &Output, count: usize) -> Vec<Rectangle<i32, Logical>> {
+    if count == 0 {
+        return Vec::new();
+    }
+    let output_scale = output.current_scale().fractional_scale();
+    let output_transform = output.current_transform();
+    let output_size = output
+        .current_mode()
+        .map(|mode| {
+            output_transform
+                .transform_size(mode.size)
+                .to_f64()
+                .to_logical(output_scale)
+        })
+        .unwrap_or_default();
+
+    let elements_per_row = usize::min(count, MAX_PREVIEWS_PER_ROW);
+    let rows = f64::ceil(count as f64 / elements_per_row as f64);
+
+    let preview_size: Size<i32, Logical> = Size::from((
+        f64::round(output_size.w / elements_per_row as f64) as i32 - PREVIEW_PADDING * 2,
+        f64::round(output_size.h / rows) as i32 - PREVIEW_PADDING * 2,
+    ));
+
+    (0..count)
+        .map(|index| {
+            let column = index % elements_per_row;
+            let row = index / elements_per_row;
+            let location = Point::from((
+                PREVIEW_PADDING + (PREVIEW_PADDING + preview_size.w) * column as i32,
+                PREVIEW_PADDING + (PREVIEW_PADDING + preview_size.h) * row as i32,
+            ));
+            Rectangle::from_loc_and_size(location, preview_size)
+        })
+        .collect()
+}
+
+/// Arrow keys move the selection in the overview
+#[derive(Debug, Clone, Copy)]
+pub enum PreviewDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
+    /// The windows shown in the overview of `output`, in the order of the previews
+    pub fn preview_windows(&self, output: &Output) -> Vec<WindowElement> {
+        self.space.elements_for_output(output).cloned().collect()
+    }
+
+    pub fn toggle_preview(&mut self) {
+        if self.show_window_preview {
+            self.close_preview(None);
+            return;
+        }
+        self.show_window_preview = true;
+        for output in self.space.outputs() {
+            // start on the focused window if it is on this output
+            let selected = self
+                .space
+                .elements_for_output(output)
+                .position(|w| Some(w) == self.find_current_focus_window())
+                .unwrap_or(0);
+            preview_state(output).selected = selected;
+        }
+    }
+
+    pub fn move_preview_selection(&mut self, direction: PreviewDirection) {
+        let Some(output) = self.current_output() else {
+            return;
+        };
+        let count = self.space.elements_for_output(&output).count();
+        if count == 0 {
+            return;
+        }
+        let per_row = usize::min(count, MAX_PREVIEWS_PER_ROW);
+        let mut state = preview_state(&output);
+        let selected = state.selected.min(count - 1);
+        state.selected = match direction {
+            PreviewDirection::Left => selected.saturating_sub(1),
+            PreviewDirection::Right => usize::min(selected + 1, count - 1),
+            PreviewDirection::Up => selected.checked_sub(per_row).unwrap_or(selected),
+            PreviewDirection::Down => Some(selected + per_row)
+                .filter(|index| *index < count)
+                .unwrap_or(selected),
+        };
+    }
+
+    /// Leave the overview with the selected window of the current output
+    pub fn select_preview(&mut self) {
+        let Some(output) = self.current_output() else {
+            return;
+        };
+        let selected = preview_state(&output).selected;
+        let window = self.preview_windows(&output).into_iter().nth(selected);
+        self.close_preview(window);
+    }
+
+    /// Leave the overview with the window whose preview is under the pointer
+    pub fn click_preview(&mut self) {
+        let location = self.pointer.current_location();
+        let Some(output) = self.space.output_under(location).next().cloned() else {
+            return;
+        };
+        let Some(output_geo) = self.space.output_geometry(&output) else {
+            return;
+        };
+        let windows = self.preview_windows(&output);
+        let location = location - output_geo.loc.to_f64();
+        let window = preview_geometries(&output, windows.len())
+            .into_iter()
+            .zip(windows)
+            .find(|(geometry, _)| geometry.to_f64().contains(location))
+            .map(|(_, window)| window);
+        if window.is_some() {
+            self.close_preview(window);
+        }
+    }
+
+    fn close_preview(&mut self, focus: Option<WindowElement>) {
+        self.show_window_preview = false;
+        let Some(window) = focus else {
+            return;
+        };
+        let serial = SERIAL_COUNTER.next_serial();
+        self.space.raise_element(&window, true);
+        if let Some(keyboard) = self.seat.get_keyboard() {
+            keyboard.set_focus(self, Some(window.clone().into()), serial);
+        }
+        self.space.elements().for_each(|window| {
+            window.send_pending_configure();
+        });
+        if !window.is_untiled_window() {
+            self.raise_untiled_elements();
+        }
+    }
+}
@@ -2,24 +2,31 @@ use smithay::{
     backend::renderer::{
         damage::{Error as OutputDamageTrackerError, OutputDamageTracker, RenderOutputResult},
         element::{
+            solid::SolidColorRenderElement,
             surface::WaylandSurfaceRenderElement,
             utils::{
-                ConstrainAlign, ConstrainScaleBehavior, CropRenderElement, RelocateRenderElement,
-                RescaleRenderElement,
+                ConstrainAlign, ConstrainScaleBehavior, CropRenderElement, Relocate,
+                RelocateRenderElement, RescaleRenderElement,
             },
-            RenderElement, Wrap,
+            AsRenderElements, Kind, RenderElement, Wrap,
         },
         ImportAll, ImportMem, Renderer,
     },
-    desktop::space::{
-        constrain_space_element, ConstrainBehavior, ConstrainReference, Space, SpaceRenderElements,
+    desktop::{
+        layer_map_for_output,
+        space::{constrain_space_element, ConstrainBehavior, ConstrainReference, Space},
     },
     output::Output,
-    utils::{Point, Rectangle, Size},
+    utils::{Point, Scale},
+    wayland::shell::wlr_layer::Layer,
 };
 
 use crate::{
-    drawing::{PointerRenderElement, CLEAR_COLOR},
+    config::config,
+    drawing::{damage_tint, PointerRenderElement},
+    gesture::output_transition,
+    preview::{preview_geometries, preview_state, HIGHLIGHT_COLOR, PREVIEW_PADDING},
+    screenshot::selection_element,
     shell::{WindowElement, WindowRenderElement},
 };
 
@@ -42,10 +49,12 @@ impl<R: Renderer> std::fmt::Debug for CustomRenderElements<R> {
 
 smithay::backend::renderer::element::render_elements! {
     pub OutputRenderElements<R, E> where R: ImportAll + ImportMem;
-    Space=SpaceRenderElements<R, E>,
     Window=Wrap<E>,
+    Layer=WaylandSurfaceRenderElement<R>,
     Custom=CustomRenderElements<R>,
     Preview=CropRenderElement<RelocateRenderElement<RescaleRenderElement<WindowRenderElement<R>>>>,
+    Transition=RelocateRenderElement<RescaleRenderElement<E>>,
+    Solid=SolidColorRenderElement,
 }
 
 impl<R: Renderer + ImportAll + ImportMem, E: RenderElement<R> + std::fmt::Debug> std::fmt::Debug
@@ -53,10 +62,12 @@ impl<R: Renderer + ImportAll + ImportMem, E: RenderElement<R> + std::fmt::Debug>
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Space(arg0) => f.debug_tuple("Space").field(arg0).finish(),
             Self::Window(arg0) => f.debug_tuple("Window").field(arg0).finish(),
+            Self::Layer(arg0) => f.debug_tuple("Layer").field(arg0).finish(),
             Self::Custom(arg0) => f.debug_tuple("Custom").field(arg0).finish(),
             Self::Preview(arg0) => f.debug_tuple("Preview").field(arg0).finish(),
+            Self::Transition(arg0) => f.debug_tuple("Transition").field(arg0).finish(),
+            Self::Solid(arg0) => f.debug_tuple("Solid").field(arg0).finish(),
             Self::_GenericCatcher(arg0) => f.debug_tuple("_GenericCatcher").field(arg0).finish(),
         }
     }
@@ -79,45 +90,19 @@ where
         align: ConstrainAlign::CENTER,
     };
 
-    let preview_padding = 10;
-
-    let elements_on_space = space.elements_for_output(output).count();
     let output_scale = output.current_scale().fractional_scale();
-    let output_transform = output.current_transform();
-    let output_size = output
-        .current_mode()
-        .map(|mode| {
-            output_transform
-                .transform_size(mode.size)
-                .to_f64()
-                .to_logical(output_scale)
-        })
-        .unwrap_or_default();
-
-    let max_elements_per_row = 4;
-    let elements_per_row = usize::min(elements_on_space, max_elements_per_row);
-    let rows = f64::ceil(elements_on_space as f64 / elements_per_row as f64);
-
-    let preview_size = Size::from((
-        f64::round(output_size.w / elements_per_row as f64) as i32 - preview_padding * 2,
-        f64::round(output_size.h / rows) as i32 - preview_padding * 2,
-    ));
-
-    space
-        .elements_for_output(output)
-        .enumerate()
-        .flat_map(move |(element_index, window)| {
-            let column = element_index % elements_per_row;
-            let row = element_index / elements_per_row;
-            let preview_location = Point::from((
-                preview_padding + (preview_padding + preview_size.w) * column as i32,
-                preview_padding + (preview_padding + preview_size.h) * row as i32,
-            ));
-            let constrain = Rectangle::from_loc_and_size(preview_location, preview_size);
+    let windows = space.elements_for_output(output).collect::<Vec<_>>();
+    let geometries = preview_geometries(output, windows.len());
+
+    windows
+        .into_iter()
+        .zip(geometries)
+        .flat_map(move |(window, geometry)| {
+            let constrain = geometry.to_physical_precise_round(output_scale);
             constrain_space_element(
                 renderer,
                 window,
-                preview_location,
+                constrain.loc,
                 1.0,
                 output_scale,
                 constrain,
@@ -126,6 +111,56 @@ where
         })
 }
 
+/// The layer surfaces of `layers` on `output`, topmost first
+fn layer_elements<R>(
+    renderer: &mut R,
+    output: &Output,
+    layers: &[Layer],
+) -> impl Iterator<Item = WaylandSurfaceRenderElement<R>>
+where
+    R: Renderer + ImportAll,
+    R::TextureId: Clone + 'static,
+{
+    let scale = Scale::from(output.current_scale().fractional_scale());
+    let map = layer_map_for_output(output);
+    let mut elements = Vec::new();
+    for layer in layers {
+        // the surfaces mapped last are on top
+        for surface in map.layers_on(*layer).rev() {
+            let Some(geometry) = map.layer_geometry(surface) else {
+                continue;
+            };
+            let location = geometry.loc.to_physical_precise_round(scale);
+            elements.extend(AsRenderElements::<R>::render_elements::<
+                WaylandSurfaceRenderElement<R>,
+            >(surface, renderer, location, scale, 1.0));
+        }
+    }
+    elements.into_iter()
+}
+
+/// The frame around the selected preview of the overview
+fn preview_highlight_element(output: &Output, count: usize) -> Option<SolidColorRenderElement> {
+    let mut state = preview_state(output);
+    let selected = state.selected.min(count.checked_sub(1)?);
+    let geometry = preview_geometries(output, count)
+        .into_iter()
+        .nth(selected)?;
+    let border = PREVIEW_PADDING / 2;
+    let size = (geometry.size.w + border * 2, geometry.size.h + border * 2);
+    state.highlight.update(size, HIGHLIGHT_COLOR);
+
+    let scale = Scale::from(output.current_scale().fractional_scale());
+    let location = (geometry.loc - Point::from((border, border))).to_physical_precise_round(scale);
+    Some(SolidColorRenderElement::from_buffer(
+        &state.highlight,
+        location,
+        scale,
+        1.0,
+        Kind::Unspecified,
+    ))
+}
+
 #[profiling::function]
 pub fn output_elements<R>(
     output: &Output,
@@ -133,6 +168,7 @@ pub fn output_elements<R>(
     custom_elements: impl IntoIterator<Item = CustomRenderElements<R>>,
     renderer: &mut R,
     show_window_preview: bool,
+    show_damage_tint: bool,
 ) -> (
     Vec<OutputRenderElements<R, WindowRenderElement<R>>>,
     [f32; 4],
@@ -167,20 +203,68 @@ where
         .map(OutputRenderElements::from)
         .collect::<Vec<_>>();
 
-    if show_window_preview && space.elements_for_output(output).count() > 0 {
+    // the region selected for a screenshot shades everything but the pointer
+    output_render_elements.extend(selection_element(output).map(OutputRenderElements::Solid));
+
+    // the first element is drawn on top, panels and overlays go above the
+    // windows and wallpapers below
+    output_render_elements.extend(
+        layer_elements(renderer, output, &[Layer::Overlay, Layer::Top])
+            .map(OutputRenderElements::Layer),
+    );
+
+    let preview_count = space.elements_for_output(output).count();
+    if show_window_preview && preview_count > 0 {
+        // the windows themselves are hidden behind the overview
         output_render_elements.extend(space_preview_elements(renderer, space, output));
+        output_render_elements.extend(
+            preview_highlight_element(output, preview_count).map(OutputRenderElements::Solid),
+        );
+    } else {
+        let window_elements = space
+            .render_elements_for_output(renderer, output, 1.0)
+            .expect("output without mode?");
+        let transition = output_transition(output).current();
+        match (transition, space.output_geometry(output)) {
+            // a gesture moves and shrinks the workspace around the middle
+            (Some((offset, zoom)), Some(output_geo)) => {
+                let scale = output.current_scale().fractional_scale();
+                let center = output_geo
+                    .size
+                    .downscale(2)
+                    .to_point()
+                    .to_physical_precise_round(scale);
+                let offset = Point::from(((offset * scale).round() as i32, 0));
+                output_render_elements.extend(window_elements.into_iter().map(|element| {
+                    let element = RescaleRenderElement::from_element(element, center, zoom);
+                    OutputRenderElements::Transition(RelocateRenderElement::from_element(
+                        element,
+                        offset,
+                        Relocate::Relative,
+                    ))
+                }));
+            }
+            _ => output_render_elements.extend(
+                window_elements
+                    .into_iter()
+                    .map(|element| OutputRenderElements::Window(Wrap::from(element))),
+            ),
+        }
     }
 
-    let space_elements = smithay::desktop::space::space_render_elements::<_, WindowElement, _>(
-        renderer,
-        [space],
-        output,
-        1.0,
-    )
-    .expect("output without mode?");
-    output_render_elements.extend(space_elements.into_iter().map(OutputRenderElements::Space));
+    output_render_elements.extend(
+        layer_elements(renderer, output, &[Layer::Bottom, Layer::Background])
+            .map(OutputRenderElements::Layer),
+    );
+
+    if show_damage_tint {
+        let mut tint = damage_tint(output);
+        tint.update::<R, _>(output, &output_render_elements);
+        let tint_elements = tint.elements().map(OutputRenderElements::Solid);
+        output_render_elements.splice(0..0, tint_elements);
+    }
 
-    (output_render_elements, CLEAR_COLOR)
+    (output_render_elements, config().theme.background)
     //}
 }
 
@@ -193,6 +277,7 @@ pub fn render_output<R>(
     damage_tracker: &mut OutputDamageTracker,
     age: usize,
     show_window_preview: bool,
+    show_damage_tint: bool,
 ) -> Result<RenderOutputResult, OutputDamageTrackerError<R>>
 where
     R: Renderer + ImportAll + ImportMem,
@@ -204,6 +289,7 @@ where
         custom_elements,
         renderer,
         show_window_preview,
+        show_damage_tint,
     );
     damage_tracker.render_output(renderer, age, &elements, clear_color)
 }
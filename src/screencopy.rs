@@ -0,0 +1,483 @@
+//! `zwlr_screencopy_manager_v1`, which screenshot and recording tools like
+//! grim and wf-recorder use to capture outputs or regions of them.
+//!
+//! The frames clients ask to be copied wait in the user data of their
+//! output, the backend renders them offscreen with the elements of the
+//! output the next time it draws it. Frames copied with damage wait until
+//! the output changed since the last frame of their client. Frames are
+//! copied in the orientation of the buffer of the output, clients turn them
+//! by the transform of the `wl_output`.
+//!
+//! Only outputs and regions of them can be captured here, single windows
+//! are captured through `ext_image_copy_capture_v1` in `image_capture`.
+
+use std::{
+    cell::RefMut,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            damage::{Error as OutputDamageTrackerError, OutputDamageTracker},
+            element::RenderElement,
+            gles::GlesTexture,
+            Bind, ExportMem, ImportAll, ImportMem, Offscreen, Renderer,
+        },
+    },
+    desktop::Space,
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::screencopy::v1::server::{
+            zwlr_screencopy_frame_v1::{self, Flags, ZwlrScreencopyFrameV1},
+            zwlr_screencopy_manager_v1::{self, ZwlrScreencopyManagerV1},
+        },
+        wayland_server::{
+            protocol::{wl_buffer::WlBuffer, wl_shm},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, Weak,
+        },
+    },
+    utils::{Buffer, Clock, Logical, Monotonic, Physical, Rectangle, Scale, Size, Transform},
+    wayland::shm::{with_buffer_contents, with_buffer_contents_mut, BufferAccessError},
+};
+
+use crate::{
+    render::{output_elements, CustomRenderElements},
+    shell::WindowElement,
+    state::{output_state, Backend, SmallCageState},
+};
+
+const VERSION: u32 = 3;
+
+/// What a frame captures, in the pixels of the buffer of its output
+#[derive(Debug, Clone)]
+struct Capture {
+    output: Output,
+    region: Rectangle<i32, Buffer>,
+    overlay_cursor: bool,
+}
+
+pub struct FrameData {
+    /// `None` if there is nothing to capture, the frame failed then
+    capture: Option<Capture>,
+    manager: Weak<ZwlrScreencopyManagerV1>,
+    used: AtomicBool,
+}
+
+/// A frame with the buffer to copy it into, waiting for its output to be
+/// drawn
+struct PendingScreencopy {
+    frame: ZwlrScreencopyFrameV1,
+    buffer: WlBuffer,
+    capture: Capture,
+    /// Set for `copy_with_damage`, the damage is tracked per manager
+    damage_of: Option<Weak<ZwlrScreencopyManagerV1>>,
+}
+
+/// What a manager which copies with damage has seen of an output
+struct DamageTracker {
+    manager: Weak<ZwlrScreencopyManagerV1>,
+    tracker: OutputDamageTracker,
+    /// What the tracker was made for, it starts over when the output changes
+    mode: (Size<i32, Physical>, f64, Transform),
+}
+
+#[derive(Default)]
+struct ScreencopyDamage(Vec<DamageTracker>);
+
+fn screencopy_damage(output: &Output) -> RefMut<'_, ScreencopyDamage> {
+    output_state(output)
+}
+
+fn pending_screencopies(output: &Output) -> RefMut<'_, Vec<PendingScreencopy>> {
+    output_state(output)
+}
+
+/// Advertise the screencopy manager to the clients
+pub fn init_screencopy<BackendData: Backend + 'static>(dh: &DisplayHandle) {
+    dh.create_global::<SmallCageState<BackendData>, ZwlrScreencopyManagerV1, _>(VERSION, ());
+}
+
+/// Fail the frames waiting for `output`, which has gone away
+pub fn fail_screencopies(output: &Output) {
+    for screencopy in std::mem::take(&mut *pending_screencopies(output)) {
+        screencopy.frame.failed();
+    }
+    screencopy_damage(output).0.clear();
+}
+
+impl<BackendData: Backend + 'static> GlobalDispatch<ZwlrScreencopyManagerV1, ()>
+    for SmallCageState<BackendData>
+{
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrScreencopyManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ZwlrScreencopyManagerV1, ()>
+    for SmallCageState<BackendData>
+{
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        manager: &ZwlrScreencopyManagerV1,
+        request: zwlr_screencopy_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        use zwlr_screencopy_manager_v1::Request;
+        let (frame, overlay_cursor, output, region) = match request {
+            Request::CaptureOutput {
+                frame,
+                overlay_cursor,
+                output,
+            } => (frame, overlay_cursor, output, None),
+            Request::CaptureOutputRegion {
+                frame,
+                overlay_cursor,
+                output,
+                x,
+                y,
+                width,
+                height,
+            } => (
+                frame,
+                overlay_cursor,
+                output,
+                Some(Rectangle::<i32, Logical>::from_loc_and_size(
+                    (x, y),
+                    (width, height),
+                )),
+            ),
+            Request::Destroy => return,
+            _ => unreachable!(),
+        };
+
+        let capture = Output::from_resource(&output).and_then(|output| {
+            let geometry = state.space.output_geometry(&output)?;
+            let scale = output.current_scale().fractional_scale();
+            let transform = output.current_transform();
+            // regions are given in logical coordinates of the output
+            let full = Rectangle::<i32, Logical>::from_loc_and_size((0, 0), geometry.size);
+            let region = match region {
+                Some(region) => region.intersection(full)?,
+                None => full,
+            };
+            let to_buffer = |rect: Rectangle<i32, Logical>| -> Rectangle<i32, Buffer> {
+                rect.to_f64()
+                    .to_buffer(scale, transform, &geometry.size.to_f64())
+                    .to_i32_round()
+            };
+            let region = to_buffer(region).intersection(to_buffer(full))?;
+            if region.is_empty() {
+                return None;
+            }
+            Some(Capture {
+                output,
+                region,
+                overlay_cursor: overlay_cursor != 0,
+            })
+        });
+
+        let frame = data_init.init(
+            frame,
+            FrameData {
+                capture: capture.clone(),
+                manager: manager.downgrade(),
+                used: AtomicBool::new(false),
+            },
+        );
+        let Some(capture) = capture else {
+            frame.failed();
+            return;
+        };
+        let size = capture.region.size;
+        frame.buffer(
+            wl_shm::Format::Xrgb8888,
+            size.w as u32,
+            size.h as u32,
+            size.w as u32 * 4,
+        );
+        if frame.version() >= 3 {
+            frame.buffer_done();
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ZwlrScreencopyFrameV1, FrameData>
+    for SmallCageState<BackendData>
+{
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        frame: &ZwlrScreencopyFrameV1,
+        request: zwlr_screencopy_frame_v1::Request,
+        data: &FrameData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        use zwlr_screencopy_frame_v1::Request;
+        let (buffer, with_damage) = match request {
+            Request::Copy { buffer } => (buffer, false),
+            Request::CopyWithDamage { buffer } => (buffer, true),
+            Request::Destroy => return,
+            _ => unreachable!(),
+        };
+        let Some(capture) = data.capture.clone() else {
+            frame.failed();
+            return;
+        };
+        if data.used.swap(true, Ordering::SeqCst) {
+            frame.post_error(
+                zwlr_screencopy_frame_v1::Error::AlreadyUsed,
+                "the frame was already copied",
+            );
+            return;
+        }
+
+        let size = capture.region.size;
+        let valid = with_buffer_contents(&buffer, |_, _, info| {
+            matches!(
+                info.format,
+                wl_shm::Format::Xrgb8888 | wl_shm::Format::Argb8888
+            ) && info.width == size.w
+                && info.height == size.h
+                && info.stride >= size.w * 4
+        })
+        .unwrap_or(false);
+        if !valid {
+            frame.post_error(
+                zwlr_screencopy_frame_v1::Error::InvalidBuffer,
+                "the buffer does not match the frame",
+            );
+            return;
+        }
+
+        let output = capture.output.clone();
+        pending_screencopies(&output).push(PendingScreencopy {
+            frame: frame.clone(),
+            buffer,
+            capture,
+            damage_of: with_damage.then(|| data.manager.clone()),
+        });
+    }
+}
+
+/// Render `elements` into an offscreen buffer of `size`, turned by
+/// `transform` like the buffer of an output, and read the pixels of `region`
+/// back, as ARGB8888 rows without padding
+pub fn render_to_memory<R, E>(
+    renderer: &mut R,
+    size: Size<i32, Physical>,
+    scale: Scale<f64>,
+    transform: Transform,
+    elements: &[E],
+    clear_color: [f32; 4],
+    region: Rectangle<i32, Buffer>,
+) -> Result<Vec<u8>, OutputDamageTrackerError<R>>
+where
+    R: Renderer + Offscreen<GlesTexture> + Bind<GlesTexture> + ExportMem,
+    E: RenderElement<R>,
+{
+    let buffer_size = size.to_logical(1).to_buffer(1, Transform::Normal);
+    let texture: GlesTexture = renderer
+        .create_buffer(Fourcc::Abgr8888, buffer_size)
+        .map_err(OutputDamageTrackerError::Rendering)?;
+    renderer
+        .bind(texture)
+        .map_err(OutputDamageTrackerError::Rendering)?;
+    let mut damage_tracker = OutputDamageTracker::new(size, scale, transform);
+    damage_tracker.render_output(renderer, 0, elements, clear_color)?;
+    let mapping = renderer
+        .copy_framebuffer(region, Fourcc::Argb8888)
+        .map_err(OutputDamageTrackerError::Rendering)?;
+    let pixels = renderer
+        .map_texture(&mapping)
+        .map_err(OutputDamageTrackerError::Rendering)?
+        .to_vec();
+    Ok(pixels)
+}
+
+/// Copy rows of `pixels` into a shm buffer, which may have a larger stride
+pub fn copy_to_shm(buffer: &WlBuffer, pixels: &[u8], width: i32) -> Result<(), BufferAccessError> {
+    with_buffer_contents_mut(buffer, |ptr, len, info| {
+        // Safety: smithay maps the whole pool for us, `len` bytes from `ptr`
+        let pool = unsafe { std::slice::from_raw_parts_mut(ptr, len) };
+        let row = width as usize * 4;
+        for (y, src) in pixels.chunks_exact(row).enumerate() {
+            let start = info.offset as usize + y * info.stride as usize;
+            if let Some(dst) = pool.get_mut(start..start + row) {
+                dst.copy_from_slice(src);
+            }
+        }
+    })
+}
+
+/// Fulfill the screencopy frames waiting for `output`. `cursor_elements`
+/// draws the pointer for the frames which include it.
+pub fn render_screencopies<R>(
+    renderer: &mut R,
+    output: &Output,
+    space: &Space<WindowElement>,
+    show_window_preview: bool,
+    mut cursor_elements: impl FnMut(&mut R) -> Vec<CustomRenderElements<R>>,
+) where
+    R: Renderer + ImportAll + ImportMem + Offscreen<GlesTexture> + Bind<GlesTexture> + ExportMem,
+    R::TextureId: Clone + 'static,
+{
+    let pending = std::mem::take(&mut *pending_screencopies(output));
+    if pending.is_empty() {
+        return;
+    }
+    let Some(geometry) = space.output_geometry(output) else {
+        for screencopy in pending {
+            screencopy.frame.failed();
+        }
+        return;
+    };
+    let scale = output.current_scale().fractional_scale();
+    let transform = output.current_transform();
+    // the size of the buffer of the output, before it is turned
+    let size = transform.transform_size(geometry.size.to_physical_precise_round(scale));
+    let mut waiting = Vec::new();
+
+    for screencopy in pending {
+        if !screencopy.frame.is_alive() {
+            continue;
+        }
+        let capture = &screencopy.capture;
+        let custom_elements = if capture.overlay_cursor {
+            cursor_elements(renderer)
+        } else {
+            Vec::new()
+        };
+        let (elements, clear_color) = output_elements(
+            output,
+            space,
+            custom_elements,
+            renderer,
+            show_window_preview,
+            false,
+        );
+        let region = capture.region;
+        let damage = match screencopy.damage_of.clone() {
+            Some(manager) => {
+                match output_damage::<R, _>(
+                    output, &manager, size, scale, transform, &elements, region,
+                ) {
+                    Ok(damage) if damage.is_empty() => {
+                        waiting.push(screencopy);
+                        continue;
+                    }
+                    Ok(damage) => damage,
+                    Err(err) => {
+                        tracing::warn!("Failed to track the screencopy damage: {:?}", err);
+                        screencopy.frame.failed();
+                        continue;
+                    }
+                }
+            }
+            None => Vec::new(),
+        };
+        let pixels = match render_to_memory(
+            renderer,
+            size,
+            Scale::from(scale),
+            transform,
+            &elements,
+            clear_color,
+            region,
+        ) {
+            Ok(pixels) => pixels,
+            Err(err) => {
+                tracing::warn!("Failed to render the screencopy: {}", err);
+                screencopy.frame.failed();
+                continue;
+            }
+        };
+        if let Err(err) = copy_to_shm(&screencopy.buffer, &pixels, region.size.w) {
+            tracing::warn!("Failed to copy into the screencopy buffer: {}", err);
+            screencopy.frame.failed();
+            continue;
+        }
+
+        // the rows are read back top down, and the transform of the output
+        // is already told by its wl_output
+        let frame = &screencopy.frame;
+        frame.flags(Flags::empty());
+        for rect in damage {
+            frame.damage(
+                rect.loc.x as u32,
+                rect.loc.y as u32,
+                rect.size.w as u32,
+                rect.size.h as u32,
+            );
+        }
+        let time = Duration::from(Clock::<Monotonic>::new().now());
+        let secs = time.as_secs();
+        frame.ready((secs >> 32) as u32, secs as u32, time.subsec_nanos());
+    }
+
+    // copies with damage wait for the output to change
+    pending_screencopies(output).extend(waiting);
+}
+
+/// The damage of `elements` since the last frame `manager` copied with
+/// damage, relative to `region`. The first frame is damaged all over, as is
+/// the first one after the output changed its size, scale or transform.
+fn output_damage<R, E>(
+    output: &Output,
+    manager: &Weak<ZwlrScreencopyManagerV1>,
+    size: Size<i32, Physical>,
+    scale: f64,
+    transform: Transform,
+    elements: &[E],
+    region: Rectangle<i32, Buffer>,
+) -> Result<Vec<Rectangle<i32, Buffer>>, OutputDamageTrackerError<R>>
+where
+    R: Renderer,
+    E: RenderElement<R>,
+{
+    let mut trackers = screencopy_damage(output);
+    let mode = (size, scale, transform);
+    trackers
+        .0
+        .retain(|tracker| tracker.manager.upgrade().is_ok() && tracker.mode == mode);
+    let index = match trackers.0.iter().position(|t| t.manager == *manager) {
+        Some(index) => index,
+        None => {
+            trackers.0.push(DamageTracker {
+                manager: manager.clone(),
+                tracker: OutputDamageTracker::new(size, scale, transform),
+                mode,
+            });
+            trackers.0.len() - 1
+        }
+    };
+    let (damage, _) = trackers.0[index].tracker.damage_output(1, elements)?;
+    // the damage is on the output as it is shown, the region on its buffer
+    let output_size = transform.transform_size(size).to_logical(1);
+    let damage = damage
+        .into_iter()
+        .flatten()
+        .map(|rect| rect.to_logical(1).to_buffer(1, transform, &output_size))
+        .filter_map(|rect| rect.intersection(region))
+        .map(|rect| {
+            Rectangle::from_loc_and_size(
+                (rect.loc.x - region.loc.x, rect.loc.y - region.loc.y),
+                (rect.size.w, rect.size.h),
+            )
+        })
+        .collect();
+    Ok(damage)
+}
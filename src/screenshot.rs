@@ -0,0 +1,339 @@
+//! Screenshots saved as PNG files, of the current output, of the focused
+//! window or of a region selected with the pointer.
+//!
+//! Like the frames of screencopy, the screenshots wait in the user data of
+//! their output and the backend renders them offscreen the next time it
+//! draws the output. The files are written on a thread of their own.
+
+use std::{
+    cell::RefMut,
+    fs::OpenOptions,
+    io::{BufWriter, ErrorKind},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use smithay::{
+    backend::{
+        input::ButtonState,
+        renderer::{
+            element::{
+                solid::{SolidColorBuffer, SolidColorRenderElement},
+                Kind,
+            },
+            gles::GlesTexture,
+            Bind, ExportMem, ImportAll, ImportMem, Offscreen, Renderer,
+        },
+    },
+    desktop::Space,
+    output::Output,
+    utils::{Buffer, Logical, Physical, Point, Rectangle, Scale, Transform},
+};
+
+use crate::{
+    config::config,
+    render::output_elements,
+    screencopy::render_to_memory,
+    shell::WindowElement,
+    state::{output_state, Backend, SmallCageState},
+};
+
+/// The highlight colour of the overview at 30%, premultiplied
+const SELECTION_COLOR: [f32; 4] = [0.105, 0.165, 0.255, 0.3];
+
+/// What a screenshot shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenshotMode {
+    /// The output the pointer is on
+    Output,
+    /// The focused window, with its header bar
+    Window,
+    /// A rectangle dragged with the pointer
+    Region,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ScreenshotError {
+    #[error("Failed to write {}: {source}", .path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Failed to encode the PNG: {0}")]
+    Png(#[from] png::EncodingError),
+}
+
+/// A region of an output is being selected for a screenshot, the drag
+/// starts with the first button press
+#[derive(Debug, Default)]
+pub struct RegionSelection {
+    start: Option<(Output, Point<i32, Logical>)>,
+}
+
+/// The selected region shown on an output, relative to it
+#[derive(Debug, Default)]
+struct SelectionState {
+    region: Option<Rectangle<i32, Logical>>,
+    buffer: SolidColorBuffer,
+}
+
+fn selection_state(output: &Output) -> RefMut<'_, SelectionState> {
+    output_state(output)
+}
+
+fn pending_screenshots(output: &Output) -> RefMut<'_, Vec<Rectangle<i32, Buffer>>> {
+    output_state(output)
+}
+
+/// The shade over the region being selected on `output`
+pub fn selection_element(output: &Output) -> Option<SolidColorRenderElement> {
+    let mut state = selection_state(output);
+    let region = state.region?;
+    state.buffer.update(region.size, SELECTION_COLOR);
+
+    let scale = Scale::from(output.current_scale().fractional_scale());
+    Some(SolidColorRenderElement::from_buffer(
+        &state.buffer,
+        region.loc.to_physical_precise_round(scale),
+        scale,
+        1.0,
+        Kind::Unspecified,
+    ))
+}
+
+impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
+    pub fn take_screenshot(&mut self, mode: ScreenshotMode) {
+        match mode {
+            ScreenshotMode::Output => {
+                let Some(output) = self.current_output() else {
+                    return;
+                };
+                let Some(geometry) = self.space.output_geometry(&output) else {
+                    return;
+                };
+                self.queue_screenshot(&output, Rectangle::from_loc_and_size((0, 0), geometry.size));
+            }
+            ScreenshotMode::Window => {
+                let Some(window) = self.find_current_focus_window().cloned() else {
+                    return;
+                };
+                let Some(output) = self.window_output(&window) else {
+                    return;
+                };
+                // the geometry of the element includes the header bar of
+                // server side decorated windows
+                let (Some(mut geometry), Some(output_geometry)) = (
+                    self.space.element_geometry(&window),
+                    self.space.output_geometry(&output),
+                ) else {
+                    return;
+                };
+                geometry.loc -= output_geometry.loc;
+                self.queue_screenshot(&output, geometry);
+            }
+            ScreenshotMode::Region => {
+                tracing::info!("Select the region of the screenshot");
+                self.region_selection = Some(RegionSelection::default());
+            }
+        }
+    }
+
+    /// Take a screenshot of `region`, relative to `output`, the next time
+    /// the output is drawn
+    fn queue_screenshot(&mut self, output: &Output, region: Rectangle<i32, Logical>) {
+        let Some(geometry) = self.space.output_geometry(output) else {
+            return;
+        };
+        let scale = output.current_scale().fractional_scale();
+        let full = Rectangle::<i32, Physical>::from_loc_and_size(
+            (0, 0),
+            geometry.size.to_physical_precise_round(scale),
+        );
+        let region: Rectangle<i32, Physical> = region.to_physical_precise_round(scale);
+        let Some(region) = region
+            .intersection(full)
+            .filter(|region| !region.is_empty())
+        else {
+            return;
+        };
+        pending_screenshots(output).push(Rectangle::from_loc_and_size(
+            (region.loc.x, region.loc.y),
+            (region.size.w, region.size.h),
+        ));
+    }
+
+    /// Start the drag of the region selection, or end it and take the
+    /// screenshot
+    pub fn region_selection_button(&mut self, state: ButtonState) {
+        let location = self.pointer.current_location().to_i32_round();
+        match state {
+            ButtonState::Pressed => {
+                let Some(output) = self.current_output() else {
+                    return;
+                };
+                if let Some(selection) = self.region_selection.as_mut() {
+                    selection.start = Some((output, location));
+                }
+            }
+            ButtonState::Released => {
+                let Some(RegionSelection {
+                    start: Some((output, _)),
+                }) = self.region_selection.as_ref()
+                else {
+                    return;
+                };
+                let output = output.clone();
+                self.update_region_selection(location.to_f64());
+                let region = selection_state(&output).region.take();
+                self.region_selection = None;
+                if let Some(region) = region {
+                    self.queue_screenshot(&output, region);
+                }
+            }
+        }
+    }
+
+    /// Follow the pointer with the selected region, it stays on the output
+    /// the drag started on
+    pub fn update_region_selection(&mut self, location: Point<f64, Logical>) {
+        let Some(RegionSelection {
+            start: Some((output, start)),
+        }) = self.region_selection.as_ref()
+        else {
+            return;
+        };
+        let Some(geometry) = self.space.output_geometry(output) else {
+            return;
+        };
+        let end = Point::<i32, Logical>::from((
+            (location.x.round() as i32).clamp(geometry.loc.x, geometry.loc.x + geometry.size.w),
+            (location.y.round() as i32).clamp(geometry.loc.y, geometry.loc.y + geometry.size.h),
+        ));
+        let loc = Point::from((start.x.min(end.x), start.y.min(end.y)));
+        let size = ((start.x - end.x).abs(), (start.y - end.y).abs());
+        selection_state(output).region =
+            Some(Rectangle::from_loc_and_size(loc - geometry.loc, size));
+    }
+
+    pub fn cancel_region_selection(&mut self) {
+        if let Some(RegionSelection {
+            start: Some((output, _)),
+        }) = self.region_selection.take()
+        {
+            selection_state(&output).region = None;
+        }
+        tracing::info!("Screenshot cancelled");
+    }
+}
+
+/// Render the screenshots waiting for `output` and save them
+pub fn render_screenshots<R>(
+    renderer: &mut R,
+    output: &Output,
+    space: &Space<WindowElement>,
+    show_window_preview: bool,
+) where
+    R: Renderer + ImportAll + ImportMem + Offscreen<GlesTexture> + Bind<GlesTexture> + ExportMem,
+    R::TextureId: Clone + 'static,
+{
+    let pending = std::mem::take(&mut *pending_screenshots(output));
+    if pending.is_empty() {
+        return;
+    }
+    let Some(geometry) = space.output_geometry(output) else {
+        return;
+    };
+    let scale = output.current_scale().fractional_scale();
+    let size = geometry.size.to_physical_precise_round(scale);
+
+    for region in pending {
+        let (elements, clear_color) = output_elements(
+            output,
+            space,
+            Vec::new(),
+            renderer,
+            show_window_preview,
+            false,
+        );
+        let pixels = match render_to_memory(
+            renderer,
+            size,
+            Scale::from(scale),
+            // screenshots are saved upright
+            Transform::Normal,
+            &elements,
+            clear_color,
+            region,
+        ) {
+            Ok(pixels) => pixels,
+            Err(err) => {
+                tracing::warn!("Failed to render the screenshot: {}", err);
+                continue;
+            }
+        };
+        let dir = screenshot_dir();
+        std::thread::spawn(move || {
+            match save_png(&dir, region.size.w as u32, region.size.h as u32, pixels) {
+                Ok(path) => tracing::info!(path = %path.display(), "Saved screenshot"),
+                Err(err) => tracing::warn!("Failed to save the screenshot: {}", err),
+            }
+        });
+    }
+}
+
+/// The configured directory, else `~/Pictures`
+fn screenshot_dir() -> PathBuf {
+    config().screenshot_dir.clone().unwrap_or_else(|| {
+        std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join("Pictures")
+    })
+}
+
+/// Write the ARGB8888 `pixels` into a new file in `dir`
+fn save_png(
+    dir: &Path,
+    width: u32,
+    height: u32,
+    mut pixels: Vec<u8>,
+) -> Result<PathBuf, ScreenshotError> {
+    let io_error = |path: &Path| {
+        let path = path.to_owned();
+        move |source| ScreenshotError::Io { path, source }
+    };
+    std::fs::create_dir_all(dir).map_err(io_error(dir))?;
+
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    // screenshots of the same second are saved on threads of their own,
+    // the name is only taken once the file could be created
+    let mut n = 0;
+    let (path, file) = loop {
+        let path = match n {
+            0 => dir.join(format!("screenshot-{}.png", secs)),
+            n => dir.join(format!("screenshot-{}-{}.png", secs, n)),
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => break (path, file),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => n += 1,
+            Err(err) => return Err(io_error(&path)(err)),
+        }
+    };
+
+    // the bytes are in BGRA order, nothing on the screen is see-through
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+        pixel[3] = 255;
+    }
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()?;
+    Ok(path)
+}
@@ -1,31 +1,49 @@
 mod elementkeyboard;
 mod elementpoint;
+mod elementtouch;
 
 use std::{
     cell::{Ref, RefCell, RefMut},
+    sync::atomic::{AtomicU64, Ordering},
     time::Duration,
 };
 
+#[cfg(feature = "xwayland")]
+use smithay::xwayland::X11Surface;
 use smithay::{
-    backend::renderer::{
-        element::{
-            solid::SolidColorRenderElement, surface::WaylandSurfaceRenderElement, AsRenderElements,
+    backend::{
+        input::TouchSlot,
+        renderer::{
+            element::{
+                solid::SolidColorRenderElement, surface::WaylandSurfaceRenderElement,
+                AsRenderElements,
+            },
+            ImportAll, ImportMem, Renderer,
         },
-        ImportAll, ImportMem, Renderer,
     },
-    desktop::{space::SpaceElement, Window, WindowSurfaceType},
+    desktop::{space::SpaceElement, Window, WindowSurface, WindowSurfaceType},
     output::Output,
-    reexports::wayland_server::{backend::ObjectId, protocol::wl_surface, Resource},
+    reexports::{
+        wayland_protocols::xdg::shell::server::xdg_toplevel, wayland_server::protocol::wl_surface,
+    },
     render_elements,
     utils::{user_data::UserDataMap, IsAlive, Logical, Physical, Point, Rectangle, Scale, Size},
     wayland::{
         compositor::{with_states, SurfaceData},
+        dmabuf::DmabufFeedback,
         seat::WaylandFocus,
         shell::xdg::{SurfaceCachedState, ToplevelSurface, XdgToplevelSurfaceData},
     },
 };
 
-use crate::handlers::{HeaderBar, HEADER_BAR_HEIGHT};
+use crate::handlers::{header_bar_height, HeaderBar};
+
+/// The ids handed out to windows, they are never reused
+static NEXT_WINDOW_ID: AtomicU64 = AtomicU64::new(1);
+
+/// The number a window is known by over IPC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WindowId(u64);
 
 #[derive(Debug, Default, Clone)]
 pub enum ElementState {
@@ -73,19 +91,19 @@ pub struct WindowState {
     pub is_ssd: bool,
     pub ptr_entered_window: bool,
     pub is_fixed_window: bool,
+    pub is_init: bool,
     pub element_state: ElementState,
-    pub output_size: Size<i32, Logical>,
-    pub element_size: Size<i32, Logical>,
-    pub origin_pos: Point<i32, Logical>,
-    pub pedding_size: Option<Size<i32, Logical>>,
     pub header_bar: HeaderBar,
     pub ssd_resize_state: SsdResizeState,
+    pub output: Option<Output>,
+    pub workspace: usize,
+    /// The touch points which went down on the header bar
+    pub header_touches: Vec<TouchSlot>,
 }
 
 #[derive(Debug, Clone)]
 pub struct WindowElement {
     window: Window,
-    is_init: bool,
 }
 
 impl PartialEq for WindowElement {
@@ -95,11 +113,8 @@ impl PartialEq for WindowElement {
 }
 
 impl WindowElement {
-    pub fn id(&self) -> ObjectId {
-        self.window.toplevel().unwrap().wl_surface().id()
-    }
     pub fn is_init(&self) -> bool {
-        self.is_init
+        self.window_state().is_init
     }
 
     pub fn to_untile_property_size(&self) -> Size<i32, Logical> {
@@ -109,36 +124,72 @@ impl WindowElement {
         self.geometry().size
     }
 
-    pub fn set_inited(&mut self) {
-        self.is_init = true;
+    pub fn set_inited(&self) {
+        self.window_state_mut().is_init = true;
     }
 
     #[allow(unused)]
     pub fn max_size(&self) -> Size<i32, Logical> {
-        with_states(self.toplevel().wl_surface(), |states| {
-            states.cached_state.pending::<SurfaceCachedState>().max_size
-        })
+        match self.window.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => with_states(toplevel.wl_surface(), |states| {
+                states.cached_state.pending::<SurfaceCachedState>().max_size
+            }),
+            #[cfg(feature = "xwayland")]
+            WindowSurface::X11(surface) => surface.max_size().unwrap_or_default(),
+        }
     }
 
-    #[allow(unused)]
     pub fn min_size(&self) -> Size<i32, Logical> {
-        with_states(self.toplevel().wl_surface(), |states| {
-            states.cached_state.pending::<SurfaceCachedState>().min_size
-        })
+        match self.window.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => with_states(toplevel.wl_surface(), |states| {
+                states.cached_state.pending::<SurfaceCachedState>().min_size
+            }),
+            #[cfg(feature = "xwayland")]
+            WindowSurface::X11(surface) => surface.min_size().unwrap_or_default(),
+        }
     }
 
-    #[allow(unused)]
     pub fn title(&self) -> Option<String> {
-        with_states(self.toplevel().wl_surface(), |states| {
-            states
-                .data_map
-                .get::<XdgToplevelSurfaceData>()
-                .unwrap()
-                .lock()
-                .unwrap()
-                .title
-                .clone()
-        })
+        match self.window.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => with_states(toplevel.wl_surface(), |states| {
+                states
+                    .data_map
+                    .get::<XdgToplevelSurfaceData>()
+                    .unwrap()
+                    .lock()
+                    .unwrap()
+                    .title
+                    .clone()
+            }),
+            #[cfg(feature = "xwayland")]
+            WindowSurface::X11(surface) => Some(surface.title()),
+        }
+    }
+
+    /// The app id of Wayland windows, X11 windows report their class
+    pub fn app_id(&self) -> Option<String> {
+        match self.window.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => with_states(toplevel.wl_surface(), |states| {
+                states
+                    .data_map
+                    .get::<XdgToplevelSurfaceData>()
+                    .unwrap()
+                    .lock()
+                    .unwrap()
+                    .app_id
+                    .clone()
+            }),
+            #[cfg(feature = "xwayland")]
+            WindowSurface::X11(surface) => Some(surface.class()),
+        }
+    }
+
+    /// The id of the window for IPC clients
+    pub fn ipc_id(&self) -> u64 {
+        self.user_data()
+            .get::<WindowId>()
+            .map(|id| id.0)
+            .unwrap_or_default()
     }
 
     pub fn user_data(&self) -> &UserDataMap {
@@ -173,6 +224,10 @@ impl WindowElement {
         self.window_state_mut().is_ssd = ssd
     }
 
+    pub fn element_state(&self) -> ElementState {
+        self.window_state().element_state.clone()
+    }
+
     pub fn is_untiled_window(&self) -> bool {
         self.window_state().element_state.is_untiled_state()
     }
@@ -201,42 +256,27 @@ impl WindowElement {
         self.window_state_mut().element_state = ElementState::Untile;
     }
 
-    pub fn output_size(&self) -> Size<i32, Logical> {
-        self.window_state().output_size
-    }
-
-    pub fn element_size(&self) -> Size<i32, Logical> {
-        self.window_state().element_size
-    }
-
-    pub fn origin_pos(&self) -> Point<i32, Logical> {
-        self.window_state().origin_pos
+    /// The output this window is tiled on
+    pub fn output(&self) -> Option<Output> {
+        self.window_state().output.clone()
     }
 
-    pub fn set_output_size(&self, size: Size<i32, Logical>) {
-        self.window_state_mut().output_size = size;
+    pub fn set_output(&self, output: Option<Output>) {
+        self.window_state_mut().output = output;
     }
 
-    pub fn set_element_size(&self, size: Size<i32, Logical>) {
-        self.window_state_mut().element_size = size;
+    /// The workspace of its output this window is on
+    pub fn workspace(&self) -> usize {
+        self.window_state().workspace
     }
 
-    pub fn has_pedding_size(&self) -> bool {
-        self.window_state_mut().pedding_size.is_some()
+    pub fn set_workspace(&self, workspace: usize) {
+        self.window_state_mut().workspace = workspace;
     }
 
-    pub fn set_pedding_size(&self, pedding_size: Option<Size<i32, Logical>>) {
-        self.window_state_mut().pedding_size = pedding_size;
-    }
-
-    pub fn get_pedding_size(&self) -> Size<i32, Logical> {
-        self.window_state()
-            .pedding_size
-            .unwrap_or(self.geometry().size)
-    }
-
-    pub fn set_origin_pos(&self, point: Point<i32, Logical>) {
-        self.window_state_mut().origin_pos = point
+    /// Draw the header bar anew with the current theme
+    pub fn invalidate_header_bar(&self) {
+        self.window_state_mut().header_bar.invalidate();
     }
 
     #[allow(unused)]
@@ -252,14 +292,96 @@ impl WindowElement {
 
 impl WindowElement {
     pub fn new(surface: ToplevelSurface) -> Self {
-        WindowElement {
-            window: Window::new_wayland_window(surface),
-            is_init: false,
+        Self::from_window(Window::new_wayland_window(surface))
+    }
+
+    #[cfg(feature = "xwayland")]
+    pub fn new_x11(surface: X11Surface) -> Self {
+        Self::from_window(Window::new_x11_window(surface))
+    }
+
+    fn from_window(window: Window) -> Self {
+        window
+            .user_data()
+            .insert_if_missing(|| WindowId(NEXT_WINDOW_ID.fetch_add(1, Ordering::Relaxed)));
+        WindowElement { window }
+    }
+
+    /// The xdg toplevel of a Wayland window, none for X11 windows
+    pub fn toplevel(&self) -> Option<&ToplevelSurface> {
+        self.window.toplevel()
+    }
+
+    #[cfg(feature = "xwayland")]
+    pub fn x11_surface(&self) -> Option<&X11Surface> {
+        self.window.x11_surface()
+    }
+
+    /// Whether `surface` is the main surface of this window
+    pub fn has_surface(&self, surface: &wl_surface::WlSurface) -> bool {
+        self.wl_surface().as_ref() == Some(surface)
+    }
+
+    pub fn is_activated(&self) -> bool {
+        match self.window.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => toplevel
+                .current_state()
+                .states
+                .contains(xdg_toplevel::State::Activated),
+            #[cfg(feature = "xwayland")]
+            WindowSurface::X11(surface) => surface.is_activated(),
         }
     }
 
-    pub fn toplevel(&self) -> &ToplevelSurface {
-        self.window.toplevel().as_ref().unwrap()
+    /// Send the state changed on the toplevel, X11 windows are configured
+    /// right away and have nothing pending
+    pub fn send_pending_configure(&self) {
+        if let Some(toplevel) = self.toplevel() {
+            toplevel.send_pending_configure();
+        }
+    }
+
+    /// Give the window the size of `geometry`, X11 windows are told where
+    /// they are as well
+    pub fn configure(&self, geometry: Rectangle<i32, Logical>) {
+        match self.window.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => {
+                toplevel.with_pending_state(|state| state.size = Some(geometry.size));
+                toplevel.send_pending_configure();
+            }
+            #[cfg(feature = "xwayland")]
+            WindowSurface::X11(surface) => {
+                if let Err(err) = surface.configure(geometry) {
+                    tracing::warn!("Failed to configure the X11 window: {}", err);
+                }
+            }
+        }
+    }
+
+    /// Tell X11 windows where they have been mapped, so that their popups
+    /// show up next to them. Wayland windows do not know their position.
+    pub fn set_location(&self, location: Point<i32, Logical>) {
+        #[cfg(feature = "xwayland")]
+        if let Some(surface) = self.x11_surface() {
+            let geometry = Rectangle::from_loc_and_size(location, surface.geometry().size);
+            if let Err(err) = surface.configure(geometry) {
+                tracing::warn!("Failed to configure the X11 window: {}", err);
+            }
+        }
+        #[cfg(not(feature = "xwayland"))]
+        let _ = location;
+    }
+
+    pub fn send_close(&self) {
+        match self.window.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => toplevel.send_close(),
+            #[cfg(feature = "xwayland")]
+            WindowSurface::X11(surface) => {
+                if let Err(err) = surface.close() {
+                    tracing::warn!("Failed to close the X11 window: {}", err);
+                }
+            }
+        }
     }
 
     pub fn surface_under<P>(
@@ -294,7 +416,27 @@ impl WindowElement {
         self.window
             .send_frame(output, time, throttle, primary_scan_out_output)
     }
-    #[allow(unused)]
+
+    pub fn send_dmabuf_feedback<'a, P, F>(
+        &self,
+        output: &Output,
+        primary_scan_out_output: P,
+        select_dmabuf_feedback: F,
+    ) where
+        P: FnMut(&wl_surface::WlSurface, &SurfaceData) -> Option<Output> + Copy,
+        F: Fn(&wl_surface::WlSurface, &SurfaceData) -> &'a DmabufFeedback + Copy,
+    {
+        self.window
+            .send_dmabuf_feedback(output, primary_scan_out_output, select_dmabuf_feedback)
+    }
+
+    pub fn with_surfaces<F>(&self, processor: F)
+    where
+        F: FnMut(&wl_surface::WlSurface, &SurfaceData),
+    {
+        self.window.with_surfaces(processor)
+    }
+
     pub fn wl_surface(&self) -> Option<wl_surface::WlSurface> {
         self.window.wl_surface()
     }
@@ -310,7 +452,7 @@ impl SpaceElement for WindowElement {
     fn geometry(&self) -> Rectangle<i32, smithay::utils::Logical> {
         let mut geo = SpaceElement::geometry(&self.window);
         if self.window_state().is_ssd {
-            geo.size.h += HEADER_BAR_HEIGHT;
+            geo.size.h += header_bar_height();
         }
         geo
     }
@@ -318,7 +460,7 @@ impl SpaceElement for WindowElement {
     fn bbox(&self) -> Rectangle<i32, smithay::utils::Logical> {
         let mut bbox = SpaceElement::bbox(&self.window);
         if self.is_ssd() {
-            bbox.size.h += HEADER_BAR_HEIGHT;
+            bbox.size.h += header_bar_height();
         }
         bbox
     }
@@ -350,7 +492,7 @@ impl SpaceElement for WindowElement {
 
 impl WaylandFocus for WindowElement {
     fn wl_surface(&self) -> Option<wl_surface::WlSurface> {
-        Some(self.toplevel().wl_surface().clone())
+        self.window.wl_surface()
     }
 }
 
@@ -406,7 +548,7 @@ where
             alpha,
         );
 
-        location.y += (scale.y * HEADER_BAR_HEIGHT as f64) as i32;
+        location.y += (scale.y * header_bar_height() as f64) as i32;
 
         let window_elements = AsRenderElements::<R>::render_elements::<WindowRenderElement<R>>(
             &self.window,
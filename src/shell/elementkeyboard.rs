@@ -10,8 +10,14 @@ impl<BackendData: Backend + 'static> KeyboardTarget<SmallCageState<BackendData>>
         keys: Vec<smithay::input::keyboard::KeysymHandle<'_>>,
         serial: smithay::utils::Serial,
     ) {
-        let WindowSurface::Wayland(surface) = self.window.underlying_surface();
-        KeyboardTarget::enter(surface.wl_surface(), seat, data, keys, serial)
+        match self.window.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => {
+                KeyboardTarget::enter(toplevel.wl_surface(), seat, data, keys, serial)
+            }
+            // X11 windows are given the input focus of the X server as well
+            #[cfg(feature = "xwayland")]
+            WindowSurface::X11(surface) => KeyboardTarget::enter(surface, seat, data, keys, serial),
+        }
     }
     fn modifiers(
         &self,
@@ -20,8 +26,16 @@ impl<BackendData: Backend + 'static> KeyboardTarget<SmallCageState<BackendData>>
         modifiers: smithay::input::keyboard::ModifiersState,
         serial: smithay::utils::Serial,
     ) {
-        let WindowSurface::Wayland(surface) = self.window.underlying_surface();
-        KeyboardTarget::modifiers(surface.wl_surface(), seat, data, modifiers, serial)
+        match self.window.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => {
+                KeyboardTarget::modifiers(toplevel.wl_surface(), seat, data, modifiers, serial)
+            }
+            // X11 windows are given the input focus of the X server as well
+            #[cfg(feature = "xwayland")]
+            WindowSurface::X11(surface) => {
+                KeyboardTarget::modifiers(surface, seat, data, modifiers, serial)
+            }
+        }
     }
     fn leave(
         &self,
@@ -29,8 +43,14 @@ impl<BackendData: Backend + 'static> KeyboardTarget<SmallCageState<BackendData>>
         data: &mut SmallCageState<BackendData>,
         serial: smithay::utils::Serial,
     ) {
-        let WindowSurface::Wayland(surface) = self.window.underlying_surface();
-        KeyboardTarget::leave(surface.wl_surface(), seat, data, serial)
+        match self.window.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => {
+                KeyboardTarget::leave(toplevel.wl_surface(), seat, data, serial)
+            }
+            // X11 windows are given the input focus of the X server as well
+            #[cfg(feature = "xwayland")]
+            WindowSurface::X11(surface) => KeyboardTarget::leave(surface, seat, data, serial),
+        }
     }
     fn key(
         &self,
@@ -41,8 +61,16 @@ impl<BackendData: Backend + 'static> KeyboardTarget<SmallCageState<BackendData>>
         serial: smithay::utils::Serial,
         time: u32,
     ) {
-        let WindowSurface::Wayland(surface) = self.window.underlying_surface();
-        KeyboardTarget::key(surface.wl_surface(), seat, data, key, state, serial, time)
+        match self.window.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => {
+                KeyboardTarget::key(toplevel.wl_surface(), seat, data, key, state, serial, time)
+            }
+            // X11 windows are given the input focus of the X server as well
+            #[cfg(feature = "xwayland")]
+            WindowSurface::X11(surface) => {
+                KeyboardTarget::key(surface, seat, data, key, state, serial, time)
+            }
+        }
     }
     // add code here
 }
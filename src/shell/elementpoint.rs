@@ -1,7 +1,7 @@
 use super::{SsdResizeState, WindowElement};
 use crate::{
     grabs::{ResizeEdge, ResizeSurfaceGrab},
-    handlers::HEADER_BAR_HEIGHT,
+    handlers::header_bar_height,
     state::Backend,
     SmallCageState,
 };
@@ -13,13 +13,14 @@ use smithay::{
     },
 };
 use smithay::{
-    desktop::{space::SpaceElement, WindowSurface},
+    desktop::space::SpaceElement,
     input::{pointer::Focus, Seat},
     reexports::{
         wayland_protocols::xdg::shell::server::xdg_toplevel,
         wayland_server::{protocol::wl_surface::WlSurface, Resource},
     },
     utils::{Rectangle, Serial},
+    wayland::seat::WaylandFocus,
 };
 
 // NOTE: if enter, set state, and check position
@@ -32,10 +33,12 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
     ) {
         let (w, h) = self.geometry().size.into();
         let mut state = self.window_state_mut();
-        let WindowSurface::Wayland(toplevel) = self.window.underlying_surface();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
         if state.is_ssd {
             'resizeState: {
-                if event.location.y < 70. && event.location.y > HEADER_BAR_HEIGHT as f64 {
+                if event.location.y < 70. && event.location.y > header_bar_height() as f64 {
                     state.ssd_resize_state = SsdResizeState::Top;
                     break 'resizeState;
                 }
@@ -54,20 +57,20 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
                 state.ssd_resize_state = SsdResizeState::Nothing;
             }
 
-            if event.location.y < HEADER_BAR_HEIGHT as f64 {
+            if event.location.y < header_bar_height() as f64 {
                 state.header_bar.pointer_enter(event.location);
             } else {
                 state.header_bar.pointer_leave();
                 let mut event = event.clone();
-                event.location.y -= HEADER_BAR_HEIGHT as f64;
+                event.location.y -= header_bar_height() as f64;
 
-                PointerTarget::enter(toplevel.wl_surface(), seat, data, &event);
+                PointerTarget::enter(&surface, seat, data, &event);
                 state.ptr_entered_window = true;
             }
             return;
         }
         state.ptr_entered_window = true;
-        PointerTarget::enter(toplevel.wl_surface(), seat, data, event)
+        PointerTarget::enter(&surface, seat, data, event)
     }
 
     fn motion(
@@ -78,10 +81,12 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
     ) {
         let (w, h) = self.geometry().size.into();
         let mut state = self.window_state_mut();
-        let WindowSurface::Wayland(surface) = self.window.underlying_surface();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
         if state.is_ssd {
             'resizeState: {
-                if event.location.y < 70. && event.location.y > HEADER_BAR_HEIGHT as f64 {
+                if event.location.y < 70. && event.location.y > header_bar_height() as f64 {
                     state.ssd_resize_state = SsdResizeState::Top;
                     break 'resizeState;
                 }
@@ -99,8 +104,8 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
                 }
                 state.ssd_resize_state = SsdResizeState::Nothing;
             }
-            if event.location.y < HEADER_BAR_HEIGHT as f64 {
-                PointerTarget::motion(surface.wl_surface(), seat, data, event);
+            if event.location.y < header_bar_height() as f64 {
+                PointerTarget::motion(&surface, seat, data, event);
 
                 state.ptr_entered_window = false;
                 state.header_bar.pointer_enter(event.location);
@@ -108,12 +113,12 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
                 state.ptr_entered_window = true;
                 state.header_bar.pointer_leave();
                 let mut event = event.clone();
-                event.location.y -= HEADER_BAR_HEIGHT as f64;
-                PointerTarget::motion(surface.wl_surface(), seat, data, &event);
+                event.location.y -= header_bar_height() as f64;
+                PointerTarget::motion(&surface, seat, data, &event);
             }
             return;
         }
-        PointerTarget::motion(surface.wl_surface(), seat, data, event);
+        PointerTarget::motion(&surface, seat, data, event);
     }
 
     fn leave(
@@ -124,16 +129,18 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
         time: u32,
     ) {
         let mut state = self.window_state_mut();
-        let WindowSurface::Wayland(surface) = self.window.underlying_surface();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
         if state.is_ssd {
             state.ssd_resize_state = SsdResizeState::Nothing;
             state.header_bar.pointer_leave();
             if state.ptr_entered_window {
-                PointerTarget::leave(surface.wl_surface(), seat, data, serial, time);
+                PointerTarget::leave(&surface, seat, data, serial, time);
                 state.ptr_entered_window = false
             }
         } else {
-            PointerTarget::leave(surface.wl_surface(), seat, data, serial, time);
+            PointerTarget::leave(&surface, seat, data, serial, time);
             state.ptr_entered_window = false;
         }
     }
@@ -145,7 +152,9 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
         event: &smithay::input::pointer::ButtonEvent,
     ) {
         let mut state = self.window_state_mut();
-        let WindowSurface::Wayland(surface) = self.window.underlying_surface();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
         if state.is_ssd {
             let ssd_resize_state = state.ssd_resize_state;
             let serial = event.serial;
@@ -160,15 +169,16 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
                         SsdResizeState::Bottom => ResizeEdge::BOTTOM,
                         _ => return,
                     };
+                    let Some(top_level) = window.toplevel() else {
+                        return;
+                    };
                     let seat = &state.seat;
-                    let Some(start_data) = check_grab(seat, window.toplevel().wl_surface(), serial)
-                    else {
+                    let Some(start_data) = check_grab(seat, top_level.wl_surface(), serial) else {
                         return;
                     };
                     let pointer = state.seat.get_pointer().unwrap();
                     let initial_window_location = state.space.element_location(&window).unwrap();
                     let initial_window_size = window.geometry().size;
-                    let top_level = window.toplevel();
                     top_level.with_pending_state(|state| {
                         state.states.set(xdg_toplevel::State::Resizing);
                     });
@@ -183,14 +193,14 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
                 });
             }
             if state.ptr_entered_window {
-                PointerTarget::button(surface.wl_surface(), seat, data, event);
+                PointerTarget::button(&surface, seat, data, event);
             } else if event.state == ButtonState::Pressed {
                 state.header_bar.clicked(seat, data, self, event.serial)
             }
             return;
         }
 
-        PointerTarget::button(surface.wl_surface(), seat, data, event);
+        PointerTarget::button(&surface, seat, data, event);
     }
 
     fn relative_motion(
@@ -200,9 +210,11 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
         event: &smithay::input::pointer::RelativeMotionEvent,
     ) {
         let state = self.window_state();
-        let WindowSurface::Wayland(surface) = self.window.underlying_surface();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
         if !state.is_ssd || state.ptr_entered_window {
-            PointerTarget::relative_motion(surface.wl_surface(), seat, data, event);
+            PointerTarget::relative_motion(&surface, seat, data, event);
         }
     }
 
@@ -213,9 +225,11 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
         event: &smithay::input::pointer::GestureHoldEndEvent,
     ) {
         let state = self.window_state();
-        let WindowSurface::Wayland(surface) = self.window.underlying_surface();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
         if !state.is_ssd || state.ptr_entered_window {
-            PointerTarget::gesture_hold_end(surface.wl_surface(), seat, data, event);
+            PointerTarget::gesture_hold_end(&surface, seat, data, event);
         }
     }
 
@@ -226,9 +240,11 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
         event: &smithay::input::pointer::GestureSwipeEndEvent,
     ) {
         let state = self.window_state();
-        let WindowSurface::Wayland(surface) = self.window.underlying_surface();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
         if !state.is_ssd || state.ptr_entered_window {
-            PointerTarget::gesture_swipe_end(surface.wl_surface(), seat, data, event);
+            PointerTarget::gesture_swipe_end(&surface, seat, data, event);
         }
     }
 
@@ -239,9 +255,11 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
         event: &smithay::input::pointer::GestureSwipeBeginEvent,
     ) {
         let state = self.window_state();
-        let WindowSurface::Wayland(surface) = self.window.underlying_surface();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
         if !state.is_ssd || state.ptr_entered_window {
-            PointerTarget::gesture_swipe_begin(surface.wl_surface(), seat, data, event);
+            PointerTarget::gesture_swipe_begin(&surface, seat, data, event);
         }
     }
 
@@ -252,9 +270,11 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
         event: &smithay::input::pointer::GestureHoldBeginEvent,
     ) {
         let state = self.window_state();
-        let WindowSurface::Wayland(surface) = self.window.underlying_surface();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
         if !state.is_ssd || state.ptr_entered_window {
-            PointerTarget::gesture_hold_begin(surface.wl_surface(), seat, data, event);
+            PointerTarget::gesture_hold_begin(&surface, seat, data, event);
         }
     }
 
@@ -265,9 +285,11 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
         event: &smithay::input::pointer::GesturePinchEndEvent,
     ) {
         let state = self.window_state();
-        let WindowSurface::Wayland(surface) = self.window.underlying_surface();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
         if !state.is_ssd || state.ptr_entered_window {
-            PointerTarget::gesture_pinch_end(surface.wl_surface(), seat, data, event);
+            PointerTarget::gesture_pinch_end(&surface, seat, data, event);
         }
     }
 
@@ -278,9 +300,11 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
         event: &smithay::input::pointer::GestureSwipeUpdateEvent,
     ) {
         let state = self.window_state();
-        let WindowSurface::Wayland(surface) = self.window.underlying_surface();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
         if !state.is_ssd || state.ptr_entered_window {
-            PointerTarget::gesture_swipe_update(surface.wl_surface(), seat, data, event);
+            PointerTarget::gesture_swipe_update(&surface, seat, data, event);
         }
     }
 
@@ -291,9 +315,11 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
         event: &smithay::input::pointer::GesturePinchUpdateEvent,
     ) {
         let state = self.window_state();
-        let WindowSurface::Wayland(surface) = self.window.underlying_surface();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
         if !state.is_ssd || state.ptr_entered_window {
-            PointerTarget::gesture_pinch_update(surface.wl_surface(), seat, data, event);
+            PointerTarget::gesture_pinch_update(&surface, seat, data, event);
         }
     }
 
@@ -304,9 +330,11 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
         event: &smithay::input::pointer::GesturePinchBeginEvent,
     ) {
         let state = self.window_state();
-        let WindowSurface::Wayland(surface) = self.window.underlying_surface();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
         if !state.is_ssd || state.ptr_entered_window {
-            PointerTarget::gesture_pinch_begin(surface.wl_surface(), seat, data, event);
+            PointerTarget::gesture_pinch_begin(&surface, seat, data, event);
         }
     }
 
@@ -316,9 +344,11 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
         data: &mut SmallCageState<BackendData>,
     ) {
         let state = self.window_state();
-        let WindowSurface::Wayland(surface) = self.window.underlying_surface();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
         if !state.is_ssd || state.ptr_entered_window {
-            PointerTarget::frame(surface.wl_surface(), seat, data);
+            PointerTarget::frame(&surface, seat, data);
         }
     }
 
@@ -329,9 +359,11 @@ impl<BackendData: Backend + 'static> PointerTarget<SmallCageState<BackendData>>
         frame: smithay::input::pointer::AxisFrame,
     ) {
         let state = self.window_state();
-        let WindowSurface::Wayland(surface) = self.window.underlying_surface();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
         if !state.is_ssd || state.ptr_entered_window {
-            PointerTarget::axis(surface.wl_surface(), seat, data, frame);
+            PointerTarget::axis(&surface, seat, data, frame);
         }
     }
 }
@@ -352,7 +384,7 @@ fn check_grab<BackendData: Backend + 'static>(
 
     let (focus, _) = start_data.focus.as_ref()?;
     // If the focus was for a different surface, ignore the request.
-    if !focus.id().same_client_as(&surface.id()) {
+    if !focus.wl_surface()?.id().same_client_as(&surface.id()) {
         return None;
     }
 
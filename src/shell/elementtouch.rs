@@ -0,0 +1,133 @@
+use super::WindowElement;
+use crate::{handlers::header_bar_height, state::Backend, SmallCageState};
+use smithay::{
+    input::{
+        touch::{DownEvent, MotionEvent, OrientationEvent, ShapeEvent, TouchTarget, UpEvent},
+        Seat,
+    },
+    utils::Serial,
+};
+
+// NOTE: touch points which go down on the header bar of a ssd window stay
+// with the header bar, the client never hears of them
+impl<BackendData: Backend + 'static> TouchTarget<SmallCageState<BackendData>> for WindowElement {
+    fn down(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &DownEvent,
+        seq: Serial,
+    ) {
+        let mut state = self.window_state_mut();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
+        if !state.is_ssd {
+            TouchTarget::down(&surface, seat, data, event, seq);
+            return;
+        }
+        if event.location.y < header_bar_height() as f64 {
+            // a tap on the header bar works like a click on it
+            state.header_touches.push(event.slot);
+            state.header_bar.pointer_enter(event.location);
+            state.header_bar.clicked(seat, data, self, event.serial);
+            state.header_bar.pointer_leave();
+            return;
+        }
+        let mut event = event.clone();
+        event.location.y -= header_bar_height() as f64;
+        TouchTarget::down(&surface, seat, data, &event, seq);
+    }
+
+    fn up(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &UpEvent,
+        seq: Serial,
+    ) {
+        let mut state = self.window_state_mut();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
+        if let Some(index) = state.header_touches.iter().position(|s| *s == event.slot) {
+            state.header_touches.remove(index);
+            return;
+        }
+        TouchTarget::up(&surface, seat, data, event, seq);
+    }
+
+    fn motion(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &MotionEvent,
+        seq: Serial,
+    ) {
+        let state = self.window_state();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
+        if state.header_touches.contains(&event.slot) {
+            return;
+        }
+        if state.is_ssd {
+            let mut event = event.clone();
+            event.location.y -= header_bar_height() as f64;
+            TouchTarget::motion(&surface, seat, data, &event, seq);
+            return;
+        }
+        TouchTarget::motion(&surface, seat, data, event, seq);
+    }
+
+    fn frame(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        seq: Serial,
+    ) {
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
+        TouchTarget::frame(&surface, seat, data, seq);
+    }
+
+    fn cancel(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        seq: Serial,
+    ) {
+        self.window_state_mut().header_touches.clear();
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
+        TouchTarget::cancel(&surface, seat, data, seq);
+    }
+
+    fn shape(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &ShapeEvent,
+        seq: Serial,
+    ) {
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
+        TouchTarget::shape(&surface, seat, data, event, seq);
+    }
+
+    fn orientation(
+        &self,
+        seat: &Seat<SmallCageState<BackendData>>,
+        data: &mut SmallCageState<BackendData>,
+        event: &OrientationEvent,
+        seq: Serial,
+    ) {
+        let Some(surface) = self.wl_surface() else {
+            return;
+        };
+        TouchTarget::orientation(&surface, seat, data, event, seq);
+    }
+}
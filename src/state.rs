@@ -1,18 +1,34 @@
 use std::{
+    cell::{RefCell, RefMut},
+    collections::HashSet,
     ffi::OsString,
     sync::{atomic::AtomicBool, Arc, Mutex},
 };
 
+use crate::config::{config, watch_config};
+use crate::focus::FocusTarget;
+use crate::foreign_toplevel::ForeignToplevelList;
+use crate::gesture::Gesture;
+use crate::ipc::IpcState;
+use crate::keyboard::add_keyboard;
+use crate::screencopy::fail_screencopies;
+use crate::screenshot::RegionSelection;
 use crate::shell::WindowElement;
+use crate::workspace::active_workspace;
 use crate::CalloopData;
 use smithay::{
-    delegate_input_method_manager, delegate_text_input_manager, delegate_virtual_keyboard_manager,
-    delegate_xdg_activation, delegate_xdg_decoration,
-    desktop::{space::SpaceElement, PopupKind, PopupManager, Space, WindowSurfaceType},
+    delegate_fractional_scale, delegate_input_method_manager, delegate_pointer_gestures,
+    delegate_relative_pointer, delegate_text_input_manager, delegate_viewporter,
+    delegate_virtual_keyboard_manager, delegate_xdg_activation, delegate_xdg_decoration,
+    desktop::{
+        layer_map_for_output, space::SpaceElement, PopupKind, PopupManager, Space,
+        WindowSurfaceType,
+    },
     input::{
-        pointer::{CursorImageStatus, PointerHandle},
+        pointer::{CursorImageStatus, MotionEvent, PointerHandle},
         Seat, SeatState,
     },
+    output::{Output, Scale as OutputScale},
     reexports::{
         calloop::{
             generic::Generic, EventLoop, Interest, LoopHandle, LoopSignal, Mode, PostAction,
@@ -28,12 +44,19 @@ use smithay::{
             Display, DisplayHandle, Resource,
         },
     },
-    utils::{Logical, Physical, Point, Rectangle, Size},
+    utils::{Logical, Point, Rectangle, Transform, SERIAL_COUNTER},
     wayland::{
-        compositor::{with_states, CompositorClientState, CompositorState},
+        compositor::{get_parent, with_states, CompositorClientState, CompositorState},
+        fractional_scale::{
+            with_fractional_scale, FractionalScaleHandler, FractionalScaleManagerState,
+        },
         input_method::{InputMethodHandler, InputMethodManagerState, PopupSurface},
         output::OutputManagerState,
+        pointer_constraints::PointerConstraintsState,
+        pointer_gestures::PointerGesturesState,
+        relative_pointer::RelativePointerManagerState,
         selection::{data_device::DataDeviceState, primary_selection::PrimarySelectionState},
+        shell::wlr_layer::{Layer, WlrLayerShellState},
         shell::xdg::{
             decoration::{XdgDecorationHandler, XdgDecorationState},
             ToplevelSurface, XdgShellState, XdgToplevelSurfaceData,
@@ -41,12 +64,71 @@ use smithay::{
         shm::ShmState,
         socket::ListeningSocketSource,
         text_input::TextInputManagerState,
+        viewporter::ViewporterState,
         virtual_keyboard::VirtualKeyboardManagerState,
         xdg_activation::{XdgActivationHandler, XdgActivationState},
     },
 };
 
-#[derive(Debug, Default, Clone, Copy)]
+#[cfg(feature = "xwayland")]
+use crate::xwayland::XDisplay;
+#[cfg(feature = "xwayland")]
+use smithay::xwayland::X11Wm;
+
+/// How much ScaleUp and ScaleDown change the scale of an output
+pub const SCALE_STEP: f64 = 0.25;
+const MIN_SCALE: f64 = 0.5;
+const MAX_SCALE: f64 = 4.0;
+
+/// The transform an output needs to show upright, kept in its user data.
+///
+/// RotateOutput turns the output away from it, outputs without one start
+/// with [`Transform::Normal`].
+#[derive(Debug, Clone, Copy)]
+pub struct BaseTransform(pub Transform);
+
+/// The next transform a quarter turn further, flipped ones stay flipped
+fn next_rotation(transform: Transform) -> Transform {
+    match transform {
+        Transform::Normal => Transform::_90,
+        Transform::_90 => Transform::_180,
+        Transform::_180 => Transform::_270,
+        Transform::_270 => Transform::Normal,
+        Transform::Flipped => Transform::Flipped90,
+        Transform::Flipped90 => Transform::Flipped180,
+        Transform::Flipped180 => Transform::Flipped270,
+        Transform::Flipped270 => Transform::Flipped,
+    }
+}
+
+/// How far the output is rotated from its [`BaseTransform`]
+pub fn output_rotation(output: &Output) -> Transform {
+    let current = output.current_transform();
+    let mut transform = output
+        .user_data()
+        .get::<BaseTransform>()
+        .map(|base| base.0)
+        .unwrap_or(Transform::Normal);
+    let mut rotation = Transform::Normal;
+    for _ in 0..4 {
+        if transform == current {
+            break;
+        }
+        transform = next_rotation(transform);
+        rotation = next_rotation(rotation);
+    }
+    rotation
+}
+
+/// The state of type `T` kept in the user data of `output`, it starts out
+/// as the default the first time it is asked for
+pub fn output_state<T: Default + 'static>(output: &Output) -> RefMut<'_, T> {
+    let user_data = output.user_data();
+    user_data.insert_if_missing(|| RefCell::new(T::default()));
+    user_data.get::<RefCell<T>>().unwrap().borrow_mut()
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum SplitState {
     #[default]
     HSplit,
@@ -57,6 +139,8 @@ pub struct SmallCageState<BackendData: Backend + 'static> {
     pub backend_data: BackendData,
     pub start_time: std::time::Instant,
     pub socket_name: OsString,
+    /// `None` if the IPC socket could not be set up
+    pub ipc: Option<IpcState>,
 
     pub display_handle: DisplayHandle,
 
@@ -74,6 +158,7 @@ pub struct SmallCageState<BackendData: Backend + 'static> {
     pub data_device_state: DataDeviceState,
     pub xdg_activation_state: XdgActivationState,
     pub xdg_decoration_state: XdgDecorationState,
+    pub layer_shell_state: WlrLayerShellState,
     pub cursor_status: Arc<Mutex<CursorImageStatus>>,
 
     pub seat: Seat<Self>,
@@ -82,6 +167,28 @@ pub struct SmallCageState<BackendData: Backend + 'static> {
     pub handle: LoopHandle<'static, CalloopData<BackendData>>,
 
     pub splitstate: SplitState,
+    /// The overview of the windows is shown instead of the windows
+    pub show_window_preview: bool,
+    /// The keys whose press was taken by smallcage, their release does not
+    /// reach the clients either
+    pub intercepted_keys: HashSet<u32>,
+    /// The touchpad gesture in progress
+    pub gesture: Option<Gesture>,
+    /// Tint the regions which are redrawn
+    pub show_damage_tint: bool,
+    /// The region of a screenshot is being selected with the pointer
+    pub region_selection: Option<RegionSelection>,
+    /// The windows of the last output which was unplugged, with the
+    /// workspace they were on, until an output is connected again
+    pub orphaned_windows: Vec<(usize, WindowElement)>,
+    /// The windows as the clients of `ext_foreign_toplevel_list_v1` know them
+    pub foreign_toplevel_list: ForeignToplevelList,
+    /// The window manager of XWayland, once the X server is ready
+    #[cfg(feature = "xwayland")]
+    pub xwm: Option<X11Wm>,
+    /// The X display XWayland is spawned on, reserved at startup
+    #[cfg(feature = "xwayland")]
+    pub xdisplay: Option<XDisplay>,
 
     pub running: Arc<AtomicBool>,
 }
@@ -110,23 +217,31 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
 
         let xdg_activation_state = XdgActivationState::new::<Self>(&dh);
         let xdg_decoration_state = XdgDecorationState::new::<Self>(&dh);
+        let layer_shell_state = WlrLayerShellState::new::<Self>(&dh);
 
         TextInputManagerState::new::<Self>(&dh);
         InputMethodManagerState::new::<Self, _>(&dh, |_| true);
         VirtualKeyboardManagerState::new::<Self, _>(&dh, |_| true);
+        // let clients render at the fractional scale of their output
+        FractionalScaleManagerState::new::<Self>(&dh);
+        ViewporterState::new::<Self>(&dh);
+        RelativePointerManagerState::new::<Self>(&dh);
+        PointerConstraintsState::new::<Self>(&dh);
+        PointerGesturesState::new::<Self>(&dh);
+        crate::screencopy::init_screencopy::<BackendData>(&dh);
+        crate::image_capture::init_image_capture::<BackendData>(&dh);
+        crate::foreign_toplevel::init_foreign_toplevel_list::<BackendData>(&dh);
         let cursor_status = Arc::new(Mutex::new(CursorImageStatus::default_named()));
 
         // A seat is a group of keyboards, pointer and touch devices.
         // A seat typically has a pointer and maintains a keyboard focus and a pointer focus.
         let mut seat: Seat<Self> = seat_state.new_wl_seat(&dh, "winit");
 
-        // Notify clients that we have a keyboard, for the sake of the example we assume that keyboard is always present.
-        // You may want to track keyboard hot-plug in real compositor.
-        seat.add_keyboard(Default::default(), 200, 200).unwrap();
-
-        // Notify clients that we have a pointer (mouse)
-        // Here we assume that there is always pointer plugged in
+        // Notify clients that we have a keyboard, a pointer and a touchscreen. The
+        // udev backend takes them away again while no such device is plugged in.
+        add_keyboard(&mut seat);
         let pointer = seat.add_pointer();
+        seat.add_touch();
 
         // A space represents a two-dimensional plane. Windows and Outputs can be mapped onto it.
         //
@@ -139,6 +254,16 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
         // Get the loop signal, used to stop the event loop
         let loop_signal = event_loop.get_signal();
 
+        watch_config(&event_loop.handle());
+
+        let ipc = match IpcState::new(&event_loop.handle(), &socket_name) {
+            Ok(ipc) => Some(ipc),
+            Err(err) => {
+                tracing::warn!("Failed to start the IPC socket: {}", err);
+                None
+            }
+        };
+
         Self {
             backend_data: data,
             start_time,
@@ -150,6 +275,7 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
             loop_signal,
 
             socket_name,
+            ipc,
 
             compositor_state,
             xdg_shell_state,
@@ -160,6 +286,7 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
             data_device_state,
             xdg_activation_state,
             xdg_decoration_state,
+            layer_shell_state,
             cursor_status,
 
             seat,
@@ -167,7 +294,18 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
 
             handle: event_loop.handle(),
 
-            splitstate: SplitState::default(),
+            splitstate: config().default_split,
+            show_window_preview: false,
+            intercepted_keys: HashSet::new(),
+            gesture: None,
+            show_damage_tint: false,
+            region_selection: None,
+            orphaned_windows: Vec::new(),
+            foreign_toplevel_list: ForeignToplevelList::default(),
+            #[cfg(feature = "xwayland")]
+            xwm: None,
+            #[cfg(feature = "xwayland")]
+            xdisplay: None,
             running: Arc::new(AtomicBool::new(true)),
         }
     }
@@ -221,8 +359,48 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
     pub fn surface_under_pointer(
         &self,
         pointer: &PointerHandle<Self>,
+    ) -> Option<(FocusTarget, Point<i32, Logical>)> {
+        self.surface_under(pointer.current_location())
+    }
+
+    /// What takes the pointer at `pos`, with its location. Panels and
+    /// overlays are above the windows, wallpapers below them.
+    pub fn surface_under(
+        &self,
+        pos: Point<f64, Logical>,
+    ) -> Option<(FocusTarget, Point<i32, Logical>)> {
+        let window_under = || {
+            self.window_under(pos)
+                .map(|(window, location)| (window.into(), location))
+        };
+        let Some(output) = self.space.output_under(pos).next() else {
+            return window_under();
+        };
+        let output_geo = self.space.output_geometry(output)?;
+        let layers = layer_map_for_output(output);
+        let layer_under = |layer| {
+            let pos = pos - output_geo.loc.to_f64();
+            let surface = layers.layer_under(layer, pos)?;
+            let location = layers.layer_geometry(surface)?.loc;
+            surface
+                .surface_under(
+                    pos - location.to_f64(),
+                    WindowSurfaceType::TOPLEVEL | WindowSurfaceType::SUBSURFACE,
+                )
+                .map(|_| (surface.clone().into(), location + output_geo.loc))
+        };
+        layer_under(Layer::Overlay)
+            .or_else(|| layer_under(Layer::Top))
+            .or_else(window_under)
+            .or_else(|| layer_under(Layer::Bottom))
+            .or_else(|| layer_under(Layer::Background))
+    }
+
+    /// The window at `pos` in the space, with its location
+    pub fn window_under(
+        &self,
+        pos: Point<f64, Logical>,
     ) -> Option<(WindowElement, Point<i32, Logical>)> {
-        let pos = pointer.current_location();
         self.space
             .element_under(pos)
             .and_then(|(window, location)| {
@@ -232,48 +410,275 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
             })
     }
 
-    // FIXME: it is not good enough
-    pub fn resize_elements(&mut self, after_size: Size<i32, Physical>) {
-        let after_w = after_size.w;
-        let after_h = after_size.h;
-        let windows: Vec<WindowElement> = self.space.elements().cloned().collect();
-        for winit in windows {
-            if winit
-                .toplevel()
-                .current_state()
-                .states
-                .contains(xdg_toplevel::State::Fullscreen)
-            {
-                self.full_screen_commit(winit.toplevel().wl_surface());
-                continue;
+    /// Run a command line, the arguments are split at whitespace
+    pub fn spawn(&self, command: &str) {
+        let mut args = command.split_whitespace();
+        let Some(program) = args.next() else {
+            return;
+        };
+        if let Err(e) = std::process::Command::new(program)
+            .args(args)
+            .env("WAYLAND_DISPLAY", self.socket_name.clone())
+            .spawn()
+        {
+            tracing::error!(cmd = command, err = %e, "Failed to start program");
+        }
+    }
+
+    /// Start the autostart commands of the config
+    pub fn run_autostart(&self) {
+        for command in config().autostart.iter() {
+            self.spawn(command);
+        }
+    }
+
+    /// The output the user is working on, this is the one under the pointer
+    pub fn current_output(&self) -> Option<Output> {
+        self.space
+            .output_under(self.pointer.current_location())
+            .next()
+            .or_else(|| self.space.outputs().next())
+            .cloned()
+    }
+
+    /// The area of an output which is given to tiled windows, what is left
+    /// by the exclusive zones of panels
+    pub fn tiling_area(&self, output: &Output) -> Option<Rectangle<i32, Logical>> {
+        let output_geo = self.space.output_geometry(output)?;
+        let mut area = layer_map_for_output(output).non_exclusive_zone();
+        area.loc += output_geo.loc;
+        Some(area)
+    }
+
+    /// The output a window belongs to, windows which have none yet or whose
+    /// output is gone go to the current one
+    pub fn window_output(&self, window: &WindowElement) -> Option<Output> {
+        window
+            .output()
+            .filter(|output| self.space.outputs().any(|o| o == output))
+            .or_else(|| self.current_output())
+    }
+
+    /// Lay the windows of an output out again after its size has changed
+    pub fn resize_elements(&mut self, output: &Output) {
+        layer_map_for_output(output).arrange();
+        self.relayout_output(output);
+        let fullscreen: Vec<WindowElement> = self
+            .space
+            .elements()
+            .filter(|w| {
+                w.output().as_ref() == Some(output)
+                    && w.toplevel().is_some_and(|toplevel| {
+                        toplevel
+                            .current_state()
+                            .states
+                            .contains(xdg_toplevel::State::Fullscreen)
+                    })
+            })
+            .cloned()
+            .collect();
+        for window in fullscreen {
+            self.full_screen_commit(&window);
+        }
+    }
+
+    /// Unmap an output which has gone away, the windows which were shown on it
+    /// are tiled again on one of the remaining outputs, or on the next one
+    /// connected if it was the last.
+    pub fn remove_output(&mut self, output: &Output) {
+        let orphans: Vec<WindowElement> = self.space.elements_for_output(output).cloned().collect();
+        let hidden = self.hidden_workspace_windows(output);
+        fail_screencopies(output);
+        self.move_image_captures(output);
+        for layer in layer_map_for_output(output).layers() {
+            layer.layer_surface().send_close();
+        }
+        self.space.unmap_output(output);
+
+        // nothing to move the windows to, they wait for the next output
+        let Some(target) = self.space.outputs().next().cloned() else {
+            let active = active_workspace(output);
+            for window in orphans.iter() {
+                self.space.unmap_elem(window);
+            }
+            // the workspaces of the output go away with it
+            for window in orphans.iter().chain(hidden.iter().map(|(_, w)| w)) {
+                window.set_output(None);
             }
-            if winit.is_untiled_window() {
-                continue;
+            self.orphaned_windows.extend(
+                orphans
+                    .into_iter()
+                    .map(|window| (active, window))
+                    .chain(hidden),
+            );
+            return;
+        };
+        let Some(target_geo) = self.space.output_geometry(&target) else {
+            return;
+        };
+
+        // the tiling follows the pointer, so bring it to the output which takes the windows
+        if self
+            .space
+            .output_under(self.pointer.current_location())
+            .next()
+            .is_none()
+        {
+            let location =
+                target_geo.loc + Point::from((target_geo.size.w / 2, target_geo.size.h / 2));
+            let serial = SERIAL_COUNTER.next_serial();
+            let pointer = self.pointer.clone();
+            pointer.motion(
+                self,
+                None,
+                &MotionEvent {
+                    location: location.to_f64(),
+                    serial,
+                    time: self.start_time.elapsed().as_millis() as u32,
+                },
+            );
+        }
+
+        for window in orphans.iter() {
+            window.set_output(Some(target.clone()));
+            self.space.unmap_elem(window);
+        }
+        for window in orphans.iter() {
+            if window.is_untiled_window() {
+                self.map_untitled_element(window);
+            } else {
+                self.resize_element_commit(window);
             }
-            let (origin_x, origin_y) = winit.origin_pos().into();
-            let (out_w, out_h) = winit.output_size().into();
-            let (w_w, w_h) = winit.element_size().into();
-            let newsize: Size<i32, Logical> = (w_w * after_w / out_w, w_h * after_h / out_h).into();
-            let newpoint: Point<i32, Logical> =
-                (after_w * origin_x / out_w, after_h * origin_y / out_h).into();
-            winit.toplevel().with_pending_state(|state| {
-                state.size = Some(newsize);
+        }
+        // the hidden workspaces keep their number on the new output
+        for (index, window) in hidden {
+            self.move_window_to_workspace(&window, &target, index);
+        }
+        self.raise_untiled_elements();
+    }
+
+    /// Move the windows left behind by the last unplugged output to `output`,
+    /// each on the workspace it was on
+    pub fn map_orphaned_windows(&mut self, output: &Output) {
+        for (index, window) in std::mem::take(&mut self.orphaned_windows) {
+            self.move_window_to_workspace(&window, output, index);
+        }
+    }
+
+    /// Change the scale of an output by `delta`, the tiled windows are laid
+    /// out again for the new logical size.
+    pub fn change_output_scale(&mut self, output: &Output, delta: f64) {
+        let current = output.current_scale().fractional_scale();
+        // stay on multiples of the step, so that the scale never drifts
+        let scale = ((current + delta) / SCALE_STEP).round() * SCALE_STEP;
+        let scale = scale.clamp(MIN_SCALE, MAX_SCALE);
+        if scale == current {
+            return;
+        }
+        tracing::info!(output = output.name(), scale, "Change output scale");
+        output.change_current_state(None, None, Some(OutputScale::Fractional(scale)), None);
+        self.send_output_scale(output);
+        self.arrange_outputs();
+    }
+
+    /// Turn an output by a quarter, the tiled windows are laid out again for
+    /// the rotated logical size.
+    pub fn rotate_output(&mut self, output: &Output) {
+        let transform = next_rotation(output.current_transform());
+        tracing::info!(output = output.name(), ?transform, "Rotate output");
+        output.change_current_state(None, Some(transform), None, None);
+        self.arrange_outputs();
+    }
+
+    /// Tell the windows and layer surfaces of an output about its scale
+    fn send_output_scale(&self, output: &Output) {
+        let scale = output.current_scale().fractional_scale();
+        let windows = self
+            .space
+            .elements()
+            .filter(|w| w.output().as_ref() == Some(output))
+            .cloned()
+            .chain(
+                self.hidden_workspace_windows(output)
+                    .into_iter()
+                    .map(|(_, w)| w),
+            );
+        for window in windows {
+            window.with_surfaces(|_, states| {
+                with_fractional_scale(states, |fractional_scale| {
+                    fractional_scale.set_preferred_scale(scale);
+                });
             });
-            winit.toplevel().send_configure();
-            self.space.map_element(winit, newpoint, false);
+        }
+        for layer in layer_map_for_output(output).layers() {
+            layer.with_surfaces(|_, states| {
+                with_fractional_scale(states, |fractional_scale| {
+                    fractional_scale.set_preferred_scale(scale);
+                });
+            });
+        }
+    }
+
+    /// Put the outputs next to each other again after the logical size of
+    /// one of them has changed, and lay out the windows of all of them.
+    pub fn arrange_outputs(&mut self) {
+        let mut outputs: Vec<(Output, Point<i32, Logical>)> = self
+            .space
+            .outputs()
+            .filter_map(|o| Some((o.clone(), self.space.output_geometry(o)?.loc)))
+            .collect();
+        outputs.sort_by_key(|(_, location)| location.x);
+
+        let mut x = 0;
+        for (output, old_location) in outputs {
+            let location = Point::from((x, 0));
+            if location != old_location {
+                // untiled windows travel with their output
+                let untiled: Vec<(WindowElement, Point<i32, Logical>)> = self
+                    .space
+                    .elements()
+                    .filter(|w| w.is_untiled_window() && w.output().as_ref() == Some(&output))
+                    .filter_map(|w| Some((w.clone(), self.space.element_location(w)?)))
+                    .collect();
+                output.change_current_state(None, None, None, Some(location));
+                self.space.map_output(&output, location);
+                for (window, window_location) in untiled {
+                    self.space.map_element(
+                        window,
+                        window_location - old_location + location,
+                        false,
+                    );
+                }
+            }
+            self.resize_elements(&output);
+            x += self
+                .space
+                .output_geometry(&output)
+                .map(|geo| geo.size.w)
+                .unwrap_or_default();
         }
         self.raise_untiled_elements();
     }
 
     // this should commit when full is here
     // TODO: very basic
-    fn full_screen_commit(&self, surface: &WlSurface) {
-        let output = self.space.outputs().next().unwrap();
-        let geometry = self.space.output_geometry(output).unwrap();
-        let window = self.space.elements().next().unwrap();
-        let toplevelsurface = window.toplevel();
+    fn full_screen_commit(&mut self, window: &WindowElement) {
+        let Some(output) = window.output() else {
+            return;
+        };
+        let Some(geometry) = self.space.output_geometry(&output) else {
+            return;
+        };
+        let Some(toplevelsurface) = window.toplevel() else {
+            return;
+        };
 
-        let client = self.display_handle.get_client(surface.id()).unwrap();
+        let Ok(client) = self
+            .display_handle
+            .get_client(toplevelsurface.wl_surface().id())
+        else {
+            return;
+        };
 
         let Some(wl_output) = output.client_outputs(&client).into_iter().next() else {
             return;
@@ -285,6 +690,7 @@ impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
             state.fullscreen_output = Some(wl_output);
         });
         toplevelsurface.send_configure();
+        self.space.map_element(window.clone(), geometry.loc, true);
     }
 }
 impl<BackendData: Backend + 'static> XdgActivationHandler for SmallCageState<BackendData> {
@@ -314,6 +720,8 @@ impl ClientData for ClientState {
 
 delegate_text_input_manager!(@<BackendData: Backend + 'static>SmallCageState<BackendData>);
 delegate_virtual_keyboard_manager!(@<BackendData: Backend + 'static>SmallCageState<BackendData>);
+delegate_relative_pointer!(@<BackendData: Backend + 'static>SmallCageState<BackendData>);
+delegate_pointer_gestures!(@<BackendData: Backend + 'static>SmallCageState<BackendData>);
 delegate_xdg_decoration!(@<BackendData: Backend + 'static>SmallCageState<BackendData>);
 impl<BackendData: Backend + 'static> XdgDecorationHandler for SmallCageState<BackendData> {
     fn new_decoration(&mut self, toplevel: ToplevelSurface) {
@@ -390,6 +798,46 @@ impl<BackendData: Backend + 'static> InputMethodHandler for SmallCageState<Backe
 }
 delegate_input_method_manager!(@<BackendData: Backend + 'static>SmallCageState<BackendData>);
 
+impl<BackendData: Backend + 'static> FractionalScaleHandler for SmallCageState<BackendData> {
+    fn new_fractional_scale(&mut self, surface: WlSurface) {
+        // use the output of the window or layer surface the surface belongs to
+        let mut root = surface.clone();
+        while let Some(parent) = get_parent(&root) {
+            root = parent;
+        }
+        let output = self
+            .find_window(&root)
+            .and_then(|window| self.window_output(&window))
+            .or_else(|| {
+                self.space
+                    .outputs()
+                    .find(|output| {
+                        layer_map_for_output(output)
+                            .layer_for_surface(&root, WindowSurfaceType::TOPLEVEL)
+                            .is_some()
+                    })
+                    .cloned()
+            })
+            .or_else(|| self.current_output());
+        let Some(output) = output else {
+            return;
+        };
+        with_states(&surface, |states| {
+            with_fractional_scale(states, |fractional_scale| {
+                fractional_scale.set_preferred_scale(output.current_scale().fractional_scale());
+            });
+        });
+    }
+}
+
+delegate_fractional_scale!(@<BackendData: Backend + 'static>SmallCageState<BackendData>);
+delegate_viewporter!(@<BackendData: Backend + 'static>SmallCageState<BackendData>);
+
 pub trait Backend {
     fn seat_name(&self) -> String;
+    fn change_vt(&mut self, vt: i32);
+    /// Drop the damage history, so that the next frames are drawn in full
+    fn reset_buffers(&mut self);
+    /// Apply the input settings of the config to the devices again
+    fn configure_input_devices(&mut self);
 }
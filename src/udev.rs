@@ -1,45 +1,300 @@
 use smithay::{
     backend::{
-        drm::{DrmDeviceFd, DrmNode, NodeType},
-        egl::context::ContextPriority,
+        allocator::{
+            dmabuf::Dmabuf,
+            gbm::{GbmAllocator, GbmBufferFlags, GbmDevice},
+            Fourcc,
+        },
+        drm::{
+            compositor::{DrmCompositor, PrimaryPlaneElement, RenderFrameError},
+            CreateDrmNodeError, DrmDevice, DrmDeviceFd, DrmError, DrmEvent, DrmNode, NodeType,
+        },
+        egl::{context::ContextPriority, EGLDevice, EGLDisplay},
         input::{Device, InputEvent},
         libinput::{LibinputInputBackend, LibinputSessionInterface},
         renderer::{
-            gles::GlesRenderer,
-            multigpu::{gbm::GbmGlesBackend, GpuManager},
-            ImportEgl, ImportMemWl,
+            damage::Error as OutputDamageTrackerError,
+            element::{
+                default_primary_scanout_output_compare, texture::TextureBuffer, AsRenderElements,
+                RenderElementStates,
+            },
+            gles::{GlesRenderer, GlesTexture},
+            multigpu::{gbm::GbmGlesBackend, GpuManager, MultiRenderer, MultiTexture},
+            ImportDma, ImportEgl, ImportMemWl,
         },
         session::{libseat::LibSeatSession, Event as SessionEvent, Session},
         udev::{all_gpus, primary_gpu, UdevBackend, UdevEvent},
+        SwapBuffersError,
+    },
+    delegate_dmabuf,
+    desktop::{
+        layer_map_for_output,
+        utils::{
+            select_dmabuf_feedback, surface_primary_scanout_output, update_primary_scanout_output,
+        },
+        Space,
     },
+    input::pointer::{CursorImageAttributes, CursorImageStatus},
+    output::{Mode as WlMode, Output, PhysicalProperties, Subpixel},
     reexports::{
-        calloop::EventLoop,
-        input::{DeviceCapability, Libinput},
-        wayland_server::{Display, DisplayHandle},
+        calloop::{
+            timer::{TimeoutAction, Timer},
+            EventLoop, RegistrationToken,
+        },
+        drm::control::{connector, crtc, ModeTypeFlags},
+        input::{self, DeviceCapability, Libinput},
+        rustix::fs::OFlags,
+        wayland_protocols::wp::linux_dmabuf::zv1::server::zwp_linux_dmabuf_feedback_v1,
+        wayland_server::{backend::GlobalId, Display, DisplayHandle},
+    },
+    utils::{DeviceFd, IsAlive, Logical, Point, Scale, Transform},
+    wayland::{
+        compositor,
+        dmabuf::{
+            DmabufFeedback, DmabufFeedbackBuilder, DmabufGlobal, DmabufHandler, DmabufState,
+            ImportNotifier,
+        },
     },
 };
+use smithay_drm_extras::{
+    drm_scanner::{DrmScanEvent, DrmScanner},
+    edid::EdidInfo,
+};
 
 use std::{
-    sync::{atomic::Ordering, Arc},
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::{atomic::Ordering, Mutex},
     time::Duration,
 };
 
 use crate::{
+    config::{config, DeviceType},
+    cursor::Cursor,
+    drawing::PointerElement,
+    image_capture::render_image_captures,
+    keyboard::add_keyboard,
+    render::{output_elements, CustomRenderElements},
+    screencopy::render_screencopies,
+    screenshot::render_screenshots,
+    shell::WindowElement,
     state::{Backend, SmallCageState},
     CalloopData,
 };
 
+// we cannot simply pick the first supported format of the intersection of *all* formats, because:
+// - we do not want something like Abgr4444, which looses color information, if something better is available
+// - some formats might perform terribly
+// - we might need some work-arounds, if one supports modifiers, but the other does not
+//
+// So lets just pick `ARGB2101010` (10-bit) or `ARGB8888` (8-bit) for now, they are widely supported.
+const SUPPORTED_FORMATS: &[Fourcc] = &[
+    Fourcc::Abgr2101010,
+    Fourcc::Argb2101010,
+    Fourcc::Abgr8888,
+    Fourcc::Argb8888,
+];
+
+type UdevRenderer<'a> = MultiRenderer<
+    'a,
+    'a,
+    GbmGlesBackend<GlesRenderer, DrmDeviceFd>,
+    GbmGlesBackend<GlesRenderer, DrmDeviceFd>,
+>;
+
+type GbmDrmCompositor =
+    DrmCompositor<GbmAllocator<DrmDeviceFd>, GbmDevice<DrmDeviceFd>, (), DrmDeviceFd>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UdevOutputId {
+    pub device_id: DrmNode,
+    pub crtc: crtc::Handle,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum DeviceAddError {
+    #[error("Failed to open device using libseat: {0}")]
+    DeviceOpen(smithay::backend::session::libseat::Error),
+    #[error("Failed to initialize drm device: {0}")]
+    DrmDevice(DrmError),
+    #[error("Failed to initialize gbm device: {0}")]
+    GbmDevice(std::io::Error),
+    #[error("Failed to access drm node: {0}")]
+    DrmNode(CreateDrmNodeError),
+    #[error("Failed to add device to GpuManager: {0}")]
+    AddNode(smithay::backend::egl::Error),
+    #[error("Failed to create an EGL display: {0}")]
+    EglDisplay(smithay::backend::egl::Error),
+    #[error("Failed to listen for drm events: {0}")]
+    InsertSource(smithay::reexports::calloop::Error),
+}
+
+/// The formats clients should allocate their buffers with to be shown on an
+/// output, with and without the compositor rendering them
+struct SurfaceDmabufFeedback {
+    render_feedback: DmabufFeedback,
+    scanout_feedback: DmabufFeedback,
+}
+
+struct SurfaceData {
+    dh: DisplayHandle,
+    global: Option<GlobalId>,
+    render_node: DrmNode,
+    compositor: GbmDrmCompositor,
+    /// `None` if the formats of the planes could not be read
+    dmabuf_feedback: Option<SurfaceDmabufFeedback>,
+}
+
+impl Drop for SurfaceData {
+    fn drop(&mut self) {
+        if let Some(global) = self.global.take() {
+            self.dh.remove_global::<SmallCageState<UdevData>>(global);
+        }
+    }
+}
+
+struct DeviceData {
+    surfaces: HashMap<crtc::Handle, SurfaceData>,
+    gbm: GbmDevice<DrmDeviceFd>,
+    drm: DrmDevice,
+    drm_scanner: DrmScanner,
+    render_node: DrmNode,
+    registration_token: RegistrationToken,
+}
+
 pub struct UdevData {
     pub session: LibSeatSession,
     dh: DisplayHandle,
     primary_gpu: DrmNode,
     gpus: GpuManager<GbmGlesBackend<GlesRenderer, DrmDeviceFd>>,
+    backends: HashMap<DrmNode, DeviceData>,
+    pointer_image: Cursor,
+    pointer_images: Vec<(xcursor::parser::Image, TextureBuffer<MultiTexture>)>,
+    /// The libinput devices which are plugged in
+    input_devices: Vec<input::Device>,
+    /// `None` until the primary gpu is set up
+    dmabuf_state: Option<(DmabufState, DmabufGlobal)>,
 }
 
 impl Backend for UdevData {
     fn seat_name(&self) -> String {
         self.session.seat()
     }
+
+    fn change_vt(&mut self, vt: i32) {
+        if let Err(err) = self.session.change_vt(vt) {
+            tracing::error!(vt, "Error changing vt: {}", err);
+        }
+    }
+
+    fn configure_input_devices(&mut self) {
+        for device in self.input_devices.iter_mut() {
+            configure_device(device);
+        }
+    }
+
+    fn reset_buffers(&mut self) {
+        for backend in self.backends.values_mut() {
+            for surface in backend.surfaces.values_mut() {
+                surface.compositor.reset_buffers();
+            }
+        }
+    }
+}
+
+/// The kinds of device `device` counts as for the input config
+fn device_types(device: &input::Device) -> Vec<DeviceType> {
+    let mut types = Vec::new();
+    if device.has_capability(DeviceCapability::Keyboard) {
+        types.push(DeviceType::Keyboard);
+    }
+    if device.has_capability(DeviceCapability::Pointer) {
+        // touchpads are the pointers which can be tapped
+        if device.config_tap_finger_count() > 0 {
+            types.push(DeviceType::Touchpad);
+        } else {
+            types.push(DeviceType::Pointer);
+        }
+    }
+    if device.has_capability(DeviceCapability::Touch) {
+        types.push(DeviceType::Touch);
+    }
+    if device.has_capability(DeviceCapability::TabletTool) {
+        types.push(DeviceType::Tablet);
+    }
+    types
+}
+
+/// Apply every entry of the input config which matches `device`, in order
+fn configure_device(device: &mut input::Device) {
+    let name = device.name().to_owned();
+    let types = device_types(device);
+    for settings in config().input.iter() {
+        if !settings.matches(&name, &types) {
+            continue;
+        }
+        let mut results = Vec::new();
+        if let Some(enabled) = settings.tap_to_click {
+            results.push(("tap-to-click", device.config_tap_set_enabled(enabled)));
+        }
+        if let Some(enabled) = settings.natural_scroll {
+            results.push((
+                "natural-scroll",
+                device.config_scroll_set_natural_scroll_enabled(enabled),
+            ));
+        }
+        if let Some(profile) = settings.accel_profile {
+            results.push(("accel-profile", device.config_accel_set_profile(profile)));
+        }
+        if let Some(speed) = settings.accel_speed {
+            results.push(("accel-speed", device.config_accel_set_speed(speed)));
+        }
+        if let Some(enabled) = settings.left_handed {
+            results.push(("left-handed", device.config_left_handed_set(enabled)));
+        }
+        if let Some(enabled) = settings.disable_while_typing {
+            results.push((
+                "disable-while-typing",
+                device.config_dwt_set_enabled(enabled),
+            ));
+        }
+        if let Some(method) = settings.scroll_method {
+            results.push(("scroll-method", device.config_scroll_set_method(method)));
+        }
+        for (setting, result) in results {
+            match result {
+                Ok(()) => {}
+                // entries for all devices hit some which do not have it
+                Err(input::DeviceConfigError::Unsupported) => {
+                    tracing::debug!(
+                        device = name,
+                        setting,
+                        "Input device does not support setting"
+                    );
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        device = name,
+                        setting,
+                        "Failed to configure input device: {:?}",
+                        err
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// The DRM device to render with instead of the primary GPU, from
+/// `SMALLCAGE_DRM_DEVICE`. `ANVIL_DRM_DEVICE`, which it was called before, is
+/// still read when the new name is not set.
+fn drm_device_override() -> Option<String> {
+    if let Ok(var) = std::env::var("SMALLCAGE_DRM_DEVICE") {
+        return Some(var);
+    }
+    let var = std::env::var("ANVIL_DRM_DEVICE").ok()?;
+    tracing::warn!("ANVIL_DRM_DEVICE is deprecated, set SMALLCAGE_DRM_DEVICE instead");
+    Some(var)
 }
 
 pub fn run_udev() -> Result<(), Box<dyn std::error::Error>> {
@@ -49,7 +304,7 @@ pub fn run_udev() -> Result<(), Box<dyn std::error::Error>> {
 
     let (session, notifier) = LibSeatSession::new()?;
 
-    let primary_gpu = if let Ok(var) = std::env::var("ANVIL_DRM_DEVICE") {
+    let primary_gpu = if let Some(var) = drm_device_override() {
         DrmNode::from_path(var).expect("Invalid drm device path")
     } else {
         primary_gpu(session.seat())
@@ -78,6 +333,11 @@ pub fn run_udev() -> Result<(), Box<dyn std::error::Error>> {
         dh: display_handle.clone(),
         primary_gpu,
         gpus,
+        backends: HashMap::new(),
+        pointer_image: Cursor::load(),
+        pointer_images: Vec::new(),
+        input_devices: Vec::new(),
+        dmabuf_state: None,
     };
 
     let mut state = SmallCageState::init(&mut event_loop, display, data);
@@ -97,34 +357,64 @@ pub fn run_udev() -> Result<(), Box<dyn std::error::Error>> {
     event_loop
         .handle()
         .insert_source(libinput_backend, move |mut event, _, data| {
-            //let dh = data.state.display_handle.clone();
-            if let InputEvent::DeviceAdded { device } = &mut event {
-                if device.has_capability(DeviceCapability::Keyboard) {}
-            } else if let InputEvent::DeviceRemoved { ref device } = event {
-                if device.has_capability(DeviceCapability::Keyboard) {}
+            match &mut event {
+                InputEvent::DeviceAdded { device } => data.state.input_device_added(device),
+                InputEvent::DeviceRemoved { device } => data.state.input_device_removed(device),
+                _ => {}
             }
             data.state.process_input_event(event);
         })
         .unwrap();
 
-    let handle = event_loop.handle();
-
     // NOTE: lession to session
     event_loop
         .handle()
         .insert_source(notifier, move |event, &mut (), data| match event {
-            SessionEvent::PauseSession => {}
-            SessionEvent::ActivateSession => {}
+            SessionEvent::PauseSession => {
+                libinput_context.suspend();
+                tracing::info!("pausing session");
+
+                for backend in data.state.backend_data.backends.values_mut() {
+                    backend.drm.pause();
+                }
+            }
+            SessionEvent::ActivateSession => {
+                tracing::info!("resuming session");
+
+                if let Err(err) = libinput_context.resume() {
+                    tracing::error!("Failed to resume libinput context: {:?}", err);
+                }
+                for (node, backend) in data.state.backend_data.backends.iter_mut() {
+                    // We leave the connectors as they are and reset the crtc state below,
+                    // which avoids a full modeset and the flickering that comes with it.
+                    if let Err(err) = backend.drm.activate(false) {
+                        tracing::error!("Failed to activate drm backend {}: {}", node, err);
+                        continue;
+                    }
+                    for surface in backend.surfaces.values_mut() {
+                        if let Err(err) = surface.compositor.reset_state() {
+                            tracing::warn!("Failed to reset drm surface state: {}", err);
+                        }
+                        // The primary plane is empty after a vt switch, so drop the
+                        // damage history to force a full redraw.
+                        surface.compositor.reset_buffers();
+                    }
+                    let node = *node;
+                    data.state
+                        .handle
+                        .insert_idle(move |data| data.state.render(node, None));
+                }
+            }
         })
         .unwrap();
 
     for (device_id, path) in udev_backend.device_list() {
-        //if let Err(err) = DrmNode::from_dev_id(device_id)
-        //    .map_err(DeviceAddE ::DrmNode)
-        //    .and_then(|node| state.device_added(node, path))
-        //{
-        //    error!("Skipping device {device_id}: {err}");
-        //}
+        if let Err(err) = DrmNode::from_dev_id(device_id)
+            .map_err(DeviceAddError::DrmNode)
+            .and_then(|node| state.device_added(node, path))
+        {
+            tracing::error!("Skipping device {device_id}: {err}");
+        }
     }
     state.shm_state.update_formats(
         state
@@ -146,17 +436,48 @@ pub fn run_udev() -> Result<(), Box<dyn std::error::Error>> {
         Err(err) => tracing::info!(?err, "Failed to initialize EGL hardware-acceleration"),
     }
 
-    // TODO: dmabuf
+    // clients get the formats of the primary gpu, the outputs refine them
+    // with per surface feedback
+    let dmabuf_formats = renderer.dmabuf_formats().collect::<Vec<_>>();
+    let default_feedback = DmabufFeedbackBuilder::new(primary_gpu.dev_id(), dmabuf_formats)
+        .build()
+        .unwrap();
+    let mut dmabuf_state = DmabufState::new();
+    let global = dmabuf_state.create_global_with_default_feedback::<SmallCageState<UdevData>>(
+        &display_handle,
+        &default_feedback,
+    );
+    state.backend_data.dmabuf_state = Some((dmabuf_state, global));
 
     event_loop
         .handle()
         .insert_source(udev_backend, move |event, _, data| match event {
-            UdevEvent::Added { device_id, path } => {}
-            UdevEvent::Changed { device_id } => {}
-            UdevEvent::Removed { device_id } => {}
+            UdevEvent::Added { device_id, path } => {
+                if let Err(err) = DrmNode::from_dev_id(device_id)
+                    .map_err(DeviceAddError::DrmNode)
+                    .and_then(|node| data.state.device_added(node, &path))
+                {
+                    tracing::error!("Skipping device {device_id}: {err}");
+                }
+            }
+            UdevEvent::Changed { device_id } => {
+                if let Ok(node) = DrmNode::from_dev_id(device_id) {
+                    data.state.device_changed(node)
+                }
+            }
+            UdevEvent::Removed { device_id } => {
+                if let Ok(node) = DrmNode::from_dev_id(device_id) {
+                    data.state.device_removed(node)
+                }
+            }
         })
         .unwrap();
 
+    std::env::set_var("WAYLAND_DISPLAY", &state.socket_name);
+    #[cfg(feature = "xwayland")]
+    state.start_xwayland();
+    state.run_autostart();
+
     // run the event loop
     while state.running.load(Ordering::SeqCst) {
         let mut calloop_data = CalloopData {
@@ -172,6 +493,8 @@ pub fn run_udev() -> Result<(), Box<dyn std::error::Error>> {
         if result.is_err() {
             state.running.store(false, Ordering::SeqCst);
         } else {
+            state.handle_focus_change();
+            state.refresh_foreign_toplevels();
             state.space.refresh();
             state.popups.cleanup();
             display_handle.flush_clients().unwrap();
@@ -179,3 +502,794 @@ pub fn run_udev() -> Result<(), Box<dyn std::error::Error>> {
     }
     Ok(())
 }
+
+impl SmallCageState<UdevData> {
+    fn input_device_added(&mut self, device: &mut input::Device) {
+        tracing::info!(device = device.name(), "Input device added");
+        configure_device(device);
+        self.backend_data.input_devices.push(device.clone());
+        self.update_seat_capabilities();
+    }
+
+    fn input_device_removed(&mut self, device: &input::Device) {
+        tracing::info!(device = device.name(), "Input device removed");
+        self.backend_data.input_devices.retain(|d| d != device);
+        self.update_seat_capabilities();
+    }
+
+    /// Offer clients a keyboard and a pointer only while there is a device
+    /// for them
+    fn update_seat_capabilities(&mut self) {
+        let has_capability = |capability| {
+            self.backend_data
+                .input_devices
+                .iter()
+                .any(|device| device.has_capability(capability))
+        };
+        let has_keyboard = has_capability(DeviceCapability::Keyboard);
+        let has_pointer = has_capability(DeviceCapability::Pointer);
+        let has_touch = has_capability(DeviceCapability::Touch);
+
+        match (has_keyboard, self.seat.get_keyboard().is_some()) {
+            (true, false) => add_keyboard(&mut self.seat),
+            (false, true) => self.seat.remove_keyboard(),
+            _ => {}
+        }
+        match (has_pointer, self.seat.get_pointer().is_some()) {
+            (true, false) => {
+                // the new pointer starts where the old one was left
+                let location = self.pointer.current_location();
+                self.pointer = self.seat.add_pointer();
+                self.pointer.set_location(location);
+            }
+            (false, true) => self.seat.remove_pointer(),
+            _ => {}
+        }
+        match (has_touch, self.seat.get_touch().is_some()) {
+            (true, false) => {
+                self.seat.add_touch();
+            }
+            (false, true) => self.seat.remove_touch(),
+            _ => {}
+        }
+    }
+
+    fn device_added(&mut self, node: DrmNode, path: &Path) -> Result<(), DeviceAddError> {
+        // Try to open the device
+        let fd = self
+            .backend_data
+            .session
+            .open(
+                path,
+                OFlags::RDWR | OFlags::CLOEXEC | OFlags::NOCTTY | OFlags::NONBLOCK,
+            )
+            .map_err(DeviceAddError::DeviceOpen)?;
+
+        let fd = DrmDeviceFd::new(DeviceFd::from(fd));
+
+        let (drm, notifier) =
+            DrmDevice::new(fd.clone(), true).map_err(DeviceAddError::DrmDevice)?;
+        let gbm = GbmDevice::new(fd).map_err(DeviceAddError::GbmDevice)?;
+
+        let display =
+            unsafe { EGLDisplay::new(gbm.clone()) }.map_err(DeviceAddError::EglDisplay)?;
+        let render_node = EGLDevice::device_for_display(&display)
+            .ok()
+            .and_then(|x| x.try_get_render_node().ok().flatten())
+            .unwrap_or(node);
+
+        self.backend_data
+            .gpus
+            .as_mut()
+            .add_node(render_node, gbm.clone())
+            .map_err(DeviceAddError::AddNode)?;
+
+        let registration_token = self
+            .handle
+            .insert_source(
+                notifier,
+                move |event, _metadata, data: &mut CalloopData<UdevData>| match event {
+                    DrmEvent::VBlank(crtc) => {
+                        profiling::scope!("vblank", &format!("{crtc:?}"));
+                        data.state.frame_finish(node, crtc);
+                    }
+                    DrmEvent::Error(error) => {
+                        tracing::error!("{:?}", error);
+                    }
+                },
+            )
+            .map_err(|err| DeviceAddError::InsertSource(err.error))?;
+
+        self.backend_data.backends.insert(
+            node,
+            DeviceData {
+                surfaces: HashMap::new(),
+                gbm,
+                drm,
+                drm_scanner: DrmScanner::new(),
+                render_node,
+                registration_token,
+            },
+        );
+
+        self.device_changed(node);
+
+        Ok(())
+    }
+
+    fn connector_connected(
+        &mut self,
+        node: DrmNode,
+        connector: connector::Info,
+        crtc: crtc::Handle,
+    ) {
+        let Some(device) = self.backend_data.backends.get_mut(&node) else {
+            return;
+        };
+
+        let mut renderer = self
+            .backend_data
+            .gpus
+            .single_renderer(&device.render_node)
+            .unwrap();
+        let render_formats = renderer
+            .as_mut()
+            .egl_context()
+            .dmabuf_render_formats()
+            .clone();
+
+        let output_name = format!(
+            "{}-{}",
+            connector.interface().as_str(),
+            connector.interface_id()
+        );
+        tracing::info!(?crtc, "Trying to setup connector {}", output_name);
+
+        let (make, model) = EdidInfo::for_connector(&device.drm, connector.handle())
+            .map(|info| (info.manufacturer, info.model))
+            .unwrap_or_else(|| ("Unknown".into(), "Unknown".into()));
+
+        let mode_id = connector
+            .modes()
+            .iter()
+            .position(|mode| mode.mode_type().contains(ModeTypeFlags::PREFERRED))
+            .unwrap_or(0);
+
+        let Some(drm_mode) = connector.modes().get(mode_id).copied() else {
+            tracing::warn!("Connector {} has no modes", output_name);
+            return;
+        };
+        let wl_mode = WlMode::from(drm_mode);
+
+        let surface = match device
+            .drm
+            .create_surface(crtc, drm_mode, &[connector.handle()])
+        {
+            Ok(surface) => surface,
+            Err(err) => {
+                tracing::warn!("Failed to create drm surface: {}", err);
+                return;
+            }
+        };
+
+        let (phys_w, phys_h) = connector.size().unwrap_or((0, 0));
+        let output = Output::new(
+            output_name,
+            PhysicalProperties {
+                size: (phys_w as i32, phys_h as i32).into(),
+                subpixel: Subpixel::Unknown,
+                make,
+                model,
+            },
+        );
+        let global = output.create_global::<SmallCageState<UdevData>>(&self.display_handle);
+
+        // place the new output right of the rightmost one, outputs may have
+        // left gaps behind when they were unplugged
+        let x = self
+            .space
+            .outputs()
+            .filter_map(|o| self.space.output_geometry(o))
+            .map(|geo| geo.loc.x + geo.size.w)
+            .max()
+            .unwrap_or(0);
+        let position = (x, 0).into();
+
+        output.set_preferred(wl_mode);
+        output.change_current_state(Some(wl_mode), None, None, Some(position));
+        self.space.map_output(&output, position);
+
+        output.user_data().insert_if_missing(|| UdevOutputId {
+            crtc,
+            device_id: node,
+        });
+
+        let allocator = GbmAllocator::new(
+            device.gbm.clone(),
+            GbmBufferFlags::RENDERING | GbmBufferFlags::SCANOUT,
+        );
+
+        let compositor = match DrmCompositor::new(
+            &output,
+            surface,
+            None,
+            allocator,
+            device.gbm.clone(),
+            SUPPORTED_FORMATS,
+            render_formats,
+            device.drm.cursor_size(),
+            Some(device.gbm.clone()),
+        ) {
+            Ok(compositor) => compositor,
+            Err(err) => {
+                tracing::warn!("Failed to create drm compositor: {}", err);
+                self.space.unmap_output(&output);
+                self.display_handle
+                    .remove_global::<SmallCageState<UdevData>>(global);
+                return;
+            }
+        };
+
+        let dmabuf_feedback = get_surface_dmabuf_feedback(
+            self.backend_data.primary_gpu,
+            device.render_node,
+            &mut self.backend_data.gpus,
+            &compositor,
+        );
+
+        device.surfaces.insert(
+            crtc,
+            SurfaceData {
+                dh: self.display_handle.clone(),
+                global: Some(global),
+                render_node: device.render_node,
+                compositor,
+                dmabuf_feedback,
+            },
+        );
+        self.map_orphaned_windows(&output);
+
+        // kick-off rendering
+        self.handle.insert_idle(move |data| {
+            data.state.render_surface(node, crtc);
+        });
+    }
+
+    fn connector_disconnected(
+        &mut self,
+        node: DrmNode,
+        connector: connector::Info,
+        crtc: crtc::Handle,
+    ) {
+        let Some(device) = self.backend_data.backends.get_mut(&node) else {
+            return;
+        };
+
+        tracing::info!(?crtc, "Connector {:?} disconnected", connector.handle());
+
+        device.surfaces.remove(&crtc);
+
+        let output = self
+            .space
+            .outputs()
+            .find(|o| {
+                o.user_data().get::<UdevOutputId>()
+                    == Some(&UdevOutputId {
+                        device_id: node,
+                        crtc,
+                    })
+            })
+            .cloned();
+
+        if let Some(output) = output {
+            self.remove_output(&output);
+        }
+    }
+
+    fn device_changed(&mut self, node: DrmNode) {
+        let Some(device) = self.backend_data.backends.get_mut(&node) else {
+            return;
+        };
+
+        for event in device.drm_scanner.scan_connectors(&device.drm) {
+            match event {
+                DrmScanEvent::Connected {
+                    connector,
+                    crtc: Some(crtc),
+                } => {
+                    self.connector_connected(node, connector, crtc);
+                }
+                DrmScanEvent::Disconnected {
+                    connector,
+                    crtc: Some(crtc),
+                } => {
+                    self.connector_disconnected(node, connector, crtc);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn device_removed(&mut self, node: DrmNode) {
+        let Some(device) = self.backend_data.backends.get_mut(&node) else {
+            return;
+        };
+
+        let crtcs: Vec<_> = device
+            .drm_scanner
+            .crtcs()
+            .map(|(info, crtc)| (info.clone(), crtc))
+            .collect();
+
+        for (connector, crtc) in crtcs {
+            self.connector_disconnected(node, connector, crtc);
+        }
+
+        tracing::debug!("Surfaces dropped");
+
+        // drop the backends on this side
+        if let Some(backend_data) = self.backend_data.backends.remove(&node) {
+            self.backend_data
+                .gpus
+                .as_mut()
+                .remove_node(&backend_data.render_node);
+
+            self.handle.remove(backend_data.registration_token);
+
+            tracing::debug!("Dropping device");
+        }
+    }
+
+    fn frame_finish(&mut self, node: DrmNode, crtc: crtc::Handle) {
+        let Some(device) = self.backend_data.backends.get_mut(&node) else {
+            tracing::error!("Trying to finish frame on non-existent backend {}", node);
+            return;
+        };
+
+        let Some(surface) = device.surfaces.get_mut(&crtc) else {
+            tracing::error!("Trying to finish frame on non-existent crtc {:?}", crtc);
+            return;
+        };
+
+        let schedule_render = match surface
+            .compositor
+            .frame_submitted()
+            .map_err(Into::<SwapBuffersError>::into)
+        {
+            Ok(_) => true,
+            Err(err) => {
+                tracing::warn!("Error during rendering: {:?}", err);
+                match err {
+                    SwapBuffersError::AlreadySwapped => true,
+                    // If the device has been deactivated do not reschedule, this will be done
+                    // by session resume
+                    SwapBuffersError::TemporaryFailure(err)
+                        if matches!(
+                            err.downcast_ref::<DrmError>(),
+                            Some(&DrmError::DeviceInactive)
+                        ) =>
+                    {
+                        false
+                    }
+                    SwapBuffersError::TemporaryFailure(err) => matches!(
+                        err.downcast_ref::<DrmError>(),
+                        Some(DrmError::Access { source, .. })
+                            if source.kind() == std::io::ErrorKind::PermissionDenied
+                    ),
+                    SwapBuffersError::ContextLost(err) => panic!("Rendering loop lost: {}", err),
+                }
+            }
+        };
+
+        if schedule_render {
+            self.render_surface(node, crtc);
+        }
+    }
+
+    fn render(&mut self, node: DrmNode, crtc: Option<crtc::Handle>) {
+        let Some(device) = self.backend_data.backends.get(&node) else {
+            return;
+        };
+
+        let crtcs: Vec<crtc::Handle> = match crtc {
+            Some(crtc) => vec![crtc],
+            None => device.surfaces.keys().copied().collect(),
+        };
+        for crtc in crtcs {
+            self.render_surface(node, crtc);
+        }
+    }
+
+    fn render_surface(&mut self, node: DrmNode, crtc: crtc::Handle) {
+        let Some(device) = self.backend_data.backends.get_mut(&node) else {
+            return;
+        };
+
+        let Some(surface) = device.surfaces.get_mut(&crtc) else {
+            return;
+        };
+
+        let Some(output) = self
+            .space
+            .outputs()
+            .find(|o| {
+                o.user_data().get::<UdevOutputId>()
+                    == Some(&UdevOutputId {
+                        device_id: node,
+                        crtc,
+                    })
+            })
+            .cloned()
+        else {
+            return;
+        };
+        let Some(output_geometry) = self.space.output_geometry(&output) else {
+            return;
+        };
+
+        let primary_gpu = self.backend_data.primary_gpu;
+        let render_node = surface.render_node;
+        let mut renderer = if primary_gpu == render_node {
+            self.backend_data.gpus.single_renderer(&render_node)
+        } else {
+            let format = surface.compositor.format();
+            self.backend_data
+                .gpus
+                .renderer(&primary_gpu, &render_node, format)
+        }
+        .unwrap();
+
+        let integer_scale = output.current_scale().integer_scale();
+        let scale = Scale::from(output.current_scale().fractional_scale());
+
+        let frame = self
+            .backend_data
+            .pointer_image
+            .get_image(integer_scale as u32, self.start_time.elapsed());
+        let pointer_images = &mut self.backend_data.pointer_images;
+        let pointer_image = pointer_images
+            .iter()
+            .find_map(|(image, texture)| (image == &frame).then(|| texture.clone()))
+            .unwrap_or_else(|| {
+                let texture = TextureBuffer::from_memory(
+                    &mut renderer,
+                    &frame.pixels_rgba,
+                    Fourcc::Abgr8888,
+                    (frame.width as i32, frame.height as i32),
+                    false,
+                    integer_scale,
+                    Transform::Normal,
+                    None,
+                )
+                .expect("Failed to import cursor bitmap");
+                pointer_images.push((frame.clone(), texture.clone()));
+                texture
+            });
+
+        let mut cursor_guard = self.cursor_status.lock().unwrap();
+
+        let mut reset = false;
+        if let CursorImageStatus::Surface(ref surface) = *cursor_guard {
+            reset = !surface.alive();
+        }
+        if reset {
+            *cursor_guard = CursorImageStatus::default_named();
+        }
+
+        let cursor_hotspot: Point<i32, Logical> =
+            if let CursorImageStatus::Surface(ref surface) = *cursor_guard {
+                compositor::with_states(surface, |states| {
+                    states
+                        .data_map
+                        .get::<Mutex<CursorImageAttributes>>()
+                        .unwrap()
+                        .lock()
+                        .unwrap()
+                        .hotspot
+                })
+            } else {
+                Point::from((frame.xhot as i32, frame.yhot as i32)).downscale(integer_scale)
+            };
+
+        let mut pointer_element = PointerElement::<MultiTexture>::default();
+        pointer_element.set_status(cursor_guard.clone());
+        pointer_element.set_texture(pointer_image);
+        drop(cursor_guard);
+
+        let cursor_pos = self.pointer.current_location()
+            - output_geometry.loc.to_f64()
+            - cursor_hotspot.to_f64();
+        let cursor_pos_scaled = cursor_pos.to_physical(scale).to_i32_round();
+
+        let mut elements = Vec::<CustomRenderElements<_>>::new();
+        elements.extend(pointer_element.render_elements(
+            &mut renderer,
+            cursor_pos_scaled,
+            scale,
+            1.0,
+        ));
+
+        render_screencopies(
+            &mut renderer,
+            &output,
+            &self.space,
+            self.show_window_preview,
+            |renderer| pointer_element.render_elements(renderer, cursor_pos_scaled, scale, 1.0),
+        );
+        render_image_captures(
+            &mut renderer,
+            &output,
+            &self.space,
+            self.show_window_preview,
+            |renderer| pointer_element.render_elements(renderer, cursor_pos_scaled, scale, 1.0),
+        );
+        render_screenshots(
+            &mut renderer,
+            &output,
+            &self.space,
+            self.show_window_preview,
+        );
+
+        let result = render_surface(
+            surface,
+            &mut renderer,
+            &self.space,
+            &output,
+            elements,
+            self.show_window_preview,
+            self.show_damage_tint,
+        );
+
+        let reschedule = match result {
+            Ok(rendered) => !rendered,
+            Err(err) => {
+                tracing::warn!("Error during rendering: {:?}", err);
+                match err {
+                    SwapBuffersError::AlreadySwapped => false,
+                    SwapBuffersError::TemporaryFailure(err) => match err.downcast_ref::<DrmError>()
+                    {
+                        // session resume renders again, like in frame_finish
+                        Some(DrmError::DeviceInactive) => false,
+                        Some(DrmError::Access { source, .. }) => {
+                            source.kind() == std::io::ErrorKind::PermissionDenied
+                        }
+                        _ => false,
+                    },
+                    SwapBuffersError::ContextLost(err) => panic!("Rendering loop lost: {}", err),
+                }
+            }
+        };
+
+        // only the surfaces shown on this output the most are called back,
+        // the others follow the refresh of their own output
+        self.space.elements().for_each(|window| {
+            if !self.space.outputs_for_element(window).contains(&output) {
+                return;
+            }
+            window.send_frame(
+                &output,
+                self.start_time.elapsed(),
+                Some(Duration::ZERO),
+                surface_primary_scanout_output,
+            )
+        });
+        for layer in layer_map_for_output(&output).layers() {
+            layer.send_frame(
+                &output,
+                self.start_time.elapsed(),
+                Some(Duration::ZERO),
+                surface_primary_scanout_output,
+            );
+        }
+
+        if reschedule {
+            // Nothing was submitted, so there will be no vblank to drive the next frame.
+            // Try again after one refresh cycle.
+            let frame_duration = output
+                .current_mode()
+                .map(|mode| Duration::from_secs_f64(1_000f64 / mode.refresh as f64))
+                .unwrap_or(Duration::from_millis(16));
+            let timer = Timer::from_duration(frame_duration);
+            self.handle
+                .insert_source(timer, move |_, _, data| {
+                    data.state.render_surface(node, crtc);
+                    TimeoutAction::Drop
+                })
+                .expect("failed to schedule frame timer");
+        }
+    }
+}
+
+fn render_surface<'a>(
+    surface: &'a mut SurfaceData,
+    renderer: &mut UdevRenderer<'a>,
+    space: &Space<WindowElement>,
+    output: &Output,
+    custom_elements: Vec<CustomRenderElements<UdevRenderer<'a>>>,
+    show_window_preview: bool,
+    show_damage_tint: bool,
+) -> Result<bool, SwapBuffersError> {
+    let (elements, clear_color) = output_elements(
+        output,
+        space,
+        custom_elements,
+        renderer,
+        show_window_preview,
+        show_damage_tint,
+    );
+
+    let res = surface
+        .compositor
+        .render_frame::<_, _, GlesTexture>(renderer, &elements, clear_color)
+        .map_err(|err| match err {
+            RenderFrameError::PrepareFrame(err) => SwapBuffersError::from(err),
+            RenderFrameError::RenderFrame(OutputDamageTrackerError::Rendering(err)) => {
+                SwapBuffersError::from(err)
+            }
+            _ => unreachable!(),
+        })?;
+
+    if res.needs_sync() {
+        if let PrimaryPlaneElement::Swapchain(element) = res.primary_element {
+            element.sync.wait();
+        }
+    }
+
+    update_primary_scanout_outputs(space, output, &res.states);
+    if let Some(feedback) = surface.dmabuf_feedback.as_ref() {
+        send_dmabuf_feedback(space, output, &res.states, feedback);
+    }
+
+    let rendered = !res.is_empty;
+    if rendered {
+        surface
+            .compositor
+            .queue_frame(())
+            .map_err(Into::<SwapBuffersError>::into)?;
+    }
+
+    Ok(rendered)
+}
+
+/// Remember on the surfaces drawn to `output` whether it is the output they
+/// are shown on the most, their frame callbacks are sent with its refresh
+fn update_primary_scanout_outputs(
+    space: &Space<WindowElement>,
+    output: &Output,
+    states: &RenderElementStates,
+) {
+    let update = |surface: &_, surface_data: &_| {
+        update_primary_scanout_output(
+            surface,
+            output,
+            surface_data,
+            states,
+            default_primary_scanout_output_compare,
+        );
+    };
+    for window in space.elements() {
+        window.with_surfaces(update);
+    }
+    for layer in layer_map_for_output(output).layers() {
+        layer.with_surfaces(update);
+    }
+}
+
+/// Tell the clients on `output` which formats could be scanned out directly,
+/// surfaces which made it onto a plane get the scanout tranche
+fn send_dmabuf_feedback(
+    space: &Space<WindowElement>,
+    output: &Output,
+    states: &RenderElementStates,
+    feedback: &SurfaceDmabufFeedback,
+) {
+    let select = |surface: &_, _: &_| {
+        select_dmabuf_feedback(
+            surface,
+            states,
+            &feedback.render_feedback,
+            &feedback.scanout_feedback,
+        )
+    };
+    space.elements().for_each(|window| {
+        window.send_dmabuf_feedback(output, |_, _| Some(output.clone()), select);
+    });
+    for layer in layer_map_for_output(output).layers() {
+        layer.send_dmabuf_feedback(output, |_, _| Some(output.clone()), select);
+    }
+}
+
+fn get_surface_dmabuf_feedback(
+    primary_gpu: DrmNode,
+    render_node: DrmNode,
+    gpus: &mut GpuManager<GbmGlesBackend<GlesRenderer, DrmDeviceFd>>,
+    compositor: &GbmDrmCompositor,
+) -> Option<SurfaceDmabufFeedback> {
+    let primary_formats = gpus
+        .single_renderer(&primary_gpu)
+        .ok()?
+        .dmabuf_formats()
+        .collect::<HashSet<_>>();
+    let render_formats = gpus
+        .single_renderer(&render_node)
+        .ok()?
+        .dmabuf_formats()
+        .collect::<HashSet<_>>();
+    let all_render_formats = primary_formats
+        .iter()
+        .chain(render_formats.iter())
+        .copied()
+        .collect::<HashSet<_>>();
+
+    // only formats which can be rendered as well go into the scanout
+    // tranche, the buffer has to be composited if it misses the planes
+    let surface = compositor.surface();
+    let planes = surface.planes();
+    let planes_formats = surface
+        .supported_formats(planes.primary.handle)
+        .ok()?
+        .into_iter()
+        .chain(
+            planes
+                .overlay
+                .iter()
+                .flat_map(|plane| surface.supported_formats(plane.handle).unwrap_or_default()),
+        )
+        .collect::<HashSet<_>>()
+        .intersection(&all_render_formats)
+        .copied()
+        .collect::<Vec<_>>();
+
+    let builder = DmabufFeedbackBuilder::new(primary_gpu.dev_id(), primary_formats);
+    let render_feedback = builder
+        .clone()
+        .add_preference_tranche(render_node.dev_id(), None, render_formats.clone())
+        .build()
+        .ok()?;
+    let scanout_feedback = builder
+        .add_preference_tranche(
+            surface.device_fd().dev_id().ok()?,
+            Some(zwp_linux_dmabuf_feedback_v1::TrancheFlags::Scanout),
+            planes_formats,
+        )
+        .add_preference_tranche(render_node.dev_id(), None, render_formats)
+        .build()
+        .ok()?;
+
+    Some(SurfaceDmabufFeedback {
+        render_feedback,
+        scanout_feedback,
+    })
+}
+
+impl DmabufHandler for SmallCageState<UdevData> {
+    fn dmabuf_state(&mut self) -> &mut DmabufState {
+        &mut self.backend_data.dmabuf_state.as_mut().unwrap().0
+    }
+
+    fn dmabuf_imported(
+        &mut self,
+        _global: &DmabufGlobal,
+        dmabuf: Dmabuf,
+        notifier: ImportNotifier,
+    ) {
+        // buffers the primary gpu cannot import are refused right away,
+        // instead of failing later when they are rendered
+        let primary_gpu = self.backend_data.primary_gpu;
+        let imported = self
+            .backend_data
+            .gpus
+            .single_renderer(&primary_gpu)
+            .and_then(|mut renderer| renderer.import_dmabuf(&dmabuf, None))
+            .is_ok();
+        if imported {
+            dmabuf.set_node(primary_gpu);
+            let _ = notifier.successful::<SmallCageState<UdevData>>();
+        } else {
+            tracing::debug!("Refused a dmabuf the primary gpu cannot import");
+            notifier.failed();
+        }
+    }
+}
+
+delegate_dmabuf!(SmallCageState<UdevData>);
@@ -10,26 +10,41 @@ use smithay::{
         },
         winit::{self, WinitEvent},
     },
+    desktop::layer_map_for_output,
     input::pointer::{CursorImageAttributes, CursorImageStatus},
     output::{Mode, Output, PhysicalProperties, Subpixel},
     reexports::{calloop::EventLoop, wayland_server::Display},
-    utils::{IsAlive, Rectangle, Scale, Transform},
+    utils::{IsAlive, Scale, Transform},
     wayland::compositor,
 };
 
 use crate::{
     drawing::PointerElement,
+    image_capture::render_image_captures,
     render::{render_output, CustomRenderElements},
-    state::Backend,
+    screencopy::render_screencopies,
+    screenshot::render_screenshots,
+    state::{Backend, BaseTransform},
     CalloopData, SmallCageState,
 };
 
-pub struct WinitData;
+#[derive(Debug, Default)]
+pub struct WinitData {
+    full_redraw: bool,
+}
 
 impl Backend for WinitData {
     fn seat_name(&self) -> String {
         "winit".to_owned()
     }
+
+    fn change_vt(&mut self, _vt: i32) {}
+
+    fn reset_buffers(&mut self) {
+        self.full_redraw = true;
+    }
+
+    fn configure_input_devices(&mut self) {}
 }
 
 pub fn run_winit() -> Result<(), Box<dyn std::error::Error>> {
@@ -37,7 +52,7 @@ pub fn run_winit() -> Result<(), Box<dyn std::error::Error>> {
 
     let display = Display::new()?;
     let display_handle = display.handle();
-    let state = SmallCageState::init(&mut event_loop, display, WinitData);
+    let state = SmallCageState::init(&mut event_loop, display, WinitData::default());
 
     let mut data = CalloopData {
         state,
@@ -46,13 +61,16 @@ pub fn run_winit() -> Result<(), Box<dyn std::error::Error>> {
 
     init_winit(&mut event_loop, &mut data)?;
 
-    std::process::Command::new("kitty").spawn().ok();
+    #[cfg(feature = "xwayland")]
+    data.state.start_xwayland();
+    data.state.run_autostart();
 
     event_loop.run(
         Some(std::time::Duration::from_secs(1)),
         &mut data,
         move |w| {
             w.state.handle_focus_change();
+            w.state.refresh_foreign_toplevels();
         },
     )?;
 
@@ -84,6 +102,10 @@ pub fn init_winit(
         },
     );
     let _global = output.create_global::<SmallCageState<WinitData>>(display_handle);
+    // the GL framebuffer of the window is upside down
+    output
+        .user_data()
+        .insert_if_missing(|| BaseTransform(Transform::Flipped180));
     output.change_current_state(
         Some(mode),
         Some(Transform::Flipped180),
@@ -119,7 +141,7 @@ pub fn init_winit(
                         None,
                         None,
                     );
-                    state.resize_elements(size);
+                    state.resize_elements(&output);
                 }
                 WinitEvent::Input(event) => state.process_input_event(event),
                 WinitEvent::Redraw => {
@@ -164,28 +186,63 @@ pub fn init_winit(
                         scale,
                         1.0,
                     ));
-
-                    // TODO: handle result
-                    render_output(
+                    render_screencopies(
+                        renderer,
                         &output,
                         &state.space,
-                        elements,
+                        state.show_window_preview,
+                        |renderer| {
+                            pointer_element.render_elements(renderer, cursor_pos_scaled, scale, 1.0)
+                        },
+                    );
+                    render_image_captures(
                         renderer,
-                        &mut damage_tracker,
-                        0,
-                        false,
-                    )
-                    .map_err(|error| match error {
-                        OutputDamageTrackerError::Rendering(err) => err,
-                        _ => unreachable!(),
-                    })
-                    .unwrap();
-
-                    let size = backend.window_size();
-                    let damage = Rectangle::from_loc_and_size((0, 0), size);
-                    backend.bind().unwrap();
-
-                    backend.submit(Some(&[damage])).unwrap();
+                        &output,
+                        &state.space,
+                        state.show_window_preview,
+                        |renderer| {
+                            pointer_element.render_elements(renderer, cursor_pos_scaled, scale, 1.0)
+                        },
+                    );
+                    render_screenshots(renderer, &output, &state.space, state.show_window_preview);
+
+                    let full_redraw = std::mem::take(&mut state.backend_data.full_redraw);
+                    let render_result = backend.bind().and_then(|_| {
+                        // an age of 0 makes the damage tracker redraw everything
+                        let age = if full_redraw {
+                            0
+                        } else {
+                            backend.buffer_age().unwrap_or(0)
+                        };
+                        render_output(
+                            &output,
+                            &state.space,
+                            elements,
+                            backend.renderer(),
+                            &mut damage_tracker,
+                            age,
+                            state.show_window_preview,
+                            state.show_damage_tint,
+                        )
+                        .map_err(|error| match error {
+                            OutputDamageTrackerError::Rendering(err) => err.into(),
+                            _ => unreachable!(),
+                        })
+                    });
+
+                    match render_result {
+                        // only hand the regions which changed to the compositor
+                        Ok(result) => {
+                            if let Some(damage) = result.damage {
+                                if let Err(err) = backend.submit(Some(&*damage)) {
+                                    tracing::warn!("Failed to submit buffer: {}", err);
+                                }
+                            }
+                        }
+                        Err(err) => {
+                            tracing::warn!("Rendering error: {}", err);
+                        }
+                    }
 
                     state.space.elements().for_each(|window| {
                         window.send_frame(
@@ -195,6 +252,14 @@ pub fn init_winit(
                             |_, _| Some(output.clone()),
                         )
                     });
+                    for layer in layer_map_for_output(&output).layers() {
+                        layer.send_frame(
+                            &output,
+                            state.start_time.elapsed(),
+                            Some(Duration::ZERO),
+                            |_, _| Some(output.clone()),
+                        );
+                    }
 
                     backend.window().set_cursor_visible(cursor_visible);
 
@@ -0,0 +1,257 @@
+use std::cell::RefMut;
+
+use smithay::{
+    output::Output,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Point, SERIAL_COUNTER},
+};
+
+use crate::{
+    layout::Tiling,
+    shell::WindowElement,
+    state::{output_state, Backend, SmallCageState},
+};
+
+/// Every output has this many workspaces, bound to Super+1..9
+pub const WORKSPACE_COUNT: usize = 9;
+
+#[derive(Default)]
+struct Workspace {
+    tiling: Tiling<WindowElement>,
+    /// Untiled windows which are put away while the workspace is hidden,
+    /// together with the location they go back to
+    floating: Vec<(WindowElement, Point<i32, Logical>)>,
+}
+
+/// The workspaces of one output, only the active one is mapped in the `Space`
+pub struct Workspaces {
+    workspaces: Vec<Workspace>,
+    active: usize,
+}
+
+impl Default for Workspaces {
+    fn default() -> Self {
+        Self {
+            workspaces: (0..WORKSPACE_COUNT).map(|_| Workspace::default()).collect(),
+            active: 0,
+        }
+    }
+}
+
+impl Workspaces {
+    /// All the windows of a workspace, mapped or not
+    fn windows(&self, index: usize) -> impl Iterator<Item = &WindowElement> {
+        let workspace = &self.workspaces[index];
+        workspace
+            .tiling
+            .windows()
+            .iter()
+            .chain(workspace.floating.iter().map(|(window, _)| window))
+    }
+}
+
+fn output_workspaces(output: &Output) -> RefMut<'_, Workspaces> {
+    output_state(output)
+}
+
+/// The index of the workspace `output` shows
+pub fn active_workspace(output: &Output) -> usize {
+    output_workspaces(output).active
+}
+
+/// The tiling state of the workspace with `index` on `output`
+pub fn workspace_tiling(output: &Output, index: usize) -> RefMut<'_, Tiling<WindowElement>> {
+    RefMut::map(output_workspaces(output), |workspaces| {
+        &mut workspaces.workspaces[index].tiling
+    })
+}
+
+/// The tiling state of the workspace `output` shows
+pub fn output_tiling(output: &Output) -> RefMut<'_, Tiling<WindowElement>> {
+    RefMut::map(output_workspaces(output), |workspaces| {
+        let active = workspaces.active;
+        &mut workspaces.workspaces[active].tiling
+    })
+}
+
+impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
+    /// Find the window of a toplevel surface, also on hidden workspaces
+    pub fn find_window(&self, surface: &WlSurface) -> Option<WindowElement> {
+        self.find_window_by(|w| w.has_surface(surface))
+    }
+
+    /// Find the first window `predicate` holds for, also on hidden workspaces
+    /// and among the windows waiting for an output
+    pub fn find_window_by(
+        &self,
+        predicate: impl Fn(&WindowElement) -> bool,
+    ) -> Option<WindowElement> {
+        if let Some(window) = self.space.elements().find(|w| predicate(w)) {
+            return Some(window.clone());
+        }
+        self.space
+            .outputs()
+            .find_map(|output| {
+                let workspaces = output_workspaces(output);
+                let window = (0..WORKSPACE_COUNT)
+                    .flat_map(|index| workspaces.windows(index))
+                    .find(|w| predicate(w))
+                    .cloned();
+                window
+            })
+            .or_else(|| {
+                self.orphaned_windows
+                    .iter()
+                    .map(|(_, w)| w)
+                    .find(|w| predicate(w))
+                    .cloned()
+            })
+    }
+
+    /// Show the workspace with `index` on `output`, the windows of the
+    /// workspace shown before are unmapped.
+    pub fn switch_workspace(&mut self, output: &Output, index: usize) {
+        let current = active_workspace(output);
+        if index >= WORKSPACE_COUNT || index == current {
+            return;
+        }
+        tracing::info!(
+            output = output.name(),
+            workspace = index + 1,
+            "Switch workspace"
+        );
+
+        let hidden: Vec<WindowElement> = self
+            .space
+            .elements()
+            .filter(|w| w.output().as_ref() == Some(output) && w.workspace() == current)
+            .cloned()
+            .collect();
+        for window in hidden {
+            if window.is_untiled_window() {
+                let location = self.space.element_location(&window).unwrap_or_default();
+                output_workspaces(output).workspaces[current]
+                    .floating
+                    .push((window.clone(), location));
+            }
+            self.space.unmap_elem(&window);
+        }
+
+        let floating = {
+            let mut workspaces = output_workspaces(output);
+            workspaces.active = index;
+            std::mem::take(&mut workspaces.workspaces[index].floating)
+        };
+        for (window, location) in floating {
+            self.space.map_element(window, location, false);
+        }
+        self.relayout_output(output);
+        self.focus_top_window(output);
+    }
+
+    /// Give the keyboard to the topmost window shown on `output`
+    fn focus_top_window(&mut self, output: &Output) {
+        let focus = self
+            .space
+            .elements()
+            .filter(|w| w.output().as_ref() == Some(output))
+            .last()
+            .cloned();
+        if let Some(window) = focus.as_ref() {
+            self.space.raise_element(window, true);
+            self.raise_untiled_elements();
+        } else {
+            self.space.elements().for_each(|window| {
+                window.set_activated(false);
+            });
+        }
+        self.space.elements().for_each(|window| {
+            window.send_pending_configure();
+        });
+        if let Some(keyboard) = self.seat.get_keyboard() {
+            keyboard.set_focus(self, focus.map(Into::into), SERIAL_COUNTER.next_serial());
+        }
+    }
+
+    /// Move `window` to the workspace with `index` on `output`, it is only
+    /// mapped if that workspace is shown.
+    pub fn move_window_to_workspace(
+        &mut self,
+        window: &WindowElement,
+        output: &Output,
+        index: usize,
+    ) {
+        if index >= WORKSPACE_COUNT
+            || (window.output().as_ref() == Some(output) && window.workspace() == index)
+        {
+            return;
+        }
+
+        // take the window out of the workspace it is on now
+        if let Some(old) = window.output() {
+            let from = window.workspace();
+            let was_shown = active_workspace(&old) == from;
+            {
+                let mut workspaces = output_workspaces(&old);
+                let workspace = &mut workspaces.workspaces[from];
+                workspace.tiling.remove(window);
+                workspace.floating.retain(|(w, _)| w != window);
+            }
+            self.space.unmap_elem(window);
+            if was_shown {
+                self.relayout_output(&old);
+                if old != *output || index != active_workspace(output) {
+                    self.focus_top_window(&old);
+                }
+            }
+        }
+
+        window.set_output(Some(output.clone()));
+        if index == active_workspace(output) {
+            if window.is_untiled_window() {
+                self.map_untitled_element(window);
+            } else {
+                self.resize_element_commit(window);
+            }
+            self.raise_untiled_elements();
+            return;
+        }
+
+        window.set_workspace(index);
+        if window.is_untiled_window() {
+            let location = self.untiled_location(window, output).unwrap_or_default();
+            output_workspaces(output).workspaces[index]
+                .floating
+                .push((window.clone(), location));
+        } else {
+            workspace_tiling(output, index).insert(window.clone(), None, self.splitstate);
+        }
+    }
+
+    /// Take a closed window out of the workspace it was on
+    pub fn remove_from_workspace(&mut self, window: &WindowElement) {
+        self.orphaned_windows.retain(|(_, w)| w != window);
+        let Some(output) = window.output() else {
+            return;
+        };
+        let index = window.workspace();
+        let removed = {
+            let mut workspaces = output_workspaces(&output);
+            let workspace = &mut workspaces.workspaces[index];
+            workspace.floating.retain(|(w, _)| w != window);
+            workspace.tiling.remove(window)
+        };
+        if removed && active_workspace(&output) == index {
+            self.relayout_output(&output);
+        }
+    }
+
+    /// The windows on the hidden workspaces of `output`, with their workspace
+    pub fn hidden_workspace_windows(&self, output: &Output) -> Vec<(usize, WindowElement)> {
+        let workspaces = output_workspaces(output);
+        (0..WORKSPACE_COUNT)
+            .filter(|index| *index != workspaces.active)
+            .flat_map(|index| workspaces.windows(index).map(move |w| (index, w.clone())))
+            .collect()
+    }
+}
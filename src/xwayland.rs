@@ -0,0 +1,672 @@
+//! X11 applications run on XWayland and smallcage is its window manager: X11
+//! windows become [`WindowElement`]s and are tiled like every other window,
+//! while menus and tooltips stay where their application put them.
+//!
+//! The X display is reserved when the compositor starts, but XWayland is
+//! only spawned once the first X11 client connects to it. The X server is
+//! handed the listening sockets with `-listenfd`, so it accepts that first
+//! connection itself. Once it exits, the display is watched again and the
+//! next X11 client spawns a new one.
+
+use std::{
+    fs::OpenOptions,
+    io::{ErrorKind, Read, Write},
+    os::{
+        fd::{AsRawFd, BorrowedFd, OwnedFd, RawFd},
+        linux::net::SocketAddrExt,
+        unix::{
+            ffi::OsStrExt,
+            net::{SocketAddr, UnixListener, UnixStream},
+            process::CommandExt,
+        },
+    },
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::Arc,
+    time::Duration,
+};
+
+use smithay::{
+    desktop::space::SpaceElement,
+    input::pointer::{Focus, GrabStartData as PointerGrabStartData},
+    reexports::{
+        calloop::{
+            channel::{self, Event as ChannelEvent},
+            generic::Generic,
+            Interest, Mode, PostAction, RegistrationToken,
+        },
+        rustix::io::{fcntl_setfd, FdFlags},
+        wayland_server::Client,
+    },
+    utils::{Logical, Point, Rectangle, Size, SERIAL_COUNTER},
+    wayland::selection::{
+        data_device::{
+            clear_data_device_selection, current_data_device_selection_userdata,
+            request_data_device_client_selection, set_data_device_selection,
+        },
+        primary_selection::{
+            clear_primary_selection, current_primary_selection_userdata,
+            request_primary_client_selection, set_primary_selection,
+        },
+        SelectionTarget,
+    },
+    xwayland::{
+        xwm::{Reorder, ResizeEdge as X11ResizeEdge, XwmId},
+        X11Surface, X11Wm, XwmHandler,
+    },
+};
+
+use crate::{
+    cursor::Cursor,
+    focus::FocusTarget,
+    grabs::{
+        NormalMoveSurfaceGrab, ResizeEdge, ResizeSurfaceGrab, TileMoveSurfaceGrab,
+        TileResizeSurfaceGrab,
+    },
+    ipc::Event,
+    shell::WindowElement,
+    state::{Backend, ClientState, SmallCageState},
+    workspace::active_workspace,
+    CalloopData,
+};
+
+const X11_SOCKET_DIR: &str = "/tmp/.X11-unix";
+/// Displays which are tried, like other X servers do
+const MAX_DISPLAY: u32 = 32;
+
+/// The X display reserved for XWayland, with the sockets X11 clients
+/// connect to
+#[derive(Debug)]
+pub struct XDisplay {
+    number: u32,
+    lock_path: PathBuf,
+    socket_path: PathBuf,
+    listeners: Vec<UnixListener>,
+    /// The sources which spawn XWayland on the next connection, empty while
+    /// it runs
+    watchers: Vec<RegistrationToken>,
+    /// The source which tells when XWayland exits
+    server: Option<RegistrationToken>,
+}
+
+/// A spawned Xwayland with the connections smallcage keeps to it
+struct XServer {
+    process: Child,
+    /// The connection of the window manager
+    wm: UnixStream,
+    /// The Wayland connection of the X server
+    wayland: UnixStream,
+    /// Where the X server writes its display number once it is ready
+    ready: UnixStream,
+}
+
+impl Drop for XDisplay {
+    fn drop(&mut self) {
+        // the socket file is only ours if it was bound
+        if !self.listeners.is_empty() {
+            let _ = std::fs::remove_file(&self.socket_path);
+        }
+        let _ = std::fs::remove_file(&self.lock_path);
+    }
+}
+
+impl XDisplay {
+    /// Take the first free display
+    fn reserve() -> std::io::Result<Self> {
+        std::fs::create_dir_all(X11_SOCKET_DIR)?;
+        for number in 0..MAX_DISPLAY {
+            match Self::claim(number) {
+                Ok(Some(display)) => return Ok(display),
+                Ok(None) => continue,
+                Err(err) => tracing::debug!(display = number, "X display is taken: {}", err),
+            }
+        }
+        Err(std::io::Error::new(
+            ErrorKind::AddrInUse,
+            "all X displays are taken",
+        ))
+    }
+
+    /// Take a display, its lock file tells other X servers that it is in
+    /// use. `None` if another one has it already.
+    fn claim(number: u32) -> std::io::Result<Option<Self>> {
+        let lock_path = PathBuf::from(format!("/tmp/.X{}-lock", number));
+        let mut lock = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+        {
+            Ok(lock) => lock,
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let mut display = XDisplay {
+            number,
+            lock_path,
+            socket_path: PathBuf::from(format!("{}/X{}", X11_SOCKET_DIR, number)),
+            listeners: Vec::new(),
+            watchers: Vec::new(),
+            server: None,
+        };
+        lock.write_all(format!("{:>10}\n", std::process::id()).as_bytes())?;
+        display.bind()?;
+        Ok(Some(display))
+    }
+
+    /// Listen on the socket file and on the abstract socket, which X11
+    /// clients on Linux try first
+    fn bind(&mut self) -> std::io::Result<()> {
+        self.listeners.push(UnixListener::bind(&self.socket_path)?);
+        let address = SocketAddr::from_abstract_name(self.socket_path.as_os_str().as_bytes())?;
+        self.listeners.push(UnixListener::bind_addr(&address)?);
+        // connections are only accepted here to turn them away
+        for listener in &self.listeners {
+            listener.set_nonblocking(true)?;
+        }
+        Ok(())
+    }
+
+    /// Spawn Xwayland on the display, it accepts the connections to the
+    /// listening sockets from now on
+    fn spawn_server(&self) -> std::io::Result<XServer> {
+        let (wm, server_wm) = UnixStream::pair()?;
+        let (wayland, server_wayland) = UnixStream::pair()?;
+        let (ready, server_ready) = UnixStream::pair()?;
+
+        let mut command = Command::new("Xwayland");
+        command
+            .arg(format!(":{}", self.number))
+            .arg("-rootless")
+            .arg("-wm")
+            .arg(server_wm.as_raw_fd().to_string())
+            .arg("-displayfd")
+            .arg(server_ready.as_raw_fd().to_string())
+            .env("WAYLAND_SOCKET", server_wayland.as_raw_fd().to_string())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        for listener in &self.listeners {
+            command
+                .arg("-listenfd")
+                .arg(listener.as_raw_fd().to_string());
+        }
+
+        // all of them are opened close-on-exec
+        let inherited: Vec<RawFd> = [&server_wm, &server_wayland, &server_ready]
+            .iter()
+            .map(|stream| stream.as_raw_fd())
+            .chain(self.listeners.iter().map(AsRawFd::as_raw_fd))
+            .collect();
+        // Safety: only fcntl is called between fork and exec, on descriptors
+        // which stay open until the command is spawned
+        unsafe {
+            command.pre_exec(move || {
+                for fd in &inherited {
+                    fcntl_setfd(BorrowedFd::borrow_raw(*fd), FdFlags::empty())?;
+                }
+                Ok(())
+            });
+        }
+        let process = command.spawn()?;
+        Ok(XServer {
+            process,
+            wm,
+            wayland,
+            ready,
+        })
+    }
+
+    /// Close the connections which waited for an X server that did not
+    /// come up, so they fail instead of spawning it over and over
+    fn refuse_pending(&self) {
+        for listener in &self.listeners {
+            while listener.accept().is_ok() {}
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> SmallCageState<BackendData> {
+    /// Reserve an X display and point `DISPLAY` at it. XWayland is spawned
+    /// with the first connection to it, the window manager once the X
+    /// server is ready.
+    pub fn start_xwayland(&mut self) {
+        let display = match XDisplay::reserve() {
+            Ok(display) => display,
+            Err(err) => {
+                tracing::warn!("Failed to reserve an X display: {}", err);
+                return;
+            }
+        };
+        std::env::set_var("DISPLAY", format!(":{}", display.number));
+        tracing::info!(display = display.number, "Reserved the X display");
+        self.xdisplay = Some(display);
+        self.watch_xdisplay();
+    }
+
+    /// Spawn XWayland once a client connects to the display
+    fn watch_xdisplay(&mut self) {
+        let Some(display) = self.xdisplay.as_mut() else {
+            return;
+        };
+        for listener in &display.listeners {
+            let watcher = match listener.try_clone() {
+                Ok(watcher) => watcher,
+                Err(err) => {
+                    tracing::warn!("Failed to watch the X display: {}", err);
+                    break;
+                }
+            };
+            let result = self.handle.insert_source(
+                Generic::new(watcher, Interest::READ, Mode::Level),
+                |_, _, data| {
+                    // the watchers are removed outside of their callbacks
+                    data.state
+                        .handle
+                        .insert_idle(|data| data.state.spawn_xwayland());
+                    Ok(PostAction::Continue)
+                },
+            );
+            match result {
+                Ok(token) => display.watchers.push(token),
+                Err(err) => {
+                    tracing::warn!("Failed to watch the X display: {}", err.error);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn spawn_xwayland(&mut self) {
+        let Some(display) = self
+            .xdisplay
+            .as_mut()
+            .filter(|display| !display.watchers.is_empty())
+        else {
+            return;
+        };
+        for token in display.watchers.drain(..) {
+            self.handle.remove(token);
+        }
+        let number = display.number;
+
+        let server = match display.spawn_server() {
+            Ok(server) => server,
+            Err(err) => {
+                tracing::warn!("Failed to start XWayland: {}", err);
+                display.refuse_pending();
+                self.handle.insert_idle(|data| data.state.watch_xdisplay());
+                return;
+            }
+        };
+        let XServer {
+            mut process,
+            wm,
+            wayland,
+            ready,
+        } = server;
+        tracing::info!(display = number, "Starting XWayland");
+
+        let client = match self
+            .display_handle
+            .insert_client(wayland, Arc::new(ClientState::default()))
+        {
+            Ok(client) => client,
+            Err(err) => {
+                tracing::warn!("Failed to add XWayland as a client: {}", err);
+                let _ = process.kill();
+                let _ = process.wait();
+                display.refuse_pending();
+                self.handle.insert_idle(|data| data.state.watch_xdisplay());
+                return;
+            }
+        };
+
+        // the X server is waited for on a thread, its exit is handled on
+        // the event loop
+        let (exited, exit) = channel::channel();
+        std::thread::spawn(move || {
+            let _ = process.wait();
+            let _ = exited.send(());
+        });
+        match self.handle.insert_source(exit, |event, _, data| {
+            if let ChannelEvent::Msg(()) = event {
+                data.state
+                    .handle
+                    .insert_idle(|data| data.state.xwayland_exited());
+            }
+        }) {
+            Ok(token) => display.server = Some(token),
+            Err(err) => tracing::warn!("Failed to wait for XWayland to exit: {}", err.error),
+        }
+
+        let mut wm = Some(wm);
+        let result = self.handle.insert_source(
+            Generic::new(ready, Interest::READ, Mode::Level),
+            move |_, ready, data| {
+                // the display number is written once the server is ready,
+                // an exit before is handled with the exit of the process
+                let mut reader: &UnixStream = ready.as_ref();
+                let mut buffer = [0u8; 16];
+                if matches!(reader.read(&mut buffer), Ok(read) if read > 0) {
+                    if let Some(wm) = wm.take() {
+                        data.state.start_xwm(wm, client.clone());
+                    }
+                }
+                Ok(PostAction::Remove)
+            },
+        );
+        if let Err(err) = result {
+            tracing::warn!("Failed to wait for XWayland: {}", err.error);
+        }
+    }
+
+    /// Watch the display again after XWayland went away, so the next X11
+    /// client starts it again
+    fn xwayland_exited(&mut self) {
+        let Some(display) = self.xdisplay.as_mut() else {
+            return;
+        };
+        if let Some(token) = display.server.take() {
+            self.handle.remove(token);
+        }
+        if self.xwm.take().is_some() {
+            tracing::warn!(display = display.number, "XWayland exited");
+        } else {
+            tracing::warn!(
+                display = display.number,
+                "XWayland exited before it was ready"
+            );
+            display.refuse_pending();
+        }
+        self.watch_xdisplay();
+    }
+
+    fn start_xwm(&mut self, connection: UnixStream, client: Client) {
+        let mut xwm = match X11Wm::start_wm(self.handle.clone(), connection, client) {
+            Ok(xwm) => xwm,
+            Err(err) => {
+                tracing::warn!("Failed to start the X11 window manager: {}", err);
+                return;
+            }
+        };
+        // the root window shows the same cursor as the compositor
+        let image = Cursor::load().get_image(1, Duration::ZERO);
+        if let Err(err) = xwm.set_cursor(
+            &image.pixels_rgba,
+            Size::from((image.width as u16, image.height as u16)),
+            Point::from((image.xhot as u16, image.yhot as u16)),
+        ) {
+            tracing::warn!("Failed to set the X11 cursor: {}", err);
+        }
+        self.xwm = Some(xwm);
+        tracing::info!("XWayland is ready");
+    }
+
+    /// The pointer grab X11 windows move or resize themselves with, the
+    /// button has to be held on the window itself
+    fn x11_grab_start_data(
+        &self,
+        window: &WindowElement,
+    ) -> Option<PointerGrabStartData<SmallCageState<BackendData>>> {
+        let start_data = self.pointer.grab_start_data()?;
+        let focus = start_data
+            .focus
+            .as_ref()
+            .and_then(|(focus, _)| focus.window());
+        (focus == Some(window)).then_some(start_data)
+    }
+
+    /// Find the window of an X11 surface, also on hidden workspaces
+    fn find_x11_window(&self, surface: &X11Surface) -> Option<WindowElement> {
+        self.find_window_by(|w| w.x11_surface() == Some(surface))
+    }
+}
+
+impl<BackendData: Backend + 'static> XwmHandler for CalloopData<BackendData> {
+    fn xwm_state(&mut self, _xwm: XwmId) -> &mut X11Wm {
+        self.state.xwm.as_mut().unwrap()
+    }
+
+    fn new_window(&mut self, _xwm: XwmId, _window: X11Surface) {}
+
+    fn new_override_redirect_window(&mut self, _xwm: XwmId, _window: X11Surface) {}
+
+    fn map_window_request(&mut self, _xwm: XwmId, surface: X11Surface) {
+        if let Err(err) = surface.set_mapped(true) {
+            tracing::warn!("Failed to map the X11 window: {}", err);
+            return;
+        }
+        let window = WindowElement::new_x11(surface);
+        // X11 windows are configured up front, there is no initial commit
+        // to wait for like with xdg toplevels
+        let (min_size, max_size) = (window.min_size(), window.max_size());
+        if min_size == max_size && max_size != (0, 0).into() {
+            window.set_is_fixed_window();
+        }
+        window.set_inited();
+        self.state.ipc_event(Event::WindowOpened {
+            id: window.ipc_id(),
+        });
+        if window.is_fixed_window() {
+            self.state.map_untitled_element(&window);
+        } else {
+            self.state.resize_element_commit(&window);
+        }
+        self.state.raise_untiled_elements();
+    }
+
+    fn mapped_override_redirect_window(&mut self, _xwm: XwmId, surface: X11Surface) {
+        let location = surface.geometry().loc;
+        let output = self
+            .state
+            .space
+            .output_under(location.to_f64())
+            .next()
+            .cloned()
+            .or_else(|| self.state.current_output());
+        // menus and tooltips float above everything else on the workspace
+        let window = WindowElement::new_x11(surface);
+        window.set_is_fixed_window();
+        window.set_inited();
+        if let Some(output) = output.as_ref() {
+            window.set_workspace(active_workspace(output));
+        }
+        window.set_output(output);
+        self.state.space.map_element(window, location, true);
+    }
+
+    fn unmapped_window(&mut self, _xwm: XwmId, surface: X11Surface) {
+        let Some(window) = self.state.find_x11_window(&surface) else {
+            return;
+        };
+        self.state.remove_from_workspace(&window);
+        self.state.space.unmap_elem(&window);
+        if surface.is_override_redirect() {
+            return;
+        }
+        if let Err(err) = surface.set_mapped(false) {
+            tracing::warn!("Failed to unmap the X11 window: {}", err);
+        }
+        self.state.ipc_event(Event::WindowClosed {
+            id: window.ipc_id(),
+        });
+    }
+
+    fn destroyed_window(&mut self, _xwm: XwmId, _window: X11Surface) {}
+
+    fn configure_request(
+        &mut self,
+        _xwm: XwmId,
+        surface: X11Surface,
+        x: Option<i32>,
+        y: Option<i32>,
+        w: Option<u32>,
+        h: Option<u32>,
+        _reorder: Option<Reorder>,
+    ) {
+        let mut geometry = surface.geometry();
+        // tiled windows keep the geometry of their tile, they are only told
+        // about it again
+        let tiled = self
+            .state
+            .find_x11_window(&surface)
+            .is_some_and(|window| !window.is_untiled_window());
+        if !tiled {
+            geometry.loc.x = x.unwrap_or(geometry.loc.x);
+            geometry.loc.y = y.unwrap_or(geometry.loc.y);
+            geometry.size.w = w.map_or(geometry.size.w, |w| w as i32);
+            geometry.size.h = h.map_or(geometry.size.h, |h| h as i32);
+        }
+        if let Err(err) = surface.configure(geometry) {
+            tracing::warn!("Failed to configure the X11 window: {}", err);
+        }
+    }
+
+    fn configure_notify(
+        &mut self,
+        _xwm: XwmId,
+        surface: X11Surface,
+        geometry: Rectangle<i32, Logical>,
+        _above: Option<u32>,
+    ) {
+        // untiled windows may move themselves
+        let Some(window) = self
+            .state
+            .find_x11_window(&surface)
+            .filter(WindowElement::is_untiled_window)
+        else {
+            return;
+        };
+        if self.state.space.element_location(&window) == Some(geometry.loc) {
+            return;
+        }
+        self.state.space.map_element(window, geometry.loc, false);
+    }
+
+    fn resize_request(
+        &mut self,
+        _xwm: XwmId,
+        surface: X11Surface,
+        _button: u32,
+        resize_edge: X11ResizeEdge,
+    ) {
+        let Some(window) = self.state.find_x11_window(&surface) else {
+            return;
+        };
+        let Some(start_data) = self.state.x11_grab_start_data(&window) else {
+            return;
+        };
+        let pointer = self.state.pointer.clone();
+        let serial = SERIAL_COUNTER.next_serial();
+        let edges = ResizeEdge::from(resize_edge);
+
+        // tiled windows are resized by moving the splits around them
+        if !window.is_untiled_window() {
+            let Some(output) = window.output() else {
+                return;
+            };
+            let grab = TileResizeSurfaceGrab::start(start_data, window, output, edges);
+            pointer.set_grab(&mut self.state, grab, serial, Focus::Clear);
+            return;
+        }
+
+        let Some(location) = self.state.space.element_location(&window) else {
+            return;
+        };
+        let size = window.geometry().size;
+        let grab = ResizeSurfaceGrab::start(
+            start_data,
+            window,
+            edges,
+            Rectangle::from_loc_and_size(location, size),
+        );
+        pointer.set_grab(&mut self.state, grab, serial, Focus::Clear);
+    }
+
+    fn move_request(&mut self, _xwm: XwmId, surface: X11Surface, _button: u32) {
+        let Some(window) = self.state.find_x11_window(&surface) else {
+            return;
+        };
+        let Some(start_data) = self.state.x11_grab_start_data(&window) else {
+            return;
+        };
+        let pointer = self.state.pointer.clone();
+        let serial = SERIAL_COUNTER.next_serial();
+
+        // tiled windows are dropped into the tile under the pointer
+        if !window.is_untiled_window() {
+            let Some(output) = window.output() else {
+                return;
+            };
+            let grab = TileMoveSurfaceGrab::start(start_data, window, output);
+            pointer.set_grab(&mut self.state, grab, serial, Focus::Clear);
+            return;
+        }
+
+        let Some(initial_window_location) = self.state.space.element_location(&window) else {
+            return;
+        };
+        let grab = NormalMoveSurfaceGrab {
+            start_data,
+            window,
+            initial_window_location,
+        };
+        pointer.set_grab(&mut self.state, grab, serial, Focus::Clear);
+    }
+
+    fn allow_selection_access(&mut self, xwm: XwmId, _selection: SelectionTarget) -> bool {
+        // only the focused X11 window may read or set the selections
+        let Some(keyboard) = self.state.seat.get_keyboard() else {
+            return false;
+        };
+        matches!(
+            keyboard.current_focus(),
+            Some(FocusTarget::Window(window))
+                if window.x11_surface().and_then(X11Surface::xwm_id) == Some(xwm)
+        )
+    }
+
+    fn send_selection(
+        &mut self,
+        _xwm: XwmId,
+        selection: SelectionTarget,
+        mime_type: String,
+        fd: OwnedFd,
+    ) {
+        let seat = &self.state.seat;
+        let result = match selection {
+            SelectionTarget::Clipboard => request_data_device_client_selection(seat, mime_type, fd),
+            SelectionTarget::Primary => request_primary_client_selection(seat, mime_type, fd),
+        };
+        if let Err(err) = result {
+            tracing::warn!(?selection, "Failed to hand the selection to X11: {}", err);
+        }
+    }
+
+    fn new_selection(&mut self, _xwm: XwmId, selection: SelectionTarget, mime_types: Vec<String>) {
+        let dh = &self.state.display_handle;
+        let seat = &self.state.seat;
+        match selection {
+            SelectionTarget::Clipboard => set_data_device_selection(dh, seat, mime_types, ()),
+            SelectionTarget::Primary => set_primary_selection(dh, seat, mime_types, ()),
+        }
+    }
+
+    fn cleared_selection(&mut self, _xwm: XwmId, selection: SelectionTarget) {
+        // the selection is only cleared if it still is the one of X11
+        let dh = &self.state.display_handle;
+        let seat = &self.state.seat;
+        match selection {
+            SelectionTarget::Clipboard => {
+                if current_data_device_selection_userdata(seat).is_some() {
+                    clear_data_device_selection(dh, seat);
+                }
+            }
+            SelectionTarget::Primary => {
+                if current_primary_selection_userdata(seat).is_some() {
+                    clear_primary_selection(dh, seat);
+                }
+            }
+        }
+    }
+}